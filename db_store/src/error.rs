@@ -22,6 +22,10 @@ pub enum Error {
     JoinError(#[from] tokio::task::JoinError),
     #[error("invalid auth token, does not start with http")]
     InvalidAuthToken(),
+    #[error("migration error: {0}")]
+    MigrateError(#[from] sqlx::migrate::MigrateError),
+    #[error("incompatible database schema: {0}")]
+    IncompatibleSchema(String),
 }
 
 pub fn invalid_configuration(str: impl Into<String>) -> Error {
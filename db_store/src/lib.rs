@@ -8,6 +8,7 @@ pub use error::{Error, Result};
 pub use settings::Settings;
 
 pub mod meta;
+pub mod schema;
 
 /// A key-value pair that is stored in the metadata table.
 pub struct MetaValue<T> {
@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{marker::PhantomData, str::FromStr};
 
 use crate::{Error, Result};
 
@@ -48,3 +48,72 @@ where
         .ok_or_else(|| Error::NotFound(key.to_string()))
         .and_then(|value| value.parse().map_err(|_| Error::DecodeError))
 }
+
+/// A key of the meta table along with the type of the value stored under it.
+/// All keys used by the services are declared in [`keys`] so that no two
+/// services disagree on the encoding of a shared value.
+#[derive(Debug, Clone, Copy)]
+pub struct MetaKey<T> {
+    key: &'static str,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> MetaKey<T> {
+    pub const fn new(key: &'static str) -> Self {
+        Self {
+            key,
+            value: PhantomData,
+        }
+    }
+
+    pub fn key(&self) -> &'static str {
+        self.key
+    }
+}
+
+impl<T> MetaKey<T>
+where
+    T: FromStr,
+{
+    pub async fn fetch(&self, exec: impl sqlx::PgExecutor<'_>) -> Result<T> {
+        fetch(exec, self.key).await
+    }
+
+    pub async fn fetch_optional(&self, exec: impl sqlx::PgExecutor<'_>) -> Result<Option<T>> {
+        match fetch(exec, self.key).await {
+            Ok(value) => Ok(Some(value)),
+            Err(Error::NotFound(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl<T> MetaKey<T>
+where
+    T: ToString,
+{
+    pub async fn store(&self, exec: impl sqlx::PgExecutor<'_>, value: T) -> Result {
+        store(exec, self.key, value).await
+    }
+}
+
+/// Registry of the meta keys used by the services
+pub mod keys {
+    use super::MetaKey;
+
+    /// End of the last rewarded period, in unix seconds
+    pub const LAST_REWARDED_END_TIME: MetaKey<i64> = MetaKey::new("last_rewarded_end_time");
+    /// End of the next period to be rewarded, in unix seconds
+    pub const NEXT_REWARDED_END_TIME: MetaKey<i64> = MetaKey::new("next_rewarded_end_time");
+    /// Version of the emission schedule the last rewarded period was computed
+    /// against
+    pub const LAST_REWARDED_EMISSION_SCHEDULE_VERSION: MetaKey<String> =
+        MetaKey::new("last_rewarded_emission_schedule_version");
+    /// Reward periods ending before this time, in unix seconds, are rewarded
+    /// without checking that all of their data has arrived
+    pub const DISABLE_COMPLETE_DATA_CHECKS_UNTIL: MetaKey<i64> =
+        MetaKey::new("disable_complete_data_checks_until");
+    /// End of the last window of poc reports loaded by the iot verifier, in
+    /// unix millis
+    pub const IOT_VERIFIER_REPORT_WINDOW_END: MetaKey<i64> = MetaKey::new("report");
+}
@@ -0,0 +1,248 @@
+//! Checks of a database schema against the migrations embedded in a service
+//! binary. Services apply their migrations through [`migrate`], which refuses
+//! to run against a database migrated by a newer binary so that a rolling
+//! deploy can't leave old code running against a schema it doesn't know.
+use crate::{Error, Result, Settings};
+use sqlx::{
+    migrate::{Migrate, Migration, Migrator},
+    PgPool,
+};
+use std::{borrow::Cow, collections::HashMap, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    /// Applied as embedded in the binary
+    Applied,
+    /// Embedded in the binary but not yet applied
+    Pending,
+    /// Applied with a checksum differing from that embedded in the binary
+    Modified,
+    /// Applied but not embedded in the binary, ie by a newer binary
+    Unknown,
+}
+
+impl fmt::Display for MigrationState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Applied => "applied",
+            Self::Pending => "pending",
+            Self::Modified => "modified",
+            Self::Unknown => "unknown",
+        };
+        f.write_str(s)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: Cow<'static, str>,
+    pub state: MigrationState,
+}
+
+#[derive(Debug, Clone)]
+pub struct SchemaStatus {
+    pub migrations: Vec<MigrationStatus>,
+    /// Version of a migration which failed part way through
+    pub dirty_version: Option<i64>,
+}
+
+impl SchemaStatus {
+    fn from_applied<'a>(
+        migrations: impl IntoIterator<Item = &'a Migration>,
+        mut applied: HashMap<i64, Vec<u8>>,
+        dirty_version: Option<i64>,
+    ) -> Self {
+        let mut statuses: Vec<MigrationStatus> = migrations
+            .into_iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .map(|migration| {
+                let state = match applied.remove(&migration.version) {
+                    Some(checksum) if checksum == *migration.checksum => MigrationState::Applied,
+                    Some(_) => MigrationState::Modified,
+                    None => MigrationState::Pending,
+                };
+                MigrationStatus {
+                    version: migration.version,
+                    description: migration.description.clone(),
+                    state,
+                }
+            })
+            .collect();
+        statuses.extend(applied.into_keys().map(|version| MigrationStatus {
+            version,
+            description: Cow::Borrowed("<not in this binary>"),
+            state: MigrationState::Unknown,
+        }));
+        statuses.sort_by_key(|status| status.version);
+        Self {
+            migrations: statuses,
+            dirty_version,
+        }
+    }
+
+    fn versions(&self, state: MigrationState) -> Vec<i64> {
+        self.migrations
+            .iter()
+            .filter(|migration| migration.state == state)
+            .map(|migration| migration.version)
+            .collect()
+    }
+
+    /// Error unless this binary can safely migrate and use the database
+    pub fn verify_compatible(&self) -> Result {
+        if let Some(version) = self.dirty_version {
+            return Err(Error::IncompatibleSchema(format!(
+                "migration {version} is partially applied"
+            )));
+        }
+        let unknown = self.versions(MigrationState::Unknown);
+        if !unknown.is_empty() {
+            return Err(Error::IncompatibleSchema(format!(
+                "schema is newer than this binary, unknown migrations {unknown:?}"
+            )));
+        }
+        let modified = self.versions(MigrationState::Modified);
+        if !modified.is_empty() {
+            return Err(Error::IncompatibleSchema(format!(
+                "applied migrations {modified:?} differ from this binary"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Error unless the database is compatible and fully migrated
+    pub fn verify_current(&self) -> Result {
+        self.verify_compatible()?;
+        let pending = self.versions(MigrationState::Pending);
+        if !pending.is_empty() {
+            return Err(Error::IncompatibleSchema(format!(
+                "pending migrations {pending:?}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub async fn status(pool: &PgPool, migrator: &Migrator) -> Result<SchemaStatus> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let dirty_version = conn.dirty_version().await?;
+    let applied = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| (migration.version, migration.checksum.into_owned()))
+        .collect();
+    Ok(SchemaStatus::from_applied(
+        migrator.iter(),
+        applied,
+        dirty_version,
+    ))
+}
+
+/// Apply any pending migrations, refusing to touch a database which has
+/// been migrated by a newer binary
+pub async fn migrate(pool: &PgPool, migrator: &Migrator) -> Result {
+    status(pool, migrator).await?.verify_compatible()?;
+    migrator.run(pool).await?;
+    Ok(())
+}
+
+/// Inspect and apply the database migrations embedded in this binary
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    #[clap(subcommand)]
+    cmd: MigrateCmd,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum MigrateCmd {
+    /// List the state of every migration
+    Status,
+    /// Apply pending migrations
+    Up,
+    /// Exit with an error unless the database is fully migrated by this
+    /// binary
+    Verify,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: &Settings, app_name: &str, migrator: &Migrator) -> Result {
+        let (shutdown_trigger, shutdown) = triggered::trigger();
+        let (pool, _db_handle) = settings.connect(app_name, shutdown).await?;
+        let result = match self.cmd {
+            MigrateCmd::Status => status(&pool, migrator).await.map(|status| {
+                for migration in &status.migrations {
+                    println!(
+                        "{:>4} {:<9} {}",
+                        migration.version, migration.state, migration.description
+                    );
+                }
+                if let Some(version) = status.dirty_version {
+                    println!("migration {version} is partially applied");
+                }
+            }),
+            MigrateCmd::Up => migrate(&pool, migrator).await,
+            MigrateCmd::Verify => status(&pool, migrator)
+                .await
+                .and_then(|status| status.verify_current()),
+        };
+        shutdown_trigger.trigger();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::migrate::MigrationType;
+
+    fn migration(version: i64, sql: &'static str) -> Migration {
+        Migration::new(
+            version,
+            Cow::Owned(format!("migration_{version}")),
+            MigrationType::Simple,
+            Cow::Borrowed(sql),
+        )
+    }
+
+    #[test]
+    fn schema_status_against_binary() {
+        let migrations = [
+            migration(1, "create table a (id int);"),
+            migration(2, "create table b (id int);"),
+            migration(3, "create table c (id int);"),
+        ];
+        let checksum = |migration: &Migration| migration.checksum.to_vec();
+
+        // behind the binary
+        let applied = HashMap::from([(1, checksum(&migrations[0]))]);
+        let status = SchemaStatus::from_applied(&migrations, applied, None);
+        assert_eq!(vec![2, 3], status.versions(MigrationState::Pending));
+        assert!(status.verify_compatible().is_ok());
+        assert!(status.verify_current().is_err());
+
+        // up to date
+        let applied: HashMap<i64, Vec<u8>> = migrations
+            .iter()
+            .map(|migration| (migration.version, checksum(migration)))
+            .collect();
+        let status = SchemaStatus::from_applied(&migrations, applied.clone(), None);
+        assert!(status.verify_current().is_ok());
+
+        // ahead of the binary
+        let mut ahead = applied.clone();
+        ahead.insert(4, vec![0]);
+        let status = SchemaStatus::from_applied(&migrations, ahead, None);
+        assert_eq!(vec![4], status.versions(MigrationState::Unknown));
+        assert!(status.verify_compatible().is_err());
+
+        // an applied migration has since been edited
+        let mut modified = applied;
+        modified.insert(2, vec![0]);
+        let status = SchemaStatus::from_applied(&migrations, modified, None);
+        assert_eq!(vec![2], status.versions(MigrationState::Modified));
+        assert!(status.verify_compatible().is_err());
+    }
+}
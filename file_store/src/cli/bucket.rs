@@ -1,13 +1,15 @@
 use crate::{
-    heartbeat::CellHeartbeat, iot_beacon_report::IotBeaconIngestReport, iot_valid_poc::IotPoc,
-    iot_witness_report::IotWitnessIngestReport, speedtest::CellSpeedtest, traits::MsgDecode, Error,
-    FileInfoStream, FileStore, FileType, Result, Settings,
+    file_checksum, heartbeat::CellHeartbeat, iot_beacon_report::IotBeaconIngestReport,
+    iot_valid_poc::IotPoc, iot_witness_report::IotWitnessIngestReport, speedtest::CellSpeedtest,
+    traits::MsgDecode, Error, FileChecksum, FileInfo, FileInfoStream, FileStore, FileType, Result,
+    Settings,
 };
-use chrono::{NaiveDateTime, TimeZone, Utc};
-use futures::{stream::TryStreamExt, StreamExt, TryFutureExt};
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use futures::{future, stream::TryStreamExt, StreamExt, TryFutureExt};
 use helium_crypto::PublicKey;
 use serde::{ser::SerializeSeq, Serializer};
 use std::{
+    collections::HashSet,
     io,
     path::{Path, PathBuf},
 };
@@ -27,6 +29,7 @@ pub enum BucketCmd {
     Put(Put),
     Get(Get),
     Locate(Locate),
+    Copy(Copy),
 }
 
 impl Cmd {
@@ -43,6 +46,7 @@ impl BucketCmd {
             Self::Put(cmd) => cmd.run(settings).await,
             Self::Get(cmd) => cmd.run(settings).await,
             Self::Locate(cmd) => cmd.run(settings).await,
+            Self::Copy(cmd) => cmd.run(settings).await,
         }
     }
 }
@@ -64,11 +68,15 @@ struct FileFilter {
 
 impl FileFilter {
     fn list(&self, store: &FileStore) -> FileInfoStream {
-        store.list(
-            self.file_type,
-            self.after.as_ref().map(|dt| Utc.from_utc_datetime(dt)),
-            self.before.as_ref().map(|dt| Utc.from_utc_datetime(dt)),
-        )
+        store.list(self.file_type, self.after(), self.before())
+    }
+
+    fn after(&self) -> Option<DateTime<Utc>> {
+        self.after.as_ref().map(|dt| Utc.from_utc_datetime(dt))
+    }
+
+    fn before(&self) -> Option<DateTime<Utc>> {
+        self.before.as_ref().map(|dt| Utc.from_utc_datetime(dt))
     }
 }
 
@@ -156,6 +164,7 @@ impl Get {
                             let mut reader = tokio_util::io::StreamReader::new(stream);
                             tokio::io::copy(&mut reader, &mut file)
                                 .map_err(Error::from)
+                                .map_err(file_checksum::map_digest_mismatch)
                                 .await
                         })
                     })
@@ -167,6 +176,97 @@ impl Get {
     }
 }
 
+/// Copy files from the configured bucket to the bucket of another settings
+/// file. Files already present in the destination are skipped, so an
+/// interrupted copy can be resumed by running it again with the same
+/// arguments. Source files are verified against their checksum while copied
+/// and keep that checksum in the destination.
+#[derive(Debug, clap::Args)]
+pub struct Copy {
+    /// Settings file for the destination bucket
+    #[clap(long)]
+    dest: PathBuf,
+    #[clap(flatten)]
+    filter: FileFilter,
+    /// Number of files to transfer concurrently
+    #[clap(long, default_value = "5")]
+    workers: usize,
+    /// Optional new start time for the copied files. The timestamp in the key
+    /// of every copied file is shifted by the difference between this time and
+    /// --after, moving the data into a new time window.
+    #[clap(long, requires = "after")]
+    shift_to: Option<NaiveDateTime>,
+}
+
+impl Copy {
+    pub async fn run(&self, settings: &Settings) -> Result {
+        let source = FileStore::from_settings(settings).await?;
+        let dest = FileStore::from_settings(&Settings::new(&self.dest)?).await?;
+        self.copy(&source, &dest).await
+    }
+
+    async fn copy(&self, source: &FileStore, dest: &FileStore) -> Result {
+        let offset = match (self.shift_to, self.filter.after) {
+            (Some(shift_to), Some(after)) => shift_to - after,
+            _ => Duration::zero(),
+        };
+
+        let existing: HashSet<String> = dest
+            .list(
+                self.filter.file_type,
+                self.filter.after().map(|dt| dt + offset),
+                self.filter.before().map(|dt| dt + offset),
+            )
+            .map_ok(|info| info.key)
+            .try_collect()
+            .await?;
+        let work_dir = tempfile::TempDir::new()?;
+        let work_dir = work_dir.path();
+
+        self.filter
+            .list(source)
+            .map_ok(|info| {
+                let dest_key = shifted_key(&info, offset);
+                (info, dest_key)
+            })
+            .try_filter(|(_, dest_key)| future::ready(!existing.contains(dest_key)))
+            .try_for_each_concurrent(self.workers, |(info, dest_key)| async move {
+                let path = work_dir.join(&dest_key);
+                let mut file = fs::File::create(&path).await?;
+                let (stream, checksum) = source.get_raw_verified(info.key.clone()).await?;
+                let mut reader = tokio_util::io::StreamReader::new(stream);
+                tokio::io::copy(&mut reader, &mut file)
+                    .await
+                    .map_err(|err| file_checksum::map_digest_mismatch(Error::from(err)))?;
+                drop(file);
+                // The bytes are unchanged, so a recorded checksum still holds
+                let checksum = match checksum {
+                    Some(checksum) => checksum,
+                    None => FileChecksum::from_path(&path).await?,
+                };
+                dest.put_with_checksum(&path, &checksum).await?;
+                fs::remove_file(&path).await?;
+                println!("{} -> {dest_key}", info.key);
+                Ok(())
+            })
+            .await
+    }
+}
+
+/// The key for a file with its timestamp shifted by the given offset. Any
+/// suffix after the timestamp, like the compression extension, is kept.
+fn shifted_key(info: &FileInfo, offset: Duration) -> String {
+    let prefix = format!("{}.{}", info.file_type, info.timestamp.timestamp_millis());
+    match info.key.strip_prefix(&prefix) {
+        Some(suffix) if offset != Duration::zero() => format!(
+            "{}.{}{suffix}",
+            info.file_type,
+            (info.timestamp + offset).timestamp_millis()
+        ),
+        _ => info.key.clone(),
+    }
+}
+
 /// Locate specific records in a time range
 #[derive(Debug, clap::Args)]
 pub struct Locate {
@@ -283,3 +383,155 @@ impl Gateway for IotPoc {
         self.beacon_report.report.pub_key.as_ref() == pub_key
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{local_store::LocalStore, Compression};
+    use async_compression::tokio::write::GzipEncoder;
+    use chrono::NaiveDate;
+    use tempfile::TempDir;
+    use tokio::io::AsyncWriteExt;
+
+    fn start() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 3, 28)
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .unwrap()
+    }
+
+    async fn local_store(dir: &TempDir) -> FileStore {
+        FileStore::new(
+            "local",
+            LocalStore::new(dir.path())
+                .await
+                .expect("failed to create local store"),
+        )
+    }
+
+    async fn put_file(store: &FileStore, timestamp: NaiveDateTime) -> String {
+        let src_dir = TempDir::new().expect("Unable to create temp dir");
+        let info = FileInfo::from((
+            FileType::EntropyReport,
+            Utc.from_utc_datetime(&timestamp),
+            Compression::Gzip,
+        ));
+        let mut encoder = GzipEncoder::new(Vec::new());
+        encoder
+            .write_all(&[0, 0, 0, 4])
+            .await
+            .expect("failed to encode frame");
+        encoder
+            .write_all(b"data")
+            .await
+            .expect("failed to encode frame");
+        encoder.shutdown().await.expect("failed to encode frame");
+        let path = src_dir.path().join(&info.key);
+        fs::write(&path, encoder.into_inner())
+            .await
+            .expect("failed to write file");
+        store.put(&path).await.expect("failed to put file");
+        info.key
+    }
+
+    fn copy_cmd(shift_to: Option<NaiveDateTime>) -> Copy {
+        Copy {
+            dest: PathBuf::new(),
+            filter: FileFilter {
+                after: Some(start() - Duration::hours(1)),
+                before: None,
+                file_type: FileType::EntropyReport,
+            },
+            workers: 2,
+            shift_to,
+        }
+    }
+
+    #[test]
+    fn shifted_key_keeps_the_suffix() {
+        let info = FileInfo::from((
+            FileType::EntropyReport,
+            Utc.from_utc_datetime(&start()),
+            Compression::Zstd,
+        ));
+        assert_eq!(info.key, shifted_key(&info, Duration::zero()));
+
+        let shifted = FileInfo::from((
+            FileType::EntropyReport,
+            Utc.from_utc_datetime(&start()) + Duration::days(1),
+            Compression::Zstd,
+        ));
+        assert_eq!(shifted.key, shifted_key(&info, Duration::days(1)));
+    }
+
+    #[tokio::test]
+    async fn copies_files_with_their_checksum() {
+        let source_dir = TempDir::new().expect("Unable to create temp dir");
+        let dest_dir = TempDir::new().expect("Unable to create temp dir");
+        let (source, dest) = (local_store(&source_dir).await, local_store(&dest_dir).await);
+        let key = put_file(&source, start()).await;
+
+        copy_cmd(None)
+            .copy(&source, &dest)
+            .await
+            .expect("failed to copy files");
+
+        let checksum = source.checksum(&key).await.expect("source checksum");
+        assert!(checksum.is_some());
+        assert_eq!(
+            checksum,
+            dest.checksum(&key).await.expect("destination checksum")
+        );
+        assert_eq!(
+            1,
+            dest.get(key.as_str())
+                .await
+                .expect("failed to get file")
+                .count()
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn copies_files_into_a_shifted_window() {
+        let source_dir = TempDir::new().expect("Unable to create temp dir");
+        let dest_dir = TempDir::new().expect("Unable to create temp dir");
+        let (source, dest) = (local_store(&source_dir).await, local_store(&dest_dir).await);
+        put_file(&source, start()).await;
+
+        let shift_to = start() + Duration::days(1) - Duration::hours(1);
+        copy_cmd(Some(shift_to))
+            .copy(&source, &dest)
+            .await
+            .expect("failed to copy files");
+
+        let infos = dest
+            .list_all(FileType::EntropyReport, None, None)
+            .await
+            .expect("failed to list files");
+        assert_eq!(1, infos.len());
+        assert_eq!(
+            Utc.from_utc_datetime(&start()) + Duration::days(1),
+            infos[0].timestamp
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_to_copy_corrupted_files() {
+        let source_dir = TempDir::new().expect("Unable to create temp dir");
+        let dest_dir = TempDir::new().expect("Unable to create temp dir");
+        let (source, dest) = (local_store(&source_dir).await, local_store(&dest_dir).await);
+        let key = put_file(&source, start()).await;
+        let path = source_dir.path().join(&key);
+        let mut data = fs::read(&path).await.expect("failed to read file");
+        data.truncate(data.len() - 1);
+        fs::write(&path, data).await.expect("failed to write file");
+
+        let result = copy_cmd(None).copy(&source, &dest).await;
+        assert!(matches!(result, Err(Error::ChecksumMismatch(_))));
+        assert!(dest
+            .list_all(FileType::EntropyReport, None, None)
+            .await
+            .expect("failed to list files")
+            .is_empty());
+    }
+}
@@ -2,7 +2,12 @@ use crate::{
     cli::print_json,
     file_source,
     heartbeat::{CellHeartbeat, CellHeartbeatIngestReport},
+    iot_hex_density::IotHexDensity,
+    iot_org_lock::IotOrgLockEvent,
     iot_packet::IotValidPacket,
+    iot_suspicious_cluster::IotSuspiciousCluster,
+    mobile_transfer::InvalidDataTransferSession,
+    payer_low_balance::PayerLowBalance,
     speedtest::{CellSpeedtest, CellSpeedtestIngestReport},
     traits::MsgDecode,
     FileType, Result, Settings,
@@ -201,6 +206,21 @@ impl Cmd {
                         "packet_timestamp": manifest.packet_timestamp,
                     }))?;
                 }
+                FileType::IotSuspiciousCluster => {
+                    print_json(&IotSuspiciousCluster::decode(msg)?)?;
+                }
+                FileType::IotHexDensity => {
+                    print_json(&IotHexDensity::decode(msg)?)?;
+                }
+                FileType::IotOrgLockEvent => {
+                    print_json(&IotOrgLockEvent::decode(msg)?)?;
+                }
+                FileType::PayerLowBalance => {
+                    print_json(&PayerLowBalance::decode(msg)?)?;
+                }
+                FileType::InvalidDataTransferSession => {
+                    print_json(&InvalidDataTransferSession::decode(msg)?)?;
+                }
                 _ => (),
             }
         }
@@ -0,0 +1,974 @@
+//! Export store files to Parquet.
+//!
+//! Every supported file type has a fixed set of columns, listed in the
+//! `COLUMNS` of its `ExportRows` implementation below. Columns are only ever
+//! added to the end of a schema so existing readers keep working. All
+//! timestamps are exported as UTC milliseconds, public keys as b58 strings and
+//! binary payloads as base64 strings.
+use crate::{
+    file_source,
+    heartbeat::CellHeartbeatIngestReport,
+    iot_beacon_report::IotBeaconIngestReport,
+    iot_invalid_poc::{IotInvalidBeaconReport, IotInvalidWitnessReport},
+    iot_packet::{IotValidPacket, PacketRouterPacketReport},
+    iot_valid_poc::{IotPoc, IotVerifiedWitnessReport},
+    iot_witness_report::IotWitnessIngestReport,
+    mobile_session::DataTransferSessionIngestReport,
+    mobile_transfer::ValidDataTransferSession,
+    speedtest::CellSpeedtestIngestReport,
+    traits::MsgDecode,
+    BytesMutStream, Error, FileInfo, FileStore, FileType, Result, Settings,
+};
+use arrow::{
+    array::{
+        ArrayRef, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder,
+        TimestampMillisecondBuilder, UInt64Builder,
+    },
+    datatypes::{DataType, Field, Schema, TimeUnit},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+use base64::Engine;
+use bytes::BytesMut;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use futures::{StreamExt, TryStreamExt};
+use helium_crypto::PublicKeyBinary;
+use helium_proto::{
+    services::{
+        poc_lora::{iot_reward_share::Reward as IotReward, IotRewardShare},
+        poc_mobile::{
+            mobile_reward_share::Reward as MobileReward, Heartbeat, MobileRewardShare, SpeedtestAvg,
+        },
+    },
+    EntropyReportV1, Message, PriceReportV1,
+};
+use parquet::arrow::ArrowWriter;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const BATCH_SIZE: usize = 10_000;
+
+/// Export a local file or a time range of bucket files to Parquet. One
+/// Parquet file is written to the output folder for every input file.
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// Type of file to export
+    #[clap(long)]
+    file_type: FileType,
+    /// Local file to export. When not given files of the given type are read
+    /// from the bucket
+    #[clap(long)]
+    in_path: Option<PathBuf>,
+    /// Optional start time to look for bucket files (exclusive)
+    #[clap(long)]
+    after: Option<NaiveDateTime>,
+    /// Optional end time to look for bucket files (inclusive)
+    #[clap(long)]
+    before: Option<NaiveDateTime>,
+    /// Folder to write Parquet files to
+    out_dir: PathBuf,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: &Settings) -> Result {
+        tokio::fs::create_dir_all(&self.out_dir).await?;
+        match &self.in_path {
+            Some(in_path) => {
+                let file_info = FileInfo::try_from(in_path.as_path())?;
+                let out_path = self.out_path(&file_info);
+                let count =
+                    export(self.file_type, file_source::source([in_path]), &out_path).await?;
+                println!("exported {count} rows to {}", out_path.display());
+            }
+            None => {
+                let store = FileStore::from_settings(settings).await?;
+                let mut file_infos = store.list(
+                    self.file_type,
+                    self.after.as_ref().map(|dt| Utc.from_utc_datetime(dt)),
+                    self.before.as_ref().map(|dt| Utc.from_utc_datetime(dt)),
+                );
+                while let Some(file_info) = file_infos.try_next().await? {
+                    let out_path = self.out_path(&file_info);
+                    let stream = store.stream_file(file_info).await?;
+                    let count = export(self.file_type, stream, &out_path).await?;
+                    println!("exported {count} rows to {}", out_path.display());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn out_path(&self, file_info: &FileInfo) -> PathBuf {
+        self.out_dir.join(format!(
+            "{}.{}.parquet",
+            file_info.file_type,
+            file_info.timestamp.timestamp_millis()
+        ))
+    }
+}
+
+async fn export(file_type: FileType, stream: BytesMutStream, out_path: &Path) -> Result<usize> {
+    match file_type {
+        FileType::IotBeaconIngestReport => {
+            export_rows::<IotBeaconIngestReport>(stream, out_path).await
+        }
+        FileType::IotWitnessIngestReport => {
+            export_rows::<IotWitnessIngestReport>(stream, out_path).await
+        }
+        FileType::IotPoc => export_rows::<IotPoc>(stream, out_path).await,
+        FileType::IotInvalidBeaconReport => {
+            export_rows::<IotInvalidBeaconReport>(stream, out_path).await
+        }
+        FileType::IotInvalidWitnessReport => {
+            export_rows::<IotInvalidWitnessReport>(stream, out_path).await
+        }
+        FileType::IotPacketReport => {
+            export_rows::<PacketRouterPacketReport>(stream, out_path).await
+        }
+        FileType::IotValidPacket => export_rows::<IotValidPacket>(stream, out_path).await,
+        FileType::IotRewardShare => export_rows::<IotRewardShare>(stream, out_path).await,
+        FileType::IotRewardSharePreview => export_rows::<IotRewardShare>(stream, out_path).await,
+        FileType::CellHeartbeatIngestReport => {
+            export_rows::<CellHeartbeatIngestReport>(stream, out_path).await
+        }
+        FileType::CellSpeedtestIngestReport => {
+            export_rows::<CellSpeedtestIngestReport>(stream, out_path).await
+        }
+        FileType::DataTransferSessionIngestReport => {
+            export_rows::<DataTransferSessionIngestReport>(stream, out_path).await
+        }
+        FileType::ValidDataTransferSession => {
+            export_rows::<ValidDataTransferSession>(stream, out_path).await
+        }
+        FileType::SpeedtestAvg => export_rows::<SpeedtestAvg>(stream, out_path).await,
+        FileType::ValidatedHeartbeat => export_rows::<Heartbeat>(stream, out_path).await,
+        FileType::MobileRewardShare => export_rows::<MobileRewardShare>(stream, out_path).await,
+        FileType::PriceReport => export_rows::<PriceReportV1>(stream, out_path).await,
+        FileType::EntropyReport => export_rows::<EntropyReportV1>(stream, out_path).await,
+        other => Err(Error::not_found(format!(
+            "export not supported for file type {other}"
+        ))),
+    }
+}
+
+async fn export_rows<T: ExportRows>(mut stream: BytesMutStream, out_path: &Path) -> Result<usize> {
+    let schema = Arc::new(Schema::new(
+        T::COLUMNS
+            .iter()
+            .map(|(name, kind)| Field::new(name, kind.data_type(), true))
+            .collect::<Vec<Field>>(),
+    ));
+    let file = std::fs::File::create(out_path)?;
+    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
+    let mut batch = Batch::new(T::COLUMNS);
+    let mut count = 0;
+
+    while let Some(buf) = stream.next().await {
+        for row in T::decode_msg(buf?)?.rows() {
+            batch.append(row)?;
+            count += 1;
+        }
+        if batch.len >= BATCH_SIZE {
+            writer.write(&batch.finish(schema.clone())?)?;
+        }
+    }
+    if batch.len > 0 {
+        writer.write(&batch.finish(schema)?)?;
+    }
+    writer.close()?;
+
+    Ok(count)
+}
+
+/// Column types used in exported schemas
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Utf8,
+    UInt64,
+    Int64,
+    Float64,
+    Bool,
+    /// UTC milliseconds
+    Timestamp,
+}
+
+impl Kind {
+    fn data_type(&self) -> DataType {
+        match self {
+            Self::Utf8 => DataType::Utf8,
+            Self::UInt64 => DataType::UInt64,
+            Self::Int64 => DataType::Int64,
+            Self::Float64 => DataType::Float64,
+            Self::Bool => DataType::Boolean,
+            Self::Timestamp => DataType::Timestamp(TimeUnit::Millisecond, None),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Null,
+    Utf8(String),
+    UInt64(u64),
+    Int64(i64),
+    Float64(f64),
+    Bool(bool),
+    Timestamp(i64),
+}
+
+impl From<&PublicKeyBinary> for Value {
+    fn from(v: &PublicKeyBinary) -> Self {
+        Self::Utf8(v.to_string())
+    }
+}
+
+impl From<DateTime<Utc>> for Value {
+    fn from(v: DateTime<Utc>) -> Self {
+        Self::Timestamp(v.timestamp_millis())
+    }
+}
+
+impl From<Decimal> for Value {
+    fn from(v: Decimal) -> Self {
+        v.to_f64().map_or(Self::Null, Self::Float64)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Self::Null, Into::into)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(v: u64) -> Self {
+        Self::UInt64(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Self::Utf8(v.to_string())
+    }
+}
+
+fn encode64(v: &[u8]) -> Value {
+    Value::Utf8(base64::engine::general_purpose::STANDARD.encode(v))
+}
+
+fn key(v: &[u8]) -> Value {
+    Value::from(&PublicKeyBinary::from(v.to_vec()))
+}
+
+fn seconds(v: u64) -> Value {
+    Utc.timestamp_opt(v as i64, 0).single().into()
+}
+
+type Row = Vec<Value>;
+
+enum ColumnBuilder {
+    Utf8(StringBuilder),
+    UInt64(UInt64Builder),
+    Int64(Int64Builder),
+    Float64(Float64Builder),
+    Bool(BooleanBuilder),
+    Timestamp(TimestampMillisecondBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(kind: Kind) -> Self {
+        match kind {
+            Kind::Utf8 => Self::Utf8(StringBuilder::new()),
+            Kind::UInt64 => Self::UInt64(UInt64Builder::new()),
+            Kind::Int64 => Self::Int64(Int64Builder::new()),
+            Kind::Float64 => Self::Float64(Float64Builder::new()),
+            Kind::Bool => Self::Bool(BooleanBuilder::new()),
+            Kind::Timestamp => Self::Timestamp(TimestampMillisecondBuilder::new()),
+        }
+    }
+
+    fn append(&mut self, name: &str, value: Value) -> std::result::Result<(), ArrowError> {
+        match (self, value) {
+            (Self::Utf8(b), Value::Utf8(v)) => b.append_value(v),
+            (Self::Utf8(b), Value::Null) => b.append_null(),
+            (Self::UInt64(b), Value::UInt64(v)) => b.append_value(v),
+            (Self::UInt64(b), Value::Null) => b.append_null(),
+            (Self::Int64(b), Value::Int64(v)) => b.append_value(v),
+            (Self::Int64(b), Value::Null) => b.append_null(),
+            (Self::Float64(b), Value::Float64(v)) => b.append_value(v),
+            (Self::Float64(b), Value::Null) => b.append_null(),
+            (Self::Bool(b), Value::Bool(v)) => b.append_value(v),
+            (Self::Bool(b), Value::Null) => b.append_null(),
+            (Self::Timestamp(b), Value::Timestamp(v)) => b.append_value(v),
+            (Self::Timestamp(b), Value::Null) => b.append_null(),
+            (_, value) => {
+                return Err(ArrowError::InvalidArgumentError(format!(
+                    "unexpected value {value:?} for column {name}"
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Utf8(b) => Arc::new(b.finish()),
+            Self::UInt64(b) => Arc::new(b.finish()),
+            Self::Int64(b) => Arc::new(b.finish()),
+            Self::Float64(b) => Arc::new(b.finish()),
+            Self::Bool(b) => Arc::new(b.finish()),
+            Self::Timestamp(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+struct Batch {
+    columns: &'static [(&'static str, Kind)],
+    builders: Vec<ColumnBuilder>,
+    len: usize,
+}
+
+impl Batch {
+    fn new(columns: &'static [(&'static str, Kind)]) -> Self {
+        Self {
+            columns,
+            builders: columns
+                .iter()
+                .map(|(_, kind)| ColumnBuilder::new(*kind))
+                .collect(),
+            len: 0,
+        }
+    }
+
+    fn append(&mut self, row: Row) -> Result {
+        if row.len() != self.columns.len() {
+            return Err(Error::from(ArrowError::InvalidArgumentError(format!(
+                "expected {} values in row, got {}",
+                self.columns.len(),
+                row.len()
+            ))));
+        }
+        for ((builder, (name, _)), value) in self.builders.iter_mut().zip(self.columns).zip(row) {
+            builder.append(name, value)?;
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self, schema: Arc<Schema>) -> Result<RecordBatch> {
+        let arrays = self
+            .builders
+            .iter_mut()
+            .map(ColumnBuilder::finish)
+            .collect();
+        self.len = 0;
+        Ok(RecordBatch::try_new(schema, arrays)?)
+    }
+}
+
+/// A message that can be exported as rows of a fixed set of columns
+trait ExportRows: Sized {
+    const COLUMNS: &'static [(&'static str, Kind)];
+
+    fn decode_msg(buf: BytesMut) -> Result<Self>;
+
+    fn rows(self) -> Vec<Row>;
+}
+
+macro_rules! decode_msg {
+    (MsgDecode) => {
+        fn decode_msg(buf: BytesMut) -> Result<Self> {
+            <Self as MsgDecode>::decode(buf)
+        }
+    };
+    (Message) => {
+        fn decode_msg(buf: BytesMut) -> Result<Self> {
+            Ok(<Self as Message>::decode(buf)?)
+        }
+    };
+}
+
+impl ExportRows for IotBeaconIngestReport {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("received_timestamp", Kind::Timestamp),
+        ("pub_key", Kind::Utf8),
+        ("timestamp", Kind::Timestamp),
+        ("frequency", Kind::UInt64),
+        ("channel", Kind::Int64),
+        ("datarate", Kind::Utf8),
+        ("tx_power", Kind::Int64),
+        ("tmst", Kind::UInt64),
+        ("local_entropy", Kind::Utf8),
+        ("remote_entropy", Kind::Utf8),
+        ("data", Kind::Utf8),
+    ];
+
+    decode_msg!(MsgDecode);
+
+    fn rows(self) -> Vec<Row> {
+        let report = self.report;
+        vec![vec![
+            self.received_timestamp.into(),
+            (&report.pub_key).into(),
+            report.timestamp.into(),
+            report.frequency.into(),
+            Value::Int64(report.channel as i64),
+            report.datarate.as_str_name().into(),
+            Value::Int64(report.tx_power as i64),
+            Value::UInt64(report.tmst as u64),
+            encode64(&report.local_entropy),
+            encode64(&report.remote_entropy),
+            encode64(&report.data),
+        ]]
+    }
+}
+
+impl ExportRows for IotWitnessIngestReport {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("received_timestamp", Kind::Timestamp),
+        ("pub_key", Kind::Utf8),
+        ("timestamp", Kind::Timestamp),
+        ("frequency", Kind::UInt64),
+        ("datarate", Kind::Utf8),
+        ("signal", Kind::Int64),
+        ("snr", Kind::Int64),
+        ("tmst", Kind::UInt64),
+        ("data", Kind::Utf8),
+    ];
+
+    decode_msg!(MsgDecode);
+
+    fn rows(self) -> Vec<Row> {
+        let report = self.report;
+        vec![vec![
+            self.received_timestamp.into(),
+            (&report.pub_key).into(),
+            report.timestamp.into(),
+            report.frequency.into(),
+            report.datarate.as_str_name().into(),
+            Value::Int64(report.signal as i64),
+            Value::Int64(report.snr as i64),
+            Value::UInt64(report.tmst as u64),
+            encode64(&report.data),
+        ]]
+    }
+}
+
+/// One row per witness of a poc, with the beacon columns repeated. A poc
+/// without witnesses is exported as a single row with empty witness columns.
+impl ExportRows for IotPoc {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("poc_id", Kind::Utf8),
+        ("beacon_received_timestamp", Kind::Timestamp),
+        ("beacon_pub_key", Kind::Utf8),
+        ("beacon_timestamp", Kind::Timestamp),
+        ("beacon_frequency", Kind::UInt64),
+        ("beacon_location", Kind::UInt64),
+        ("beacon_gain", Kind::Int64),
+        ("beacon_elevation", Kind::Int64),
+        ("beacon_hex_scale", Kind::Float64),
+        ("beacon_reward_unit", Kind::Float64),
+        ("witness_selected", Kind::Bool),
+        ("witness_received_timestamp", Kind::Timestamp),
+        ("witness_pub_key", Kind::Utf8),
+        ("witness_timestamp", Kind::Timestamp),
+        ("witness_status", Kind::Utf8),
+        ("witness_invalid_reason", Kind::Utf8),
+        ("witness_participant_side", Kind::Utf8),
+        ("witness_signal", Kind::Int64),
+        ("witness_snr", Kind::Int64),
+        ("witness_location", Kind::UInt64),
+        ("witness_hex_scale", Kind::Float64),
+        ("witness_reward_unit", Kind::Float64),
+    ];
+
+    decode_msg!(MsgDecode);
+
+    fn rows(self) -> Vec<Row> {
+        let beacon = self.beacon_report;
+        let beacon_columns = vec![
+            encode64(&self.poc_id),
+            beacon.received_timestamp.into(),
+            (&beacon.report.pub_key).into(),
+            beacon.report.timestamp.into(),
+            beacon.report.frequency.into(),
+            beacon.location.into(),
+            Value::Int64(beacon.gain as i64),
+            Value::Int64(beacon.elevation as i64),
+            beacon.hex_scale.into(),
+            beacon.reward_unit.into(),
+        ];
+        let witnesses: Vec<(bool, IotVerifiedWitnessReport)> = self
+            .selected_witnesses
+            .into_iter()
+            .map(|witness| (true, witness))
+            .chain(
+                self.unselected_witnesses
+                    .into_iter()
+                    .map(|witness| (false, witness)),
+            )
+            .collect();
+        if witnesses.is_empty() {
+            let mut row = beacon_columns;
+            row.resize(Self::COLUMNS.len(), Value::Null);
+            return vec![row];
+        }
+        witnesses
+            .into_iter()
+            .map(|(selected, witness)| {
+                let mut row = beacon_columns.clone();
+                row.extend([
+                    Value::Bool(selected),
+                    witness.received_timestamp.into(),
+                    (&witness.report.pub_key).into(),
+                    witness.report.timestamp.into(),
+                    witness.status.as_str_name().into(),
+                    witness.invalid_reason.as_str_name().into(),
+                    witness.participant_side.as_str_name().into(),
+                    Value::Int64(witness.report.signal as i64),
+                    Value::Int64(witness.report.snr as i64),
+                    witness.location.into(),
+                    witness.hex_scale.into(),
+                    witness.reward_unit.into(),
+                ]);
+                row
+            })
+            .collect()
+    }
+}
+
+impl ExportRows for IotInvalidBeaconReport {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("received_timestamp", Kind::Timestamp),
+        ("reason", Kind::Utf8),
+        ("pub_key", Kind::Utf8),
+        ("timestamp", Kind::Timestamp),
+        ("frequency", Kind::UInt64),
+    ];
+
+    decode_msg!(MsgDecode);
+
+    fn rows(self) -> Vec<Row> {
+        vec![vec![
+            self.received_timestamp.into(),
+            self.reason.as_str_name().into(),
+            (&self.report.pub_key).into(),
+            self.report.timestamp.into(),
+            self.report.frequency.into(),
+        ]]
+    }
+}
+
+impl ExportRows for IotInvalidWitnessReport {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("received_timestamp", Kind::Timestamp),
+        ("reason", Kind::Utf8),
+        ("participant_side", Kind::Utf8),
+        ("pub_key", Kind::Utf8),
+        ("timestamp", Kind::Timestamp),
+        ("frequency", Kind::UInt64),
+        ("signal", Kind::Int64),
+        ("snr", Kind::Int64),
+    ];
+
+    decode_msg!(MsgDecode);
+
+    fn rows(self) -> Vec<Row> {
+        vec![vec![
+            self.received_timestamp.into(),
+            self.reason.as_str_name().into(),
+            self.participant_side.as_str_name().into(),
+            (&self.report.pub_key).into(),
+            self.report.timestamp.into(),
+            self.report.frequency.into(),
+            Value::Int64(self.report.signal as i64),
+            Value::Int64(self.report.snr as i64),
+        ]]
+    }
+}
+
+impl ExportRows for PacketRouterPacketReport {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("received_timestamp", Kind::Timestamp),
+        ("oui", Kind::UInt64),
+        ("net_id", Kind::UInt64),
+        ("gateway", Kind::Utf8),
+        ("region", Kind::Utf8),
+        ("frequency", Kind::UInt64),
+        ("data_rate", Kind::Utf8),
+        ("rssi", Kind::Int64),
+        ("snr", Kind::Float64),
+        ("payload_hash", Kind::Utf8),
+        ("payload_size", Kind::UInt64),
+    ];
+
+    decode_msg!(MsgDecode);
+
+    fn rows(self) -> Vec<Row> {
+        vec![vec![
+            self.received_timestamp.into(),
+            self.oui.into(),
+            Value::UInt64(self.net_id as u64),
+            (&self.gateway).into(),
+            self.region.as_str_name().into(),
+            Value::UInt64(self.frequency as u64),
+            self.data_rate.as_str_name().into(),
+            Value::Int64(self.rssi as i64),
+            Value::Float64(self.snr as f64),
+            encode64(&self.payload_hash),
+            Value::UInt64(self.payload_size as u64),
+        ]]
+    }
+}
+
+impl ExportRows for IotValidPacket {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("packet_timestamp", Kind::Timestamp),
+        ("gateway", Kind::Utf8),
+        ("payload_hash", Kind::Utf8),
+        ("payload_size", Kind::UInt64),
+        ("num_dcs", Kind::UInt64),
+    ];
+
+    decode_msg!(MsgDecode);
+
+    fn rows(self) -> Vec<Row> {
+        vec![vec![
+            self.packet_timestamp.into(),
+            (&self.gateway).into(),
+            encode64(&self.payload_hash),
+            Value::UInt64(self.payload_size as u64),
+            Value::UInt64(self.num_dcs as u64),
+        ]]
+    }
+}
+
+/// `reward_type` is either "gateway" or "operational". Operational rewards
+/// have no hotspot and report their amount as `total_amount` only.
+impl ExportRows for IotRewardShare {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("start_period", Kind::Timestamp),
+        ("end_period", Kind::Timestamp),
+        ("reward_type", Kind::Utf8),
+        ("hotspot_key", Kind::Utf8),
+        ("beacon_amount", Kind::UInt64),
+        ("witness_amount", Kind::UInt64),
+        ("dc_transfer_amount", Kind::UInt64),
+        ("total_amount", Kind::UInt64),
+    ];
+
+    decode_msg!(Message);
+
+    fn rows(self) -> Vec<Row> {
+        let period = [seconds(self.start_period), seconds(self.end_period)];
+        let reward = match self.reward {
+            Some(IotReward::GatewayReward(r)) => vec![
+                "gateway".into(),
+                key(&r.hotspot_key),
+                r.beacon_amount.into(),
+                r.witness_amount.into(),
+                r.dc_transfer_amount.into(),
+                (r.beacon_amount + r.witness_amount + r.dc_transfer_amount).into(),
+            ],
+            Some(IotReward::OperationalReward(r)) => vec![
+                "operational".into(),
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                r.amount.into(),
+            ],
+            _ => return vec![],
+        };
+        vec![period.into_iter().chain(reward).collect()]
+    }
+}
+
+impl ExportRows for CellHeartbeatIngestReport {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("received_timestamp", Kind::Timestamp),
+        ("pub_key", Kind::Utf8),
+        ("timestamp", Kind::Timestamp),
+        ("cbsd_id", Kind::Utf8),
+        ("cbsd_category", Kind::Utf8),
+        ("cell_id", Kind::UInt64),
+        ("hotspot_type", Kind::Utf8),
+        ("lat", Kind::Float64),
+        ("lon", Kind::Float64),
+        ("operation_mode", Kind::Bool),
+    ];
+
+    decode_msg!(MsgDecode);
+
+    fn rows(self) -> Vec<Row> {
+        let report = self.report;
+        vec![vec![
+            self.received_timestamp.into(),
+            (&report.pubkey).into(),
+            report.timestamp.into(),
+            Value::Utf8(report.cbsd_id),
+            Value::Utf8(report.cbsd_category),
+            Value::UInt64(report.cell_id as u64),
+            Value::Utf8(report.hotspot_type),
+            Value::Float64(report.lat),
+            Value::Float64(report.lon),
+            Value::Bool(report.operation_mode),
+        ]]
+    }
+}
+
+impl ExportRows for CellSpeedtestIngestReport {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("received_timestamp", Kind::Timestamp),
+        ("pub_key", Kind::Utf8),
+        ("timestamp", Kind::Timestamp),
+        ("serial", Kind::Utf8),
+        ("upload_speed", Kind::UInt64),
+        ("download_speed", Kind::UInt64),
+        ("latency", Kind::UInt64),
+    ];
+
+    decode_msg!(MsgDecode);
+
+    fn rows(self) -> Vec<Row> {
+        let report = self.report;
+        vec![vec![
+            self.received_timestamp.into(),
+            (&report.pubkey).into(),
+            report.timestamp.into(),
+            Value::Utf8(report.serial),
+            report.upload_speed.into(),
+            report.download_speed.into(),
+            Value::UInt64(report.latency as u64),
+        ]]
+    }
+}
+
+impl ExportRows for DataTransferSessionIngestReport {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("received_timestamp", Kind::Timestamp),
+        ("reporter_pub_key", Kind::Utf8),
+        ("reward_cancelled", Kind::Bool),
+        ("pub_key", Kind::Utf8),
+        ("payer", Kind::Utf8),
+        ("timestamp", Kind::Timestamp),
+        ("event_id", Kind::Utf8),
+        ("radio_access_technology", Kind::Utf8),
+        ("upload_bytes", Kind::UInt64),
+        ("download_bytes", Kind::UInt64),
+    ];
+
+    decode_msg!(MsgDecode);
+
+    fn rows(self) -> Vec<Row> {
+        let report = self.report;
+        let usage = report.data_transfer_usage;
+        vec![vec![
+            self.received_timestamp.into(),
+            (&report.pub_key).into(),
+            Value::Bool(report.reward_cancelled),
+            (&usage.pub_key).into(),
+            (&usage.payer).into(),
+            usage.timestamp.into(),
+            Value::Utf8(usage.event_id),
+            usage.radio_access_technology.as_str_name().into(),
+            usage.upload_bytes.into(),
+            usage.download_bytes.into(),
+        ]]
+    }
+}
+
+impl ExportRows for ValidDataTransferSession {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("pub_key", Kind::Utf8),
+        ("payer", Kind::Utf8),
+        ("first_timestamp", Kind::Timestamp),
+        ("last_timestamp", Kind::Timestamp),
+        ("upload_bytes", Kind::UInt64),
+        ("download_bytes", Kind::UInt64),
+        ("num_dcs", Kind::UInt64),
+    ];
+
+    decode_msg!(MsgDecode);
+
+    fn rows(self) -> Vec<Row> {
+        vec![vec![
+            (&self.pub_key).into(),
+            (&self.payer).into(),
+            self.first_timestamp.into(),
+            self.last_timestamp.into(),
+            self.upload_bytes.into(),
+            self.download_bytes.into(),
+            self.num_dcs.into(),
+        ]]
+    }
+}
+
+/// `validity` is the numeric value of the proto validity enum
+impl ExportRows for SpeedtestAvg {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("pub_key", Kind::Utf8),
+        ("upload_speed_avg_bps", Kind::UInt64),
+        ("download_speed_avg_bps", Kind::UInt64),
+        ("latency_avg_ms", Kind::UInt64),
+        ("validity", Kind::Int64),
+        ("number_of_speedtests", Kind::UInt64),
+        ("reward_multiplier", Kind::Float64),
+    ];
+
+    decode_msg!(Message);
+
+    fn rows(self) -> Vec<Row> {
+        vec![vec![
+            key(&self.pub_key),
+            Value::UInt64(self.upload_speed_avg_bps as u64),
+            Value::UInt64(self.download_speed_avg_bps as u64),
+            Value::UInt64(self.latency_avg_ms as u64),
+            Value::Int64(self.validity as i64),
+            Value::UInt64(self.speedtests.len() as u64),
+            Value::Float64(self.reward_multiplier as f64),
+        ]]
+    }
+}
+
+/// `cell_type` and `validity` are the numeric values of the proto enums and
+/// `timestamp` is in unix seconds as reported
+impl ExportRows for Heartbeat {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("pub_key", Kind::Utf8),
+        ("cbsd_id", Kind::Utf8),
+        ("timestamp", Kind::UInt64),
+        ("cell_type", Kind::Int64),
+        ("validity", Kind::Int64),
+        ("reward_multiplier", Kind::Float64),
+    ];
+
+    decode_msg!(Message);
+
+    fn rows(self) -> Vec<Row> {
+        vec![vec![
+            key(&self.pub_key),
+            Value::Utf8(self.cbsd_id),
+            Value::UInt64(self.timestamp as u64),
+            Value::Int64(self.cell_type as i64),
+            Value::Int64(self.validity as i64),
+            Value::Float64(self.reward_multiplier as f64),
+        ]]
+    }
+}
+
+/// `reward_type` is either "radio" or "gateway"
+impl ExportRows for MobileRewardShare {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("start_period", Kind::Timestamp),
+        ("end_period", Kind::Timestamp),
+        ("reward_type", Kind::Utf8),
+        ("hotspot_key", Kind::Utf8),
+        ("cbsd_id", Kind::Utf8),
+        ("poc_reward", Kind::UInt64),
+        ("dc_transfer_reward", Kind::UInt64),
+    ];
+
+    decode_msg!(Message);
+
+    fn rows(self) -> Vec<Row> {
+        let period = [seconds(self.start_period), seconds(self.end_period)];
+        let reward = match self.reward {
+            Some(MobileReward::RadioReward(r)) => vec![
+                "radio".into(),
+                key(&r.hotspot_key),
+                Value::Utf8(r.cbsd_id),
+                r.poc_reward.into(),
+                Value::Null,
+            ],
+            Some(MobileReward::GatewayReward(r)) => vec![
+                "gateway".into(),
+                key(&r.hotspot_key),
+                Value::Null,
+                Value::Null,
+                r.dc_transfer_reward.into(),
+            ],
+            _ => return vec![],
+        };
+        vec![period.into_iter().chain(reward).collect()]
+    }
+}
+
+impl ExportRows for PriceReportV1 {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("timestamp", Kind::Timestamp),
+        ("token_type", Kind::Utf8),
+        ("price", Kind::UInt64),
+    ];
+
+    decode_msg!(Message);
+
+    fn rows(self) -> Vec<Row> {
+        vec![vec![
+            seconds(self.timestamp),
+            self.token_type().as_str_name().into(),
+            self.price.into(),
+        ]]
+    }
+}
+
+impl ExportRows for EntropyReportV1 {
+    const COLUMNS: &'static [(&'static str, Kind)] = &[
+        ("timestamp", Kind::Timestamp),
+        ("version", Kind::UInt64),
+        ("data", Kind::Utf8),
+    ];
+
+    decode_msg!(Message);
+
+    fn rows(self) -> Vec<Row> {
+        vec![vec![
+            seconds(self.timestamp),
+            Value::UInt64(self.version as u64),
+            encode64(&self.data),
+        ]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn exports_rows_in_batches() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let out_path = tmp_dir.path().join("price_report.parquet");
+        let reports: Vec<Result<BytesMut>> = (0..BATCH_SIZE as u64 + 5)
+            .map(|i| {
+                let report = PriceReportV1 {
+                    price: i,
+                    timestamp: 1_680_000_000 + i,
+                    ..Default::default()
+                };
+                Ok(BytesMut::from(report.encode_to_vec().as_slice()))
+            })
+            .collect();
+
+        let count = export_rows::<PriceReportV1>(stream::iter(reports).boxed(), &out_path)
+            .await
+            .expect("failed to export");
+        assert_eq!(BATCH_SIZE + 5, count);
+
+        let reader = SerializedFileReader::new(
+            std::fs::File::open(&out_path).expect("failed to open parquet file"),
+        )
+        .expect("invalid parquet file");
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!((BATCH_SIZE + 5) as i64, metadata.num_rows());
+        assert_eq!(
+            PriceReportV1::COLUMNS.len(),
+            metadata.schema_descr().num_columns()
+        );
+    }
+}
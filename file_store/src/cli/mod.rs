@@ -1,6 +1,8 @@
 pub mod bucket;
 pub mod dump;
+pub mod export;
 pub mod info;
+pub mod verify;
 
 use crate::Result;
 
@@ -0,0 +1,234 @@
+use crate::{
+    cli::print_json, file_source, traits::MsgVerify, BytesMutStream, Error, FileStore, FileType,
+    Result, Settings,
+};
+use bytes::BytesMut;
+use futures::StreamExt;
+use helium_crypto::{PublicKey, PublicKeyBinary};
+use helium_proto::{
+    services::{
+        poc_lora::{
+            LoraBeaconIngestReportV1, LoraBeaconReportReqV1, LoraWitnessIngestReportV1,
+            LoraWitnessReportReqV1,
+        },
+        poc_mobile::{
+            CellHeartbeatIngestReportV1, CellHeartbeatReqV1, DataTransferSessionIngestReportV1,
+            DataTransferSessionReqV1, SpeedtestIngestReportV1, SpeedtestReqV1,
+            SubscriberLocationIngestReportV1, SubscriberLocationReqV1,
+        },
+    },
+    Message,
+};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Re-verify the signatures of all reports in an ingest file against the
+/// public key embedded in each report.
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// Type of ingest file to verify
+    file_type: FileType,
+    /// Path to a local file, or the key of a file in the bucket when --remote
+    /// is given
+    file: String,
+    /// Read the file from the configured bucket instead of the local
+    /// filesystem
+    #[clap(long)]
+    remote: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    file_type: String,
+    total: u64,
+    valid: u64,
+    invalid: u64,
+    invalid_entries: Vec<InvalidEntry>,
+}
+
+#[derive(Debug, Serialize)]
+struct InvalidEntry {
+    index: u64,
+    pub_key: Option<PublicKeyBinary>,
+    reason: String,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: &Settings) -> Result {
+        let mut file_stream = self.source(settings).await?;
+        let mut summary = Summary {
+            file_type: self.file_type.to_string(),
+            ..Default::default()
+        };
+
+        while let Some(result) = file_stream.next().await {
+            let index = summary.total;
+            summary.total += 1;
+            match verify_entry(self.file_type, result?)? {
+                Ok(()) => summary.valid += 1,
+                Err((pub_key, reason)) => {
+                    summary.invalid += 1;
+                    summary.invalid_entries.push(InvalidEntry {
+                        index,
+                        pub_key,
+                        reason,
+                    });
+                }
+            }
+        }
+
+        print_json(&summary)
+    }
+
+    async fn source(&self, settings: &Settings) -> Result<BytesMutStream> {
+        if self.remote {
+            FileStore::from_settings(settings)
+                .await?
+                .get(self.file.as_str())
+                .await
+        } else {
+            Ok(file_source::source([PathBuf::from(&self.file)]))
+        }
+    }
+}
+
+type VerifyOutcome = std::result::Result<(), (Option<PublicKeyBinary>, String)>;
+
+/// Verify a single entry of the file. An entry that can't be decoded is
+/// reported as invalid rather than failing the whole verification.
+fn verify_entry(file_type: FileType, buf: BytesMut) -> Result<VerifyOutcome> {
+    match verify(file_type, buf) {
+        Err(Error::Decode(err)) => Ok(Err((None, format!("undecodable entry: {err}")))),
+        other => other,
+    }
+}
+
+fn verify(file_type: FileType, buf: BytesMut) -> Result<VerifyOutcome> {
+    let outcome = match file_type {
+        FileType::CellHeartbeat => verify_req(Some(CellHeartbeatReqV1::decode(buf)?)),
+        FileType::CellSpeedtest => verify_req(Some(SpeedtestReqV1::decode(buf)?)),
+        FileType::SubscriberLocationReq => verify_req(Some(SubscriberLocationReqV1::decode(buf)?)),
+        FileType::CellHeartbeatIngestReport => {
+            verify_req(CellHeartbeatIngestReportV1::decode(buf)?.report)
+        }
+        FileType::CellSpeedtestIngestReport => {
+            verify_req(SpeedtestIngestReportV1::decode(buf)?.report)
+        }
+        FileType::IotBeaconIngestReport => {
+            verify_req(LoraBeaconIngestReportV1::decode(buf)?.report)
+        }
+        FileType::IotWitnessIngestReport => {
+            verify_req(LoraWitnessIngestReportV1::decode(buf)?.report)
+        }
+        FileType::DataTransferSessionIngestReport => {
+            verify_req(DataTransferSessionIngestReportV1::decode(buf)?.report)
+        }
+        FileType::SubscriberLocationIngestReport => {
+            verify_req(SubscriberLocationIngestReportV1::decode(buf)?.report)
+        }
+        other => {
+            return Err(Error::not_found(format!(
+                "no signed reports in file type {other}"
+            )))
+        }
+    };
+    Ok(outcome)
+}
+
+fn verify_req<R>(req: Option<R>) -> VerifyOutcome
+where
+    R: MsgVerify + SignedReq,
+{
+    let req = req.ok_or_else(|| (None, "missing report".to_string()))?;
+    let pub_key = PublicKeyBinary::from(req.signer().to_vec());
+    let verifier = PublicKey::try_from(req.signer())
+        .map_err(|err| (Some(pub_key.clone()), format!("invalid public key: {err}")))?;
+    req.verify(&verifier)
+        .map_err(|err| (Some(pub_key), format!("invalid signature: {err:?}")))
+}
+
+/// The public key a signed request is verified against
+trait SignedReq {
+    fn signer(&self) -> &[u8];
+}
+
+macro_rules! impl_signed_req {
+    ($msg_type:ty, $key:ident) => {
+        impl SignedReq for $msg_type {
+            fn signer(&self) -> &[u8] {
+                &self.$key
+            }
+        }
+    };
+}
+
+impl_signed_req!(CellHeartbeatReqV1, pub_key);
+impl_signed_req!(SpeedtestReqV1, pub_key);
+impl_signed_req!(LoraBeaconReportReqV1, pub_key);
+impl_signed_req!(LoraWitnessReportReqV1, pub_key);
+impl_signed_req!(DataTransferSessionReqV1, pub_key);
+impl_signed_req!(SubscriberLocationReqV1, carrier_pub_key);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+
+    // Generated by FreedomFi
+    const SPEEDTEST_MSG: &str = "CiEAPGoan3wJ+7zNiR3cIvcPpVSIxpvNUcpa5i0W46TNduMSEEhMLTIxNTMtMDAwMTI2OTQYtoOhlwYgsN4CKKDnAjAxOkcwRQIhAI8gko+CSzGkC4JxIY0+g1HwL4/kii6HEktOmoCasEV3AiBJgKrRUAJFEOS8fJo4/v8DUehl0IbH3dPZFY4CXEOuKA==";
+
+    fn speedtest() -> SpeedtestReqV1 {
+        let buf = base64::engine::general_purpose::STANDARD
+            .decode(SPEEDTEST_MSG)
+            .expect("base64 message");
+        SpeedtestReqV1::decode(buf.as_ref()).expect("cell speedtest")
+    }
+
+    fn ingest_report(report: SpeedtestReqV1) -> BytesMut {
+        let report = SpeedtestIngestReportV1 {
+            received_timestamp: 1,
+            report: Some(report),
+        };
+        BytesMut::from(report.encode_to_vec().as_slice())
+    }
+
+    #[test]
+    fn signed_report_is_valid() {
+        let outcome = verify_entry(
+            FileType::CellSpeedtestIngestReport,
+            ingest_report(speedtest()),
+        )
+        .expect("verified entry");
+        assert!(outcome.is_ok());
+    }
+
+    #[test]
+    fn tampered_report_is_invalid() {
+        let mut report = speedtest();
+        report.upload_speed += 1;
+        let pub_key = PublicKeyBinary::from(report.pub_key.clone());
+        let (invalid_key, reason) =
+            verify_entry(FileType::CellSpeedtestIngestReport, ingest_report(report))
+                .expect("verified entry")
+                .unwrap_err();
+        assert_eq!(Some(pub_key), invalid_key);
+        assert!(reason.starts_with("invalid signature"));
+    }
+
+    #[test]
+    fn undecodable_entry_is_invalid() {
+        let (pub_key, reason) = verify_entry(
+            FileType::CellSpeedtestIngestReport,
+            BytesMut::from(&[0xff, 0xff, 0xff][..]),
+        )
+        .expect("verified entry")
+        .unwrap_err();
+        assert_eq!(None, pub_key);
+        assert!(reason.starts_with("undecodable entry"));
+    }
+
+    #[test]
+    fn unsigned_file_type_fails() {
+        assert!(verify_entry(FileType::IotPoc, ingest_report(speedtest())).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use async_compression::tokio::{
+    bufread::{GzipDecoder, ZstdDecoder},
+    write::{self, GzipEncoder, ZstdEncoder},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+/// Compression codec for store files. The codec of a file is identified by
+/// the extension of its key, `.gz` for gzip and `.zst` for zstd.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Zstd => "zst",
+        }
+    }
+
+    /// The codec for the given file key or path. Keys without a known
+    /// extension are assumed to be gzip encoded.
+    pub fn from_key(key: &str) -> Self {
+        if key.ends_with(".zst") {
+            Self::Zstd
+        } else {
+            Self::Gzip
+        }
+    }
+
+    pub(crate) fn encoder<W>(&self, writer: W) -> Encoder<W>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        match self {
+            Self::Gzip => Encoder::Gzip(GzipEncoder::new(writer)),
+            Self::Zstd => Encoder::Zstd(ZstdEncoder::new(writer)),
+        }
+    }
+
+    pub(crate) fn decoder<R>(&self, reader: R) -> Box<dyn AsyncRead + Send + Unpin>
+    where
+        R: AsyncBufRead + Send + Unpin + 'static,
+    {
+        match self {
+            Self::Gzip => Box::new(GzipDecoder::new(reader)),
+            Self::Zstd => Box::new(ZstdDecoder::new(reader)),
+        }
+    }
+
+    /// A decoder that writes the decompressed data of everything written to
+    /// it to the given writer
+    pub(crate) fn write_decoder<W>(&self, writer: W) -> WriteDecoder<W>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        match self {
+            Self::Gzip => WriteDecoder::Gzip(write::GzipDecoder::new(writer)),
+            Self::Zstd => WriteDecoder::Zstd(write::ZstdDecoder::new(writer)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum Encoder<W> {
+    Gzip(GzipEncoder<W>),
+    Zstd(ZstdEncoder<W>),
+}
+
+impl<W> AsyncWrite for Encoder<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Gzip(encoder) => Pin::new(encoder).poll_write(cx, buf),
+            Self::Zstd(encoder) => Pin::new(encoder).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Gzip(encoder) => Pin::new(encoder).poll_flush(cx),
+            Self::Zstd(encoder) => Pin::new(encoder).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Gzip(encoder) => Pin::new(encoder).poll_shutdown(cx),
+            Self::Zstd(encoder) => Pin::new(encoder).poll_shutdown(cx),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum WriteDecoder<W> {
+    Gzip(write::GzipDecoder<W>),
+    Zstd(write::ZstdDecoder<W>),
+}
+
+impl<W> WriteDecoder<W> {
+    pub(crate) fn into_inner(self) -> W {
+        match self {
+            Self::Gzip(decoder) => decoder.into_inner(),
+            Self::Zstd(decoder) => decoder.into_inner(),
+        }
+    }
+}
+
+impl<W> AsyncWrite for WriteDecoder<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Gzip(decoder) => Pin::new(decoder).poll_write(cx, buf),
+            Self::Zstd(decoder) => Pin::new(decoder).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Gzip(decoder) => Pin::new(decoder).poll_flush(cx),
+            Self::Zstd(decoder) => Pin::new(decoder).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Gzip(decoder) => Pin::new(decoder).poll_shutdown(cx),
+            Self::Zstd(decoder) => Pin::new(decoder).poll_shutdown(cx),
+        }
+    }
+}
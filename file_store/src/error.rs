@@ -32,6 +32,12 @@ pub enum Error {
     SendTimeout,
     #[error("shutting down")]
     Shutdown,
+    #[error("checksum mismatch for {0}")]
+    ChecksumMismatch(String),
+    #[error("arrow error")]
+    Arrow(#[from] arrow::error::ArrowError),
+    #[error("parquet error")]
+    Parquet(#[from] parquet::errors::ParquetError),
 }
 
 #[derive(Error, Debug)]
@@ -0,0 +1,314 @@
+use crate::{file_sink, file_store::ByteStream, BytesMutStream, Compression, Error, Result};
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt, io,
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncWrite, AsyncWriteExt},
+};
+
+const DIGEST_KEY: &str = "blake3-digest";
+const RECORD_COUNT_KEY: &str = "record-count";
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Integrity data for a stored file: the blake3 digest of the file as stored
+/// and, for files of length delimited records, the number of records in it. A
+/// checksum is recorded for every file put in a store and verified while the
+/// file is read back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChecksum {
+    pub digest: String,
+    pub record_count: Option<u64>,
+}
+
+impl FileChecksum {
+    /// Compute the checksum of a local store file in a single pass over it.
+    /// Files that are not a sequence of length delimited records get no
+    /// record count.
+    pub async fn from_path(path: &Path) -> Result<Self> {
+        let mut file = fs::File::open(path).await?;
+        let mut hasher = blake3::Hasher::new();
+        let mut records = Some(
+            Compression::from_key(&path.to_string_lossy()).write_decoder(RecordCounter::default()),
+        );
+        let mut buf = vec![0; CHUNK_SIZE];
+        loop {
+            let read = file.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+            if let Some(decoder) = records.as_mut() {
+                if let Err(err) = decoder.write_all(&buf[..read]).await {
+                    tracing::debug!(path = %path.display(), "no record count for file: {err:?}");
+                    records = None;
+                }
+            }
+        }
+        let record_count = match records {
+            Some(mut decoder) => match decoder.shutdown().await {
+                Ok(()) => decoder.into_inner().count(),
+                Err(err) => {
+                    tracing::debug!(path = %path.display(), "no record count for file: {err:?}");
+                    None
+                }
+            },
+            None => None,
+        };
+        Ok(Self {
+            digest: hasher.finalize().to_hex().to_string(),
+            record_count,
+        })
+    }
+
+    /// Verify the raw bytes of the file with the given key against the digest
+    /// as they are streamed. The last chunk of the file is only passed on once
+    /// the digest matches, so a file that does not match never decodes
+    /// completely but fails with an io error instead.
+    pub fn verify_digest(&self, key: &str, stream: ByteStream) -> ByteStream {
+        let key = key.to_string();
+        let expected = self.digest.clone();
+        stream::unfold(
+            Some((stream.peekable(), blake3::Hasher::new())),
+            move |state| {
+                let key = key.clone();
+                let expected = expected.clone();
+                async move {
+                    let (mut stream, mut hasher) = state?;
+                    let bytes = match stream.next().await {
+                        Some(Ok(bytes)) => bytes,
+                        Some(Err(err)) => return Some((Err(err), None)),
+                        None => {
+                            return check_digest(&key, &expected, &hasher)
+                                .err()
+                                .map(|err| (Err(err), None))
+                        }
+                    };
+                    hasher.update(&bytes);
+                    if std::pin::Pin::new(&mut stream).peek().await.is_some() {
+                        return Some((Ok(bytes), Some((stream, hasher))));
+                    }
+                    match check_digest(&key, &expected, &hasher) {
+                        Ok(()) => Some((Ok(bytes), None)),
+                        Err(err) => Some((Err(err), None)),
+                    }
+                }
+            },
+        )
+        .boxed()
+    }
+
+    /// Verify that the decoded records of the file with the given key match
+    /// the record count, if one was recorded, failing the stream at its end
+    /// if they do not.
+    pub fn verify_record_count(&self, key: &str, stream: BytesMutStream) -> BytesMutStream {
+        let Some(expected) = self.record_count else {
+            return stream;
+        };
+        let key = key.to_string();
+        stream::unfold(Some((stream, 0)), move |state| {
+            let key = key.clone();
+            async move {
+                let (mut stream, count) = state?;
+                match stream.next().await {
+                    Some(Ok(record)) => Some((Ok(record), Some((stream, count + 1)))),
+                    Some(Err(err)) => Some((Err(err), None)),
+                    None if count == expected => None,
+                    None => {
+                        tracing::error!(
+                            key = %key,
+                            expected,
+                            actual = count,
+                            "file record count mismatch"
+                        );
+                        Some((Err(Error::ChecksumMismatch(key)), None))
+                    }
+                }
+            }
+        })
+        .boxed()
+    }
+
+    /// Object metadata to store the checksum with
+    pub fn to_metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::from([(DIGEST_KEY.to_string(), self.digest.clone())]);
+        if let Some(record_count) = self.record_count {
+            metadata.insert(RECORD_COUNT_KEY.to_string(), record_count.to_string());
+        }
+        metadata
+    }
+
+    /// Read a checksum from object metadata. Files uploaded before checksums
+    /// were recorded have none.
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            digest: metadata.get(DIGEST_KEY)?.clone(),
+            record_count: metadata
+                .get(RECORD_COUNT_KEY)
+                .and_then(|count| count.parse().ok()),
+        })
+    }
+}
+
+/// Counts the length delimited records in the decompressed data written to
+/// it, framed as by the codec of file sinks
+#[derive(Default)]
+struct RecordCounter {
+    header: [u8; 4],
+    header_len: usize,
+    remaining: usize,
+    count: u64,
+}
+
+impl RecordCounter {
+    /// The number of records, if the data ended on a record boundary
+    fn count(&self) -> Option<u64> {
+        (self.header_len == 0 && self.remaining == 0).then_some(self.count)
+    }
+
+    fn update(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            if self.remaining > 0 {
+                let len = self.remaining.min(data.len());
+                self.remaining -= len;
+                data = &data[len..];
+                if self.remaining == 0 {
+                    self.count += 1;
+                }
+                continue;
+            }
+            let len = (self.header.len() - self.header_len).min(data.len());
+            self.header[self.header_len..self.header_len + len].copy_from_slice(&data[..len]);
+            self.header_len += len;
+            data = &data[len..];
+            if self.header_len < self.header.len() {
+                continue;
+            }
+            self.header_len = 0;
+            match u32::from_be_bytes(self.header) as usize {
+                0 => self.count += 1,
+                len if len > file_sink::MAX_FRAME_LENGTH => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "frame exceeds max length",
+                    ))
+                }
+                len => self.remaining = len,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AsyncWrite for RecordCounter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.get_mut().update(buf).map(|_| buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn check_digest(key: &str, expected: &str, hasher: &blake3::Hasher) -> io::Result<()> {
+    let actual = hasher.finalize().to_hex().to_string();
+    if actual == expected {
+        return Ok(());
+    }
+    tracing::error!(key, expected, actual = %actual, "file checksum mismatch");
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        DigestMismatch(key.to_string()),
+    ))
+}
+
+/// The io error a raw file stream fails with when it does not match its
+/// digest
+#[derive(Debug)]
+struct DigestMismatch(String);
+
+impl fmt::Display for DigestMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "digest mismatch for {}", self.0)
+    }
+}
+
+impl std::error::Error for DigestMismatch {}
+
+/// Turn an io error from a raw file stream that did not match its digest back
+/// into a checksum mismatch
+pub(crate) fn map_digest_mismatch(err: Error) -> Error {
+    match err {
+        Error::Io(err) => match err
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<DigestMismatch>())
+        {
+            Some(DigestMismatch(key)) => Error::ChecksumMismatch(key.clone()),
+            None => Error::Io(err),
+        },
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_compression::tokio::write::GzipEncoder;
+
+    #[test]
+    fn counts_records_split_across_writes() {
+        let mut counter = RecordCounter::default();
+        counter.update(&[0, 0]).expect("partial header");
+        counter.update(&[0, 3, b'a']).expect("partial record");
+        assert_eq!(None, counter.count());
+        counter.update(&[b'b', b'c', 0, 0, 0, 0]).expect("records");
+        assert_eq!(Some(2), counter.count());
+    }
+
+    #[test]
+    fn rejects_oversized_records() {
+        let mut counter = RecordCounter::default();
+        let len = (file_sink::MAX_FRAME_LENGTH as u32 + 1).to_be_bytes();
+        assert!(counter.update(&len).is_err());
+    }
+
+    #[tokio::test]
+    async fn checksums_the_whole_file() {
+        let dir = tempfile::TempDir::new().expect("Unable to create temp dir");
+        let path = dir.path().join("entropy_report.1680000000000.gz");
+        let mut encoder = GzipEncoder::new(Vec::new());
+        for record in [&b"one"[..], &b"two"[..]] {
+            encoder
+                .write_all(&(record.len() as u32).to_be_bytes())
+                .await
+                .expect("failed to encode frame");
+            encoder
+                .write_all(record)
+                .await
+                .expect("failed to encode frame");
+        }
+        encoder.shutdown().await.expect("failed to encode frame");
+        let data = encoder.into_inner();
+        fs::write(&path, &data).await.expect("failed to write file");
+
+        let checksum = FileChecksum::from_path(&path)
+            .await
+            .expect("failed to checksum file");
+        assert_eq!(blake3::hash(&data).to_hex().to_string(), checksum.digest);
+        assert_eq!(Some(2), checksum.record_count);
+    }
+}
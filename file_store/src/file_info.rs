@@ -1,4 +1,4 @@
-use crate::{error::DecodeError, traits::TimestampDecode, Error, Result};
+use crate::{error::DecodeError, traits::TimestampDecode, Compression, Error, Result};
 use chrono::{DateTime, Utc};
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -14,7 +14,7 @@ pub struct FileInfo {
 }
 
 lazy_static! {
-    static ref RE: Regex = Regex::new(r"([a-z,_]+).(\d+)(.gz)?").unwrap();
+    static ref RE: Regex = Regex::new(r"([a-z,_]+).(\d+)(.gz|.zst)?").unwrap();
 }
 
 impl FromStr for FileInfo {
@@ -55,10 +55,10 @@ impl From<FileInfo> for String {
     }
 }
 
-impl From<(FileType, DateTime<Utc>)> for FileInfo {
-    fn from(v: (FileType, DateTime<Utc>)) -> Self {
+impl From<(FileType, DateTime<Utc>, Compression)> for FileInfo {
+    fn from(v: (FileType, DateTime<Utc>, Compression)) -> Self {
         Self {
-            key: format!("{}.{}.gz", &v.0, v.1.timestamp_millis()),
+            key: format!("{}.{}.{}", &v.0, v.1.timestamp_millis(), v.2.extension()),
             file_type: v.0,
             timestamp: v.1,
             size: 0,
@@ -121,8 +121,14 @@ pub const NON_REWARDABLE_PACKET: &str = "non_rewardable_packet";
 pub const IOT_REWARD_SHARE: &str = "iot_reward_share";
 pub const DATA_TRANSFER_SESSION_INGEST_REPORT: &str = "data_transfer_session_ingest_report";
 pub const VALID_DATA_TRANSFER_SESSION: &str = "valid_data_transfer_session";
+pub const INVALID_DATA_TRANSFER_SESSION: &str = "invalid_data_transfer_session";
 pub const PRICE_REPORT: &str = "price_report";
 pub const MOBILE_REWARD_SHARE: &str = "mobile_reward_share";
+pub const IOT_SUSPICIOUS_CLUSTER: &str = "iot_suspicious_cluster";
+pub const IOT_HEX_DENSITY: &str = "iot_hex_density";
+pub const IOT_ORG_LOCK_EVENT: &str = "iot_org_lock_event";
+pub const PAYER_LOW_BALANCE: &str = "payer_low_balance";
+pub const IOT_REWARD_SHARE_PREVIEW: &str = "iot_reward_share_preview";
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Copy, strum::EnumCount)]
 #[serde(rename_all = "snake_case")]
@@ -151,10 +157,16 @@ pub enum FileType {
     IotRewardShare,
     DataTransferSessionIngestReport,
     ValidDataTransferSession,
+    InvalidDataTransferSession,
     PriceReport,
     MobileRewardShare,
     SubscriberLocationReq,
     SubscriberLocationIngestReport,
+    IotSuspiciousCluster,
+    IotHexDensity,
+    IotOrgLockEvent,
+    PayerLowBalance,
+    IotRewardSharePreview,
 }
 
 impl fmt::Display for FileType {
@@ -186,8 +198,14 @@ impl fmt::Display for FileType {
             Self::IotRewardShare => IOT_REWARD_SHARE,
             Self::DataTransferSessionIngestReport => DATA_TRANSFER_SESSION_INGEST_REPORT,
             Self::ValidDataTransferSession => VALID_DATA_TRANSFER_SESSION,
+            Self::InvalidDataTransferSession => INVALID_DATA_TRANSFER_SESSION,
             Self::PriceReport => PRICE_REPORT,
             Self::MobileRewardShare => MOBILE_REWARD_SHARE,
+            Self::IotSuspiciousCluster => IOT_SUSPICIOUS_CLUSTER,
+            Self::IotHexDensity => IOT_HEX_DENSITY,
+            Self::IotOrgLockEvent => IOT_ORG_LOCK_EVENT,
+            Self::PayerLowBalance => PAYER_LOW_BALANCE,
+            Self::IotRewardSharePreview => IOT_REWARD_SHARE_PREVIEW,
         };
         f.write_str(s)
     }
@@ -222,8 +240,14 @@ impl FileType {
             Self::IotRewardShare => IOT_REWARD_SHARE,
             Self::DataTransferSessionIngestReport => DATA_TRANSFER_SESSION_INGEST_REPORT,
             Self::ValidDataTransferSession => VALID_DATA_TRANSFER_SESSION,
+            Self::InvalidDataTransferSession => INVALID_DATA_TRANSFER_SESSION,
             Self::PriceReport => PRICE_REPORT,
             Self::MobileRewardShare => MOBILE_REWARD_SHARE,
+            Self::IotSuspiciousCluster => IOT_SUSPICIOUS_CLUSTER,
+            Self::IotHexDensity => IOT_HEX_DENSITY,
+            Self::IotOrgLockEvent => IOT_ORG_LOCK_EVENT,
+            Self::PayerLowBalance => PAYER_LOW_BALANCE,
+            Self::IotRewardSharePreview => IOT_REWARD_SHARE_PREVIEW,
         }
     }
 }
@@ -256,8 +280,14 @@ impl FromStr for FileType {
             IOT_REWARD_SHARE => Self::IotRewardShare,
             DATA_TRANSFER_SESSION_INGEST_REPORT => Self::DataTransferSessionIngestReport,
             VALID_DATA_TRANSFER_SESSION => Self::ValidDataTransferSession,
+            INVALID_DATA_TRANSFER_SESSION => Self::InvalidDataTransferSession,
             PRICE_REPORT => Self::PriceReport,
             MOBILE_REWARD_SHARE => Self::MobileRewardShare,
+            IOT_SUSPICIOUS_CLUSTER => Self::IotSuspiciousCluster,
+            IOT_HEX_DENSITY => Self::IotHexDensity,
+            IOT_ORG_LOCK_EVENT => Self::IotOrgLockEvent,
+            PAYER_LOW_BALANCE => Self::PayerLowBalance,
+            IOT_REWARD_SHARE_PREVIEW => Self::IotRewardSharePreview,
             _ => return Err(Error::from(io::Error::from(io::ErrorKind::InvalidInput))),
         };
         Ok(result)
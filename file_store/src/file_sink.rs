@@ -1,5 +1,4 @@
-use crate::{file_upload, Error, FileType, Result};
-use async_compression::tokio::write::GzipEncoder;
+use crate::{compression::Encoder, file_upload, Compression, Error, FileType, Result};
 use bytes::Bytes;
 use chrono::{DateTime, Duration, Utc};
 use futures::SinkExt;
@@ -28,7 +27,7 @@ pub const SINK_CHECK_MILLIS: i64 = 50;
 
 pub const MAX_FRAME_LENGTH: usize = 15_000_000;
 
-type Sink = GzipEncoder<BufWriter<File>>;
+type Sink = Encoder<BufWriter<File>>;
 type Transport = FramedWrite<Sink, LengthDelimitedCodec>;
 pub type FileManifest = Vec<String>;
 
@@ -64,6 +63,7 @@ pub struct FileSinkBuilder {
     roll_time: Duration,
     deposits: Option<file_upload::MessageSender>,
     auto_commit: bool,
+    compression: Compression,
     metric: &'static str,
     shutdown_listener: triggered::Listener,
 }
@@ -83,6 +83,7 @@ impl FileSinkBuilder {
             roll_time: Duration::minutes(DEFAULT_SINK_ROLL_MINS),
             deposits: None,
             auto_commit: true,
+            compression: Compression::default(),
             metric,
             shutdown_listener,
         }
@@ -124,6 +125,13 @@ impl FileSinkBuilder {
         }
     }
 
+    pub fn compression(self, compression: Compression) -> Self {
+        Self {
+            compression,
+            ..self
+        }
+    }
+
     pub async fn create(self) -> Result<(FileSinkClient, FileSink)> {
         let (tx, rx) = message_channel(50);
 
@@ -145,6 +153,7 @@ impl FileSinkBuilder {
             messages: rx,
             staged_files: Vec::new(),
             auto_commit: self.auto_commit,
+            compression: self.compression,
             active_sink: None,
             shutdown_listener: self.shutdown_listener,
         };
@@ -244,6 +253,7 @@ pub struct FileSink {
     deposits: Option<file_upload::MessageSender>,
     staged_files: Vec<PathBuf>,
     auto_commit: bool,
+    compression: Compression,
 
     active_sink: Option<ActiveSink>,
     shutdown_listener: triggered::Listener,
@@ -362,9 +372,14 @@ impl FileSink {
 
     async fn new_sink(&mut self) -> Result {
         let sink_time = Utc::now();
-        let filename = format!("{}.{}.gz", self.prefix, sink_time.timestamp_millis());
+        let filename = format!(
+            "{}.{}.{}",
+            self.prefix,
+            sink_time.timestamp_millis(),
+            self.compression.extension()
+        );
         let new_path = self.tmp_path.join(filename);
-        let writer = GzipEncoder::new(BufWriter::new(
+        let writer = self.compression.encoder(BufWriter::new(
             OpenOptions::new()
                 .write(true)
                 .create(true)
@@ -553,6 +568,52 @@ mod tests {
         assert_eq!("hello", read_file(&entropy_file).await);
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn writes_a_framed_zstd_encoded_file() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+
+        let (file_sink_client, mut file_sink_server) = FileSinkBuilder::new(
+            FileType::EntropyReport,
+            tmp_dir.path(),
+            "fake_metric",
+            shutdown_listener.clone(),
+        )
+        .roll_time(chrono::Duration::milliseconds(100))
+        .compression(Compression::Zstd)
+        .create()
+        .await
+        .expect("failed to create file sink");
+
+        let sink_thread = tokio::spawn(async move {
+            file_sink_server
+                .run()
+                .await
+                .expect("failed to complete file sink");
+        });
+
+        let (on_write_tx, _on_write_rx) = oneshot::channel();
+
+        file_sink_client
+            .sender
+            .try_send(Message::Data(
+                on_write_tx,
+                String::into_bytes("hello".to_string()),
+            ))
+            .expect("failed to send bytes to file sink");
+
+        tokio::time::sleep(time::Duration::from_millis(200)).await;
+
+        shutdown_trigger.trigger();
+        sink_thread.await.expect("file sink did not complete");
+
+        let entropy_file = get_entropy_file(&tmp_dir)
+            .await
+            .expect("no entropy available");
+        assert!(entropy_file.file_name().to_string_lossy().ends_with(".zst"));
+        assert_eq!("hello", read_file(&entropy_file).await);
+    }
+
     #[tokio::test]
     async fn only_uploads_after_commit_when_auto_commit_is_false() {
         let tmp_dir = TempDir::new().expect("Unable to create temp dir");
@@ -1,5 +1,6 @@
-use crate::{file_info_poller::FileInfoPollerBuilder, file_sink, BytesMutStream, Error};
-use async_compression::tokio::bufread::GzipDecoder;
+use crate::{
+    file_info_poller::FileInfoPollerBuilder, file_sink, BytesMutStream, Compression, Error,
+};
 use futures::{
     stream::{self},
     StreamExt, TryFutureExt, TryStreamExt,
@@ -25,16 +26,21 @@ where
         .map(|path| path.as_ref().to_path_buf())
         .collect();
     stream::iter(paths)
-        .map(|path| File::open(path).map_err(Error::from))
+        .map(|path| {
+            let compression = Compression::from_key(&path.to_string_lossy());
+            File::open(path)
+                .map_ok(move |file| (compression, file))
+                .map_err(Error::from)
+        })
         .buffered(2)
         .flat_map(|file| match file {
-            Ok(file) => {
+            Ok((compression, file)) => {
                 let buf_reader = BufReader::new(file);
                 let codec = LengthDelimitedCodec::builder()
                     .max_frame_length(file_sink::MAX_FRAME_LENGTH)
                     .new_codec();
 
-                FramedRead::new(GzipDecoder::new(buf_reader), codec)
+                FramedRead::new(compression.decoder(buf_reader), codec)
                     .map_err(Error::from)
                     .boxed()
             }
@@ -70,6 +76,7 @@ mod test {
             bucket: "devnet-poc5g-rewards".to_string(),
             endpoint: None,
             region: "us-east-1".to_string(),
+            local_path: None,
             access_key_id: None,
             secret_access_key: None,
         };
@@ -1,19 +1,49 @@
 use crate::{
-    error::DecodeError, BytesMutStream, Error, FileInfo, FileInfoStream, FileType, Result, Settings,
+    file_checksum, local_store::LocalStore, s3_store::S3Store, BytesMutStream, Compression, Error,
+    FileChecksum, FileInfo, FileInfoStream, FileType, Result, Settings,
 };
-use aws_config::meta::region::RegionProviderChain;
-use aws_sdk_s3::{types::ByteStream, Client, Endpoint, Region};
+use async_trait::async_trait;
+use bytes::Bytes;
 use chrono::{DateTime, Utc};
-use futures::FutureExt;
-use futures::{stream, StreamExt, TryFutureExt, TryStreamExt};
-use http::Uri;
-use std::path::Path;
-use std::str::FromStr;
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use std::{fmt, io, path::Path, sync::Arc};
+
+/// A raw stream of (compressed) bytes for a single file in a store
+pub type ByteStream = BoxStream<'static, io::Result<Bytes>>;
+
+/// The storage operations a file store backend must provide. Keys are the
+/// plain file names as produced by `FileInfo`.
+#[async_trait]
+pub trait FileStoreBackend: fmt::Debug + Send + Sync {
+    /// List the files of the given type with a timestamp in the range
+    /// `(after, before]`, ordered by timestamp.
+    fn list(
+        &self,
+        file_type: FileType,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    ) -> FileInfoStream;
+
+    /// The raw stream of a stored file along with its recorded checksum, if
+    /// any, fetched in a single request
+    async fn get_raw(&self, key: String) -> Result<(ByteStream, Option<FileChecksum>)>;
+
+    /// The checksum recorded for the given key, if any
+    async fn checksum(&self, key: &str) -> Result<Option<FileChecksum>>;
+
+    /// Store the given file under its file name along with its checksum
+    async fn put(&self, file: &Path, checksum: &FileChecksum) -> Result;
+
+    async fn remove(&self, key: &str) -> Result;
+}
 
 #[derive(Debug, Clone)]
 pub struct FileStore {
     pub(crate) bucket: String,
-    client: Client,
+    backend: Arc<dyn FileStoreBackend>,
 }
 
 pub struct FileData {
@@ -23,38 +53,26 @@ pub struct FileData {
 
 impl FileStore {
     pub async fn from_settings(settings: &Settings) -> Result<Self> {
-        let endpoint: Option<Endpoint> = match &settings.endpoint {
-            Some(endpoint) => Uri::from_str(endpoint)
-                .map(Endpoint::immutable)
-                .map(Some)
-                .map_err(DecodeError::from)?,
-            _ => None,
-        };
-        let region = Region::new(settings.region.clone());
-        let region_provider = RegionProviderChain::first_try(region).or_default_provider();
-
-        let mut config = aws_config::from_env().region(region_provider);
-        if let Some(endpoint) = endpoint {
-            config = config.endpoint_resolver(endpoint);
+        match &settings.local_path {
+            Some(path) => Ok(Self::new(
+                settings.bucket.clone(),
+                LocalStore::new(path).await?,
+            )),
+            None => Ok(Self::new(
+                settings.bucket.clone(),
+                S3Store::from_settings(settings).await?,
+            )),
         }
+    }
 
-        #[cfg(feature = "local")]
-        if settings.access_key_id.is_some() && settings.secret_access_key.is_some() {
-            let creds = aws_types::credentials::Credentials::from_keys(
-                settings.access_key_id.as_ref().unwrap(),
-                settings.secret_access_key.as_ref().unwrap(),
-                None,
-            );
-            config = config.credentials_provider(creds);
+    pub fn new<B>(bucket: impl Into<String>, backend: B) -> Self
+    where
+        B: FileStoreBackend + 'static,
+    {
+        Self {
+            bucket: bucket.into(),
+            backend: Arc::new(backend),
         }
-
-        let config = config.load().await;
-
-        let client = Client::new(&config);
-        Ok(Self {
-            client,
-            bucket: settings.bucket.clone(),
-        })
     }
 
     pub async fn list_all<A, B, F>(
@@ -77,115 +95,76 @@ impl FileStore {
         A: Into<Option<DateTime<Utc>>> + Copy,
         B: Into<Option<DateTime<Utc>>> + Copy,
     {
-        let file_type = file_type.into();
-        let before = before.into();
-        let after = after.into();
-
-        let request = self
-            .client
-            .list_objects_v2()
-            .bucket(&self.bucket)
-            .prefix(file_type.to_string())
-            .set_start_after(after.map(|dt| FileInfo::from((file_type, dt)).into()));
-
-        futures::stream::unfold(
-            (request, true, None),
-            |(req, first_time, next)| async move {
-                if first_time || next.is_some() {
-                    let list_objects_response =
-                        req.clone().set_continuation_token(next).send().await;
-
-                    let next_token = list_objects_response
-                        .as_ref()
-                        .ok()
-                        .and_then(|r| r.next_continuation_token())
-                        .map(|x| x.to_owned());
-
-                    Some((list_objects_response, (req, false, next_token)))
-                } else {
-                    None
-                }
-            },
-        )
-        .flat_map(move |entry| match entry {
-            Ok(output) => {
-                let filtered = output
-                    .contents
-                    .unwrap_or_default()
-                    .into_iter()
-                    .filter_map(|obj| {
-                        if FileInfo::matches(obj.key().unwrap_or_default()) {
-                            Some(FileInfo::try_from(&obj).unwrap())
-                        } else {
-                            None
-                        }
-                    })
-                    .filter(move |info| after.map_or(true, |v| info.timestamp > v))
-                    .filter(move |info| before.map_or(true, |v| info.timestamp <= v))
-                    .map(Ok);
-                stream::iter(filtered).boxed()
-            }
-            Err(err) => stream::once(async move { Err(Error::s3_error(err)) }).boxed(),
-        })
-        .boxed()
+        self.backend
+            .list(file_type.into(), after.into(), before.into())
     }
 
     pub async fn put(&self, file: &Path) -> Result {
-        let byte_stream = ByteStream::from_path(&file)
-            .await
-            .map_err(|_| Error::not_found(format!("could not open {}", file.display())))?;
+        let checksum = FileChecksum::from_path(file).await?;
         poc_metrics::record_duration!(
             "file_store_put_duration",
-            self.client
-                .put_object()
-                .bucket(&self.bucket)
-                .key(file.file_name().map(|name| name.to_string_lossy()).unwrap())
-                .body(byte_stream)
-                .send()
-                .map_ok(|_| ())
-                .map_err(Error::s3_error)
-                .await
+            self.backend.put(file, &checksum).await
         )
     }
 
-    pub async fn remove(&self, key: &str) -> Result {
+    /// Store the given file with an already known checksum, like that of
+    /// the file it is a copy of
+    pub(crate) async fn put_with_checksum(&self, file: &Path, checksum: &FileChecksum) -> Result {
         poc_metrics::record_duration!(
-            "file_store_remove_duration",
-            self.client
-                .delete_object()
-                .bucket(&self.bucket)
-                .key(key)
-                .send()
-                .map_ok(|_| ())
-                .map_err(Error::s3_error)
-                .await
+            "file_store_put_duration",
+            self.backend.put(file, checksum).await
         )
     }
 
+    pub async fn checksum(&self, key: &str) -> Result<Option<FileChecksum>> {
+        self.backend.checksum(key).await
+    }
+
+    pub async fn remove(&self, key: &str) -> Result {
+        poc_metrics::record_duration!("file_store_remove_duration", self.backend.remove(key).await)
+    }
+
+    /// The raw stream of a stored file. When the file has a recorded
+    /// checksum the stream fails with an io error at its end if the bytes
+    /// don't match the digest.
     pub async fn get_raw<K>(&self, key: K) -> Result<ByteStream>
     where
         K: Into<String>,
     {
-        get_byte_stream(self.client.clone(), self.bucket.clone(), key).await
+        let (stream, _) = self.get_raw_verified(key.into()).await?;
+        Ok(stream)
+    }
+
+    /// The raw stream of a stored file along with its recorded checksum, if
+    /// any. The stream is verified against the digest as it is read.
+    pub(crate) async fn get_raw_verified(
+        &self,
+        key: String,
+    ) -> Result<(ByteStream, Option<FileChecksum>)> {
+        let (stream, checksum) = self.backend.get_raw(key.clone()).await?;
+        let stream = match &checksum {
+            Some(checksum) => checksum.verify_digest(&key, stream),
+            None => stream,
+        };
+        Ok((stream, checksum))
     }
 
     pub async fn get<K>(&self, key: K) -> Result<BytesMutStream>
     where
         K: Into<String>,
     {
-        Ok(stream_source(self.get_raw(key).await?))
+        get_source(self.backend.clone(), key.into()).await
     }
 
     /// Stream a series of ordered items from the store from remote files with
     /// the given keys.
     pub fn source(&self, infos: FileInfoStream) -> BytesMutStream {
-        let bucket = self.bucket.clone();
-        let client = self.client.clone();
+        let backend = self.backend.clone();
         infos
-            .map_ok(move |info| get_byte_stream(client.clone(), bucket.clone(), info.key))
+            .map_ok(move |info| get_source(backend.clone(), info.key))
             .try_buffered(2)
             .flat_map(|stream| match stream {
-                Ok(stream) => stream_source(stream),
+                Ok(stream) => stream,
                 Err(err) => stream::once(async move { Err(err) }).boxed(),
             })
             .fuse()
@@ -197,13 +176,12 @@ impl FileStore {
     /// stream of buffers to be produced as soon as available from up to
     /// "worker" number of remote files
     pub fn source_unordered(&self, workers: usize, infos: FileInfoStream) -> BytesMutStream {
-        let bucket = self.bucket.clone();
-        let client = self.client.clone();
+        let backend = self.backend.clone();
         infos
-            .map_ok(move |info| get_byte_stream(client.clone(), bucket.clone(), info.key))
+            .map_ok(move |info| get_source(backend.clone(), info.key))
             .try_buffer_unordered(workers)
             .flat_map(|stream| match stream {
-                Ok(stream) => stream_source(stream),
+                Ok(stream) => stream,
                 Err(err) => stream::once(async move { Err(err) }).boxed(),
             })
             .fuse()
@@ -211,14 +189,11 @@ impl FileStore {
     }
 
     pub async fn stream_file(&self, file_info: FileInfo) -> Result<BytesMutStream> {
-        get_byte_stream(self.client.clone(), self.bucket.clone(), file_info)
-            .await
-            .map(stream_source)
+        get_source(self.backend.clone(), file_info.key).await
     }
 }
 
-fn stream_source(stream: ByteStream) -> BytesMutStream {
-    use async_compression::tokio::bufread::GzipDecoder;
+fn stream_source(compression: Compression, stream: ByteStream) -> BytesMutStream {
     use tokio_util::{
         codec::{length_delimited::LengthDelimitedCodec, FramedRead},
         io::StreamReader,
@@ -226,24 +201,26 @@ fn stream_source(stream: ByteStream) -> BytesMutStream {
 
     Box::pin(
         FramedRead::new(
-            GzipDecoder::new(StreamReader::new(stream)),
+            compression.decoder(StreamReader::new(stream)),
             LengthDelimitedCodec::new(),
         )
         .map_err(Error::from),
     )
 }
 
-async fn get_byte_stream<K>(client: Client, bucket: String, key: K) -> Result<ByteStream>
-where
-    K: Into<String>,
-{
-    client
-        .get_object()
-        .bucket(bucket)
-        .key(key)
-        .send()
-        .map_ok(|output| output.body)
-        .map_err(Error::s3_error)
-        .fuse()
-        .await
+/// Get a decoded stream for the given key. When the file has a recorded
+/// checksum, its digest is verified while it is streamed and its record count
+/// at the end of the stream, failing the stream with a checksum mismatch if
+/// either does not match.
+async fn get_source(backend: Arc<dyn FileStoreBackend>, key: String) -> Result<BytesMutStream> {
+    let compression = Compression::from_key(&key);
+    let (stream, checksum) = backend.get_raw(key.clone()).await?;
+    let Some(checksum) = checksum else {
+        return Ok(stream_source(compression, stream));
+    };
+
+    let records = stream_source(compression, checksum.verify_digest(&key, stream))
+        .map_err(file_checksum::map_digest_mismatch)
+        .boxed();
+    Ok(checksum.verify_record_count(&key, records))
 }
@@ -0,0 +1,142 @@
+use crate::{
+    iot_valid_poc::SCALE_MULTIPLIER,
+    traits::{MsgDecode, MsgTimestamp, TimestampDecode, TimestampEncode},
+    Error, Result, SCALING_PRECISION,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::Serialize;
+
+/// The transmit scale of an asserted hex as derived by the HIP-17 density
+/// calculation, together with the clipping of its parent at each resolution
+/// which produced it. The pinned helium-proto has no message for these
+/// snapshots yet, so they are encoded with the prost messages below
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IotHexDensityV1 {
+    /// Time of the snapshot, in millis
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    /// The asserted hex
+    #[prost(uint64, tag = "2")]
+    pub hex: u64,
+    /// Transmit scale of the hex, scaled by 10000
+    #[prost(uint32, tag = "3")]
+    pub hex_scale: u32,
+    #[prost(message, repeated, tag = "4")]
+    pub resolutions: Vec<IotHexDensityResV1>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IotHexDensityResV1 {
+    #[prost(uint32, tag = "1")]
+    pub resolution: u32,
+    /// Parent of the asserted hex at this resolution
+    #[prost(uint64, tag = "2")]
+    pub hex: u64,
+    /// Sum of the clipped counts of the children of the parent
+    #[prost(uint64, tag = "3")]
+    pub unclipped: u64,
+    /// Count of the parent after clipping to the limit
+    #[prost(uint64, tag = "4")]
+    pub clipped: u64,
+    /// Number of hexes in the k-ring of the parent at or above the density
+    /// target of this resolution
+    #[prost(uint64, tag = "5")]
+    pub occupied_neighbors: u64,
+    #[prost(uint64, tag = "6")]
+    pub limit: u64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct IotHexDensity {
+    pub timestamp: DateTime<Utc>,
+    pub hex: u64,
+    pub hex_scale: Decimal,
+    pub resolutions: Vec<IotHexDensityRes>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct IotHexDensityRes {
+    pub resolution: u8,
+    pub hex: u64,
+    pub unclipped: u64,
+    pub clipped: u64,
+    pub occupied_neighbors: u64,
+    pub limit: u64,
+}
+
+impl MsgTimestamp<u64> for IotHexDensity {
+    fn timestamp(&self) -> u64 {
+        self.timestamp.encode_timestamp_millis()
+    }
+}
+
+impl MsgTimestamp<Result<DateTime<Utc>>> for IotHexDensityV1 {
+    fn timestamp(&self) -> Result<DateTime<Utc>> {
+        self.timestamp.to_timestamp_millis()
+    }
+}
+
+impl MsgDecode for IotHexDensity {
+    type Msg = IotHexDensityV1;
+}
+
+impl TryFrom<IotHexDensityV1> for IotHexDensity {
+    type Error = Error;
+
+    fn try_from(v: IotHexDensityV1) -> Result<Self> {
+        let timestamp = v.timestamp()?;
+        Ok(Self {
+            timestamp,
+            hex: v.hex,
+            hex_scale: Decimal::new(v.hex_scale as i64, SCALING_PRECISION),
+            resolutions: v
+                .resolutions
+                .into_iter()
+                .map(IotHexDensityRes::from)
+                .collect(),
+        })
+    }
+}
+
+impl From<IotHexDensity> for IotHexDensityV1 {
+    fn from(v: IotHexDensity) -> Self {
+        let timestamp = v.timestamp();
+        Self {
+            timestamp,
+            hex: v.hex,
+            hex_scale: (v.hex_scale * SCALE_MULTIPLIER).to_u32().unwrap_or(0),
+            resolutions: v
+                .resolutions
+                .into_iter()
+                .map(IotHexDensityResV1::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<IotHexDensityResV1> for IotHexDensityRes {
+    fn from(v: IotHexDensityResV1) -> Self {
+        Self {
+            resolution: v.resolution as u8,
+            hex: v.hex,
+            unclipped: v.unclipped,
+            clipped: v.clipped,
+            occupied_neighbors: v.occupied_neighbors,
+            limit: v.limit,
+        }
+    }
+}
+
+impl From<IotHexDensityRes> for IotHexDensityResV1 {
+    fn from(v: IotHexDensityRes) -> Self {
+        Self {
+            resolution: v.resolution as u32,
+            hex: v.hex,
+            unclipped: v.unclipped,
+            clipped: v.clipped,
+            occupied_neighbors: v.occupied_neighbors,
+            limit: v.limit,
+        }
+    }
+}
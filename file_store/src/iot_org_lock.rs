@@ -0,0 +1,88 @@
+use crate::{
+    traits::{MsgDecode, MsgTimestamp, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use serde::Serialize;
+
+/// Encoded org lock transition. Not part of the pinned helium-proto yet, so
+/// the prost message is kept here
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IotOrgLockEventV1 {
+    /// Time of the transition, in millis
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    #[prost(uint64, tag = "2")]
+    pub oui: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub payer: Vec<u8>,
+    /// Whether the org was locked, or else unlocked
+    #[prost(bool, tag = "4")]
+    pub locked: bool,
+    /// Balance of the payer in data credits at the time of the transition
+    #[prost(uint64, tag = "5")]
+    pub balance: u64,
+    /// Balance below which the org is locked, or at or above which it is
+    /// unlocked
+    #[prost(uint64, tag = "6")]
+    pub threshold: u64,
+}
+
+/// A transition of an iot org between locked and unlocked made by the packet
+/// verifier, with the balance of the payer of the org which caused it
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct IotOrgLockEvent {
+    pub timestamp: DateTime<Utc>,
+    pub oui: u64,
+    pub payer: PublicKeyBinary,
+    pub locked: bool,
+    pub balance: u64,
+    pub threshold: u64,
+}
+
+impl MsgTimestamp<u64> for IotOrgLockEvent {
+    fn timestamp(&self) -> u64 {
+        self.timestamp.encode_timestamp_millis()
+    }
+}
+
+impl MsgTimestamp<Result<DateTime<Utc>>> for IotOrgLockEventV1 {
+    fn timestamp(&self) -> Result<DateTime<Utc>> {
+        self.timestamp.to_timestamp_millis()
+    }
+}
+
+impl MsgDecode for IotOrgLockEvent {
+    type Msg = IotOrgLockEventV1;
+}
+
+impl TryFrom<IotOrgLockEventV1> for IotOrgLockEvent {
+    type Error = Error;
+
+    fn try_from(v: IotOrgLockEventV1) -> Result<Self> {
+        let timestamp = v.timestamp()?;
+        Ok(Self {
+            timestamp,
+            oui: v.oui,
+            payer: v.payer.into(),
+            locked: v.locked,
+            balance: v.balance,
+            threshold: v.threshold,
+        })
+    }
+}
+
+impl From<IotOrgLockEvent> for IotOrgLockEventV1 {
+    fn from(v: IotOrgLockEvent) -> Self {
+        let timestamp = v.timestamp();
+        Self {
+            timestamp,
+            oui: v.oui,
+            payer: v.payer.into(),
+            locked: v.locked,
+            balance: v.balance,
+            threshold: v.threshold,
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{
+    traits::{MsgDecode, MsgTimestamp, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use serde::Serialize;
+
+/// Wire form of a suspicious cluster report. The pinned helium-proto has no
+/// message for these reports yet, so the prost message lives here
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IotSuspiciousClusterV1 {
+    /// Time the cluster was detected, in millis
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    /// Start of the window of verified pocs examined, in millis
+    #[prost(uint64, tag = "2")]
+    pub window_start: u64,
+    /// End of the window of verified pocs examined, in millis
+    #[prost(uint64, tag = "3")]
+    pub window_end: u64,
+    /// Position of the cluster among those detected in the same run, most
+    /// suspicious first
+    #[prost(uint32, tag = "4")]
+    pub rank: u32,
+    #[prost(double, tag = "5")]
+    pub score: f64,
+    #[prost(double, tag = "6")]
+    pub reciprocity: f64,
+    #[prost(double, tag = "7")]
+    pub exclusivity: f64,
+    /// Witnesses of member beacons by other members
+    #[prost(uint64, tag = "8")]
+    pub internal_witnesses: u64,
+    /// Witnesses of member beacons plus witnesses by members of any beacon
+    #[prost(uint64, tag = "9")]
+    pub total_witnesses: u64,
+    #[prost(bytes = "vec", repeated, tag = "10")]
+    pub members: Vec<Vec<u8>>,
+}
+
+/// A group of iot gateways found to predominantly witness each other's
+/// beacons over a window of verified pocs
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct IotSuspiciousCluster {
+    pub timestamp: DateTime<Utc>,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub rank: u32,
+    pub score: f64,
+    pub reciprocity: f64,
+    pub exclusivity: f64,
+    pub internal_witnesses: u64,
+    pub total_witnesses: u64,
+    pub members: Vec<PublicKeyBinary>,
+}
+
+impl MsgTimestamp<u64> for IotSuspiciousCluster {
+    fn timestamp(&self) -> u64 {
+        self.timestamp.encode_timestamp_millis()
+    }
+}
+
+impl MsgTimestamp<Result<DateTime<Utc>>> for IotSuspiciousClusterV1 {
+    fn timestamp(&self) -> Result<DateTime<Utc>> {
+        self.timestamp.to_timestamp_millis()
+    }
+}
+
+impl MsgDecode for IotSuspiciousCluster {
+    type Msg = IotSuspiciousClusterV1;
+}
+
+impl TryFrom<IotSuspiciousClusterV1> for IotSuspiciousCluster {
+    type Error = Error;
+
+    fn try_from(v: IotSuspiciousClusterV1) -> Result<Self> {
+        let timestamp = v.timestamp()?;
+        Ok(Self {
+            timestamp,
+            window_start: v.window_start.to_timestamp_millis()?,
+            window_end: v.window_end.to_timestamp_millis()?,
+            rank: v.rank,
+            score: v.score,
+            reciprocity: v.reciprocity,
+            exclusivity: v.exclusivity,
+            internal_witnesses: v.internal_witnesses,
+            total_witnesses: v.total_witnesses,
+            members: v.members.into_iter().map(PublicKeyBinary::from).collect(),
+        })
+    }
+}
+
+impl From<IotSuspiciousCluster> for IotSuspiciousClusterV1 {
+    fn from(v: IotSuspiciousCluster) -> Self {
+        let timestamp = v.timestamp();
+        Self {
+            timestamp,
+            window_start: v.window_start.encode_timestamp_millis(),
+            window_end: v.window_end.encode_timestamp_millis(),
+            rank: v.rank,
+            score: v.score,
+            reciprocity: v.reciprocity,
+            exclusivity: v.exclusivity,
+            internal_witnesses: v.internal_witnesses,
+            total_witnesses: v.total_witnesses,
+            members: v.members.into_iter().map(Vec::from).collect(),
+        }
+    }
+}
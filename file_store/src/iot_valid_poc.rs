@@ -16,7 +16,7 @@ use rust_decimal::{prelude::ToPrimitive, Decimal};
 use rust_decimal_macros::dec;
 use serde::Serialize;
 
-const SCALE_MULTIPLIER: Decimal = dec!(10000);
+pub const SCALE_MULTIPLIER: Decimal = dec!(10000);
 pub const SCALING_PRECISION: u32 = 4;
 
 #[derive(Serialize, Clone, Debug)]
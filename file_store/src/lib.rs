@@ -1,6 +1,8 @@
 pub mod cli;
+mod compression;
 pub mod entropy_report;
 mod error;
+pub mod file_checksum;
 mod file_info;
 pub mod file_info_poller;
 pub mod file_sink;
@@ -9,23 +11,31 @@ pub mod file_store;
 pub mod file_upload;
 pub mod heartbeat;
 pub mod iot_beacon_report;
+pub mod iot_hex_density;
 pub mod iot_invalid_poc;
+pub mod iot_org_lock;
 pub mod iot_packet;
+pub mod iot_suspicious_cluster;
 pub mod iot_valid_poc;
 pub mod iot_witness_report;
+pub mod local_store;
 pub mod mobile_session;
 pub mod mobile_subscriber;
 pub mod mobile_transfer;
+pub mod payer_low_balance;
 pub mod reward_manifest;
+pub mod s3_store;
 mod settings;
 pub mod speedtest;
 pub mod traits;
 
-pub use crate::file_store::FileStore;
+pub use crate::file_store::{FileStore, FileStoreBackend};
+pub use compression::Compression;
 pub use error::{Error, Result};
+pub use file_checksum::FileChecksum;
 pub use file_info::{FileInfo, FileType};
 pub use file_sink::{FileSink, FileSinkBuilder};
-pub use iot_valid_poc::SCALING_PRECISION;
+pub use iot_valid_poc::{SCALE_MULTIPLIER, SCALING_PRECISION};
 pub use settings::Settings;
 
 use bytes::BytesMut;
@@ -0,0 +1,349 @@
+use crate::{
+    file_store::{ByteStream, FileStoreBackend},
+    Error, FileChecksum, FileInfo, FileInfoStream, FileType, Result,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt, TryStreamExt};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use tokio::fs;
+use tokio_util::io::ReaderStream;
+
+const CHECKSUM_DIR: &str = ".checksums";
+
+/// A file store backend using a directory on the local filesystem. Files are
+/// stored flat in the directory under their key, which makes it usable for
+/// running services and integration tests without an object store. Checksums
+/// are kept as json files in a `.checksums` subdirectory.
+#[derive(Debug, Clone)]
+pub struct LocalStore {
+    path: PathBuf,
+}
+
+impl LocalStore {
+    pub async fn new(path: &Path) -> Result<Self> {
+        fs::create_dir_all(path.join(CHECKSUM_DIR)).await?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn checksum_path(&self, key: &str) -> PathBuf {
+        self.path.join(CHECKSUM_DIR).join(format!("{key}.json"))
+    }
+
+    async fn list_files(
+        path: PathBuf,
+        file_type: FileType,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<FileInfo>> {
+        let prefix = file_type.to_string();
+        let mut infos = Vec::new();
+        let mut dir = fs::read_dir(&path).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let key = entry.file_name().to_string_lossy().to_string();
+            if !key.starts_with(&prefix) || !FileInfo::matches(&key) {
+                continue;
+            }
+            let mut info = match FileInfo::from_str(&key) {
+                Ok(info) if info.file_type == file_type => info,
+                _ => continue,
+            };
+            if after.map_or(false, |v| info.timestamp <= v)
+                || before.map_or(false, |v| info.timestamp > v)
+            {
+                continue;
+            }
+            info.size = entry.metadata().await?.len() as usize;
+            infos.push(info);
+        }
+        infos.sort_by_key(|info| info.timestamp);
+        Ok(infos)
+    }
+}
+
+/// Hidden path next to the given one that a file is written to before being
+/// renamed into place, so readers never see a partially written file
+fn tmp_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{file_name}.tmp"))
+}
+
+#[async_trait]
+impl FileStoreBackend for LocalStore {
+    fn list(
+        &self,
+        file_type: FileType,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    ) -> FileInfoStream {
+        stream::once(Self::list_files(
+            self.path.clone(),
+            file_type,
+            after,
+            before,
+        ))
+        .map_ok(|infos| stream::iter(infos.into_iter().map(Ok)))
+        .try_flatten()
+        .boxed()
+    }
+
+    async fn get_raw(&self, key: String) -> Result<(ByteStream, Option<FileChecksum>)> {
+        let path = self.path.join(&key);
+        let file = fs::File::open(&path)
+            .await
+            .map_err(|_| Error::not_found(format!("could not open {}", path.display())))?;
+        let checksum = self.checksum(&key).await?;
+        Ok((ReaderStream::new(file).boxed(), checksum))
+    }
+
+    async fn checksum(&self, key: &str) -> Result<Option<FileChecksum>> {
+        match fs::read(self.checksum_path(key)).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Error::from(err)),
+        }
+    }
+
+    async fn put(&self, file: &Path, checksum: &FileChecksum) -> Result {
+        let file_name = file
+            .file_name()
+            .ok_or_else(|| Error::not_found(format!("could not open {}", file.display())))?;
+        let key = file_name.to_string_lossy();
+        // The checksum goes in place before the file so a listed file can
+        // always be verified
+        let checksum_path = self.checksum_path(&key);
+        let tmp_checksum_path = tmp_path(&checksum_path);
+        fs::write(&tmp_checksum_path, serde_json::to_vec(checksum)?).await?;
+        fs::rename(&tmp_checksum_path, &checksum_path).await?;
+
+        let path = self.path.join(file_name);
+        let tmp_file_path = tmp_path(&path);
+        fs::copy(file, &tmp_file_path).await?;
+        fs::rename(&tmp_file_path, &path).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result {
+        fs::remove_file(self.path.join(key)).await?;
+        match fs::remove_file(self.checksum_path(key)).await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(Error::from(err)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Compression, FileStore};
+    use async_compression::tokio::write::GzipEncoder;
+    use bytes::BytesMut;
+    use chrono::{Duration, TimeZone};
+    use tempfile::TempDir;
+    use tokio::io::AsyncWriteExt;
+
+    async fn touch(dir: &Path, file_type: FileType, timestamp: DateTime<Utc>) -> String {
+        let info = FileInfo::from((file_type, timestamp, Compression::Gzip));
+        let mut encoder = GzipEncoder::new(Vec::new());
+        encoder
+            .write_all(&[0, 0, 0, 4])
+            .await
+            .expect("failed to encode frame");
+        encoder
+            .write_all(b"data")
+            .await
+            .expect("failed to encode frame");
+        encoder.shutdown().await.expect("failed to encode frame");
+        fs::write(dir.join(&info.key), encoder.into_inner())
+            .await
+            .expect("failed to write file");
+        info.key
+    }
+
+    async fn local_store(dir: &TempDir) -> FileStore {
+        FileStore::new(
+            "local",
+            LocalStore::new(dir.path())
+                .await
+                .expect("failed to create local store"),
+        )
+    }
+
+    #[tokio::test]
+    async fn lists_files_in_range_ordered_by_timestamp() {
+        let src_dir = TempDir::new().expect("Unable to create temp dir");
+        let store_dir = TempDir::new().expect("Unable to create temp dir");
+        let store = local_store(&store_dir).await;
+
+        let start = Utc.timestamp_opt(1_680_000_000, 0).single().unwrap();
+        for offset in [3, 1, 2, 5] {
+            let key = touch(
+                src_dir.path(),
+                FileType::EntropyReport,
+                start + Duration::minutes(offset),
+            )
+            .await;
+            store
+                .put(&src_dir.path().join(key))
+                .await
+                .expect("failed to put file");
+        }
+        let other = touch(src_dir.path(), FileType::PriceReport, start).await;
+        store
+            .put(&src_dir.path().join(other))
+            .await
+            .expect("failed to put file");
+
+        let infos = store
+            .list_all(
+                FileType::EntropyReport,
+                start + Duration::minutes(1),
+                start + Duration::minutes(3),
+            )
+            .await
+            .expect("failed to list files");
+        let timestamps: Vec<DateTime<Utc>> = infos.iter().map(|info| info.timestamp).collect();
+        assert_eq!(
+            vec![start + Duration::minutes(2), start + Duration::minutes(3)],
+            timestamps
+        );
+        assert!(infos.iter().all(|info| info.size > 0));
+
+        store
+            .remove(&infos[0].key)
+            .await
+            .expect("failed to remove file");
+        let remaining = store
+            .list_all(FileType::EntropyReport, None, None)
+            .await
+            .expect("failed to list files");
+        assert_eq!(3, remaining.len());
+        assert!(!store_dir
+            .path()
+            .join(format!(".{}.tmp", infos[1].key))
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn rejects_files_not_matching_their_checksum() {
+        let src_dir = TempDir::new().expect("Unable to create temp dir");
+        let store_dir = TempDir::new().expect("Unable to create temp dir");
+        let store = local_store(&store_dir).await;
+
+        let start = Utc.timestamp_opt(1_680_000_000, 0).single().unwrap();
+        let key = touch(src_dir.path(), FileType::EntropyReport, start).await;
+        store
+            .put(&src_dir.path().join(&key))
+            .await
+            .expect("failed to put file");
+
+        let checksum = store
+            .checksum(&key)
+            .await
+            .expect("failed to read checksum")
+            .expect("no checksum recorded");
+        assert_eq!(Some(1), checksum.record_count);
+        assert_eq!(
+            1,
+            store
+                .get(key.as_str())
+                .await
+                .expect("failed to get file")
+                .count()
+                .await
+        );
+
+        let mut data = fs::read(store_dir.path().join(&key))
+            .await
+            .expect("failed to read file");
+        data.truncate(data.len() - 1);
+        fs::write(store_dir.path().join(&key), data)
+            .await
+            .expect("failed to write file");
+        let results: Vec<Result<BytesMut>> = store
+            .get(key.as_str())
+            .await
+            .expect("failed to get file")
+            .collect()
+            .await;
+        assert!(matches!(
+            results.last(),
+            Some(Err(Error::ChecksumMismatch(_)))
+        ));
+        let raw: Vec<io::Result<bytes::Bytes>> = store
+            .get_raw(key.as_str())
+            .await
+            .expect("failed to get raw file")
+            .collect()
+            .await;
+        assert!(matches!(raw.last(), Some(Err(err)) if err.kind() == io::ErrorKind::InvalidData));
+    }
+
+    #[tokio::test]
+    async fn rejects_files_not_matching_their_record_count() {
+        let src_dir = TempDir::new().expect("Unable to create temp dir");
+        let store_dir = TempDir::new().expect("Unable to create temp dir");
+        let store = local_store(&store_dir).await;
+
+        let start = Utc.timestamp_opt(1_680_000_000, 0).single().unwrap();
+        let key = touch(src_dir.path(), FileType::EntropyReport, start).await;
+        store
+            .put(&src_dir.path().join(&key))
+            .await
+            .expect("failed to put file");
+
+        let checksum_path = store_dir
+            .path()
+            .join(CHECKSUM_DIR)
+            .join(format!("{key}.json"));
+        let mut checksum: FileChecksum = serde_json::from_slice(
+            &fs::read(&checksum_path)
+                .await
+                .expect("failed to read checksum"),
+        )
+        .expect("failed to decode checksum");
+        checksum.record_count = Some(2);
+        fs::write(
+            &checksum_path,
+            serde_json::to_vec(&checksum).expect("failed to encode checksum"),
+        )
+        .await
+        .expect("failed to write checksum");
+
+        let results: Vec<Result<BytesMut>> = store
+            .get(key.as_str())
+            .await
+            .expect("failed to get file")
+            .collect()
+            .await;
+        assert_eq!(2, results.len());
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(Error::ChecksumMismatch(_))));
+    }
+
+    #[tokio::test]
+    async fn stores_files_without_records() {
+        let src_dir = TempDir::new().expect("Unable to create temp dir");
+        let store_dir = TempDir::new().expect("Unable to create temp dir");
+        let store = local_store(&store_dir).await;
+
+        let path = src_dir.path().join("notes.txt");
+        fs::write(&path, b"not length delimited")
+            .await
+            .expect("failed to write file");
+        store.put(&path).await.expect("failed to put file");
+
+        let checksum = store
+            .checksum("notes.txt")
+            .await
+            .expect("failed to read checksum")
+            .expect("no checksum recorded");
+        assert_eq!(None, checksum.record_count);
+    }
+}
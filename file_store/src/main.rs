@@ -1,6 +1,6 @@
 use clap::Parser;
 use file_store::{
-    cli::{bucket, dump, info},
+    cli::{bucket, dump, export, info, verify},
     Result, Settings,
 };
 use std::path;
@@ -29,6 +29,8 @@ pub enum Cmd {
     Info(info::Cmd),
     Dump(dump::Cmd),
     Bucket(Box<bucket::Cmd>),
+    Verify(verify::Cmd),
+    Export(export::Cmd),
 }
 
 impl Cmd {
@@ -37,6 +39,8 @@ impl Cmd {
             Cmd::Info(cmd) => cmd.run(&settings).await,
             Cmd::Dump(cmd) => cmd.run(&settings).await,
             Cmd::Bucket(cmd) => cmd.run(&settings).await,
+            Cmd::Verify(cmd) => cmd.run(&settings).await,
+            Cmd::Export(cmd) => cmd.run(&settings).await,
         }
     }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    traits::{MsgDecode, TimestampDecode},
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
     Error, Result,
 };
 use chrono::{DateTime, Utc};
@@ -39,3 +39,91 @@ impl TryFrom<proto::ValidDataTransferSession> for ValidDataTransferSession {
         })
     }
 }
+
+/// A data transfer session rejected by the mobile packet verifier, and so
+/// neither burned for nor rewarded. There is no helium proto message for
+/// these reports, the message is defined here instead
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct InvalidDataTransferSessionV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub pub_key: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub payer: Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    pub upload_bytes: u64,
+    #[prost(uint64, tag = "4")]
+    pub download_bytes: u64,
+    /// Data credits the session would have been charged
+    #[prost(uint64, tag = "5")]
+    pub num_dcs: u64,
+    /// Time of the first report of the session, in millis
+    #[prost(uint64, tag = "6")]
+    pub first_timestamp: u64,
+    /// Time of the last report of the session, in millis
+    #[prost(uint64, tag = "7")]
+    pub last_timestamp: u64,
+    #[prost(enumeration = "InvalidDataTransferReason", tag = "8")]
+    pub reason: i32,
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, prost::Enumeration,
+)]
+#[repr(i32)]
+pub enum InvalidDataTransferReason {
+    /// The payer's balance couldn't cover the session
+    InsufficientBalance = 0,
+    /// The gateway of the session isn't known to the mobile config server
+    UnknownGateway = 1,
+    /// The reward of the session was cancelled by the reporter
+    RewardCancelled = 2,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct InvalidDataTransferSession {
+    pub pub_key: PublicKeyBinary,
+    pub payer: PublicKeyBinary,
+    pub upload_bytes: u64,
+    pub download_bytes: u64,
+    pub num_dcs: u64,
+    pub first_timestamp: DateTime<Utc>,
+    pub last_timestamp: DateTime<Utc>,
+    pub reason: InvalidDataTransferReason,
+}
+
+impl MsgDecode for InvalidDataTransferSession {
+    type Msg = InvalidDataTransferSessionV1;
+}
+
+impl TryFrom<InvalidDataTransferSessionV1> for InvalidDataTransferSession {
+    type Error = Error;
+
+    fn try_from(v: InvalidDataTransferSessionV1) -> Result<Self> {
+        let reason = v.reason();
+        Ok(Self {
+            pub_key: v.pub_key.into(),
+            payer: v.payer.into(),
+            upload_bytes: v.upload_bytes,
+            download_bytes: v.download_bytes,
+            num_dcs: v.num_dcs,
+            first_timestamp: v.first_timestamp.to_timestamp_millis()?,
+            last_timestamp: v.last_timestamp.to_timestamp_millis()?,
+            reason,
+        })
+    }
+}
+
+impl From<InvalidDataTransferSession> for InvalidDataTransferSessionV1 {
+    fn from(v: InvalidDataTransferSession) -> Self {
+        Self {
+            pub_key: v.pub_key.into(),
+            payer: v.payer.into(),
+            upload_bytes: v.upload_bytes,
+            download_bytes: v.download_bytes,
+            num_dcs: v.num_dcs,
+            first_timestamp: v.first_timestamp.encode_timestamp_millis(),
+            last_timestamp: v.last_timestamp.encode_timestamp_millis(),
+            reason: v.reason as i32,
+        }
+    }
+}
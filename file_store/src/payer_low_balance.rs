@@ -0,0 +1,95 @@
+use crate::{
+    traits::{MsgDecode, MsgTimestamp, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use serde::Serialize;
+
+/// Wire form of a low balance warning, kept here as a prost message until the
+/// pinned helium-proto carries one
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PayerLowBalanceV1 {
+    /// Time of the warning, in millis
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    /// Org of the payer, for iot payers
+    #[prost(uint64, optional, tag = "2")]
+    pub oui: Option<u64>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub payer: Vec<u8>,
+    /// Balance of the payer in data credits
+    #[prost(uint64, tag = "4")]
+    pub balance: u64,
+    /// Balance below which the payer is warned
+    #[prost(uint64, tag = "5")]
+    pub threshold: u64,
+    /// Data credits burned per hour, over the recent balance history
+    #[prost(uint64, tag = "6")]
+    pub burn_rate: u64,
+    /// Projected seconds until the balance runs out at the burn rate, unset
+    /// if the balance isn't falling
+    #[prost(uint64, optional, tag = "7")]
+    pub seconds_until_empty: Option<u64>,
+}
+
+/// A warning to a payer of the packet verifiers that its data credit balance
+/// is running low
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct PayerLowBalance {
+    pub timestamp: DateTime<Utc>,
+    pub oui: Option<u64>,
+    pub payer: PublicKeyBinary,
+    pub balance: u64,
+    pub threshold: u64,
+    pub burn_rate: u64,
+    pub seconds_until_empty: Option<u64>,
+}
+
+impl MsgTimestamp<u64> for PayerLowBalance {
+    fn timestamp(&self) -> u64 {
+        self.timestamp.encode_timestamp_millis()
+    }
+}
+
+impl MsgTimestamp<Result<DateTime<Utc>>> for PayerLowBalanceV1 {
+    fn timestamp(&self) -> Result<DateTime<Utc>> {
+        self.timestamp.to_timestamp_millis()
+    }
+}
+
+impl MsgDecode for PayerLowBalance {
+    type Msg = PayerLowBalanceV1;
+}
+
+impl TryFrom<PayerLowBalanceV1> for PayerLowBalance {
+    type Error = Error;
+
+    fn try_from(v: PayerLowBalanceV1) -> Result<Self> {
+        let timestamp = v.timestamp()?;
+        Ok(Self {
+            timestamp,
+            oui: v.oui,
+            payer: v.payer.into(),
+            balance: v.balance,
+            threshold: v.threshold,
+            burn_rate: v.burn_rate,
+            seconds_until_empty: v.seconds_until_empty,
+        })
+    }
+}
+
+impl From<PayerLowBalance> for PayerLowBalanceV1 {
+    fn from(v: PayerLowBalance) -> Self {
+        let timestamp = v.timestamp();
+        Self {
+            timestamp,
+            oui: v.oui,
+            payer: v.payer.into(),
+            balance: v.balance,
+            threshold: v.threshold,
+            burn_rate: v.burn_rate,
+            seconds_until_empty: v.seconds_until_empty,
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{
+    error::DecodeError,
+    file_store::{ByteStream, FileStoreBackend},
+    Compression, Error, FileChecksum, FileInfo, FileInfoStream, FileType, Result, Settings,
+};
+use async_trait::async_trait;
+use aws_config::meta::region::RegionProviderChain;
+use aws_sdk_s3::{types, Client, Endpoint, Region};
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt, TryFutureExt, TryStreamExt};
+use http::Uri;
+use std::{io, path::Path, str::FromStr};
+
+/// A file store backend using an S3 compatible bucket
+#[derive(Debug, Clone)]
+pub struct S3Store {
+    bucket: String,
+    client: Client,
+}
+
+impl S3Store {
+    pub async fn from_settings(settings: &Settings) -> Result<Self> {
+        let endpoint: Option<Endpoint> = match &settings.endpoint {
+            Some(endpoint) => Uri::from_str(endpoint)
+                .map(Endpoint::immutable)
+                .map(Some)
+                .map_err(DecodeError::from)?,
+            _ => None,
+        };
+        let region = Region::new(settings.region.clone());
+        let region_provider = RegionProviderChain::first_try(region).or_default_provider();
+
+        let mut config = aws_config::from_env().region(region_provider);
+        if let Some(endpoint) = endpoint {
+            config = config.endpoint_resolver(endpoint);
+        }
+
+        #[cfg(feature = "local")]
+        if settings.access_key_id.is_some() && settings.secret_access_key.is_some() {
+            let creds = aws_types::credentials::Credentials::from_keys(
+                settings.access_key_id.as_ref().unwrap(),
+                settings.secret_access_key.as_ref().unwrap(),
+                None,
+            );
+            config = config.credentials_provider(creds);
+        }
+
+        let config = config.load().await;
+
+        let client = Client::new(&config);
+        Ok(Self {
+            client,
+            bucket: settings.bucket.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl FileStoreBackend for S3Store {
+    fn list(
+        &self,
+        file_type: FileType,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    ) -> FileInfoStream {
+        let request = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(file_type.to_string())
+            .set_start_after(
+                after.map(|dt| FileInfo::from((file_type, dt, Compression::Gzip)).into()),
+            );
+
+        futures::stream::unfold(
+            (request, true, None),
+            |(req, first_time, next)| async move {
+                if first_time || next.is_some() {
+                    let list_objects_response =
+                        req.clone().set_continuation_token(next).send().await;
+
+                    let next_token = list_objects_response
+                        .as_ref()
+                        .ok()
+                        .and_then(|r| r.next_continuation_token())
+                        .map(|x| x.to_owned());
+
+                    Some((list_objects_response, (req, false, next_token)))
+                } else {
+                    None
+                }
+            },
+        )
+        .flat_map(move |entry| match entry {
+            Ok(output) => {
+                let filtered = output
+                    .contents
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|obj| {
+                        if FileInfo::matches(obj.key().unwrap_or_default()) {
+                            Some(FileInfo::try_from(&obj).unwrap())
+                        } else {
+                            None
+                        }
+                    })
+                    .filter(move |info| after.map_or(true, |v| info.timestamp > v))
+                    .filter(move |info| before.map_or(true, |v| info.timestamp <= v))
+                    .map(Ok);
+                stream::iter(filtered).boxed()
+            }
+            Err(err) => stream::once(async move { Err(Error::s3_error(err)) }).boxed(),
+        })
+        .boxed()
+    }
+
+    async fn get_raw(&self, key: String) -> Result<(ByteStream, Option<FileChecksum>)> {
+        self.client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .map_ok(|output| {
+                let checksum = output.metadata().and_then(FileChecksum::from_metadata);
+                (output.body.map_err(io::Error::from).boxed(), checksum)
+            })
+            .map_err(Error::s3_error)
+            .await
+    }
+
+    async fn checksum(&self, key: &str) -> Result<Option<FileChecksum>> {
+        self.client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .map_ok(|output| output.metadata().and_then(FileChecksum::from_metadata))
+            .map_err(Error::s3_error)
+            .await
+    }
+
+    async fn put(&self, file: &Path, checksum: &FileChecksum) -> Result {
+        let byte_stream = types::ByteStream::from_path(&file)
+            .await
+            .map_err(|_| Error::not_found(format!("could not open {}", file.display())))?;
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(file.file_name().map(|name| name.to_string_lossy()).unwrap())
+            .body(byte_stream)
+            .set_metadata(Some(checksum.to_metadata()))
+            .send()
+            .map_ok(|_| ())
+            .map_err(Error::s3_error)
+            .await
+    }
+
+    async fn remove(&self, key: &str) -> Result {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .map_ok(|_| ())
+            .map_err(Error::s3_error)
+            .await
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{Error, Result};
 use config::{Config, File};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Settings {
@@ -12,6 +12,9 @@ pub struct Settings {
     /// Optional region for the endpoint. Default: us-west-2
     #[serde(default = "default_region")]
     pub region: String,
+    /// Optional local directory to use as the store instead of the bucket.
+    /// Useful for running locally or in integration tests. Default none
+    pub local_path: Option<PathBuf>,
 
     /// Should only be used for local testing
     pub access_key_id: Option<String>,
@@ -39,6 +39,8 @@ impl_msg_verify!(iot_config::OrgCreateRoamerReqV1, signature);
 impl_msg_verify!(iot_config::OrgUpdateReqV1, signature);
 impl_msg_verify!(iot_config::OrgDisableReqV1, signature);
 impl_msg_verify!(iot_config::OrgEnableReqV1, signature);
+impl_msg_verify!(iot_config::OrgResV1, signature);
+impl_msg_verify!(iot_config::OrgListResV1, signature);
 impl_msg_verify!(iot_config::RouteStreamReqV1, signature);
 impl_msg_verify!(iot_config::RouteListReqV1, signature);
 impl_msg_verify!(iot_config::RouteGetReqV1, signature);
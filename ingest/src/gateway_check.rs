@@ -0,0 +1,194 @@
+use helium_crypto::PublicKeyBinary;
+use retainer::Cache;
+use std::{sync::Arc, time::Duration};
+use tonic::Status;
+
+const UNKNOWN_GATEWAY_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "_unknown_gateway");
+const CACHE_EVICTION_FREQUENCY: Duration = Duration::from_secs(60 * 60);
+
+/// Resolves whether a gateway is known to a config service
+#[async_trait::async_trait]
+pub trait GatewayResolver: Clone + Send + Sync + 'static {
+    async fn gateway_exists(&mut self, address: &PublicKeyBinary) -> anyhow::Result<bool>;
+}
+
+#[async_trait::async_trait]
+impl GatewayResolver for iot_config::client::Client {
+    async fn gateway_exists(&mut self, address: &PublicKeyBinary) -> anyhow::Result<bool> {
+        use iot_config::gateway_info::GatewayInfoResolver;
+        Ok(self.resolve_gateway_info(address).await?.is_some())
+    }
+}
+
+#[async_trait::async_trait]
+impl GatewayResolver for mobile_config::client::Client {
+    // The client's own cache would keep a miss for its full ttl, the check
+    // below does the caching instead
+    async fn gateway_exists(&mut self, address: &PublicKeyBinary) -> anyhow::Result<bool> {
+        Ok(self.fetch_gateway_info(address).await?.is_some())
+    }
+}
+
+/// Optional check that a report was submitted for a gateway that is onboarded
+/// in the config service. Lookups of known gateways are cached for
+/// `cache_ttl`, those of unknown gateways for the shorter `unknown_cache_ttl`
+/// so a gateway onboarded after its first report is accepted soon. Without a
+/// resolver every gateway is accepted.
+#[derive(Clone)]
+pub struct GatewayCheck<R> {
+    resolver: Option<R>,
+    cache: Arc<Cache<PublicKeyBinary, bool>>,
+    cache_ttl: Duration,
+    unknown_cache_ttl: Duration,
+}
+
+impl<R> GatewayCheck<R>
+where
+    R: GatewayResolver,
+{
+    pub fn new(resolver: Option<R>, cache_ttl: Duration, unknown_cache_ttl: Duration) -> Self {
+        let cache = Arc::new(Cache::new());
+        if resolver.is_some() {
+            let cloned_cache = cache.clone();
+            tokio::spawn(async move {
+                cloned_cache
+                    .monitor(4, 0.25, CACHE_EVICTION_FREQUENCY)
+                    .await
+            });
+        }
+        Self {
+            resolver,
+            cache,
+            cache_ttl,
+            unknown_cache_ttl,
+        }
+    }
+
+    /// Check that the given gateway is known, returning a `PERMISSION_DENIED`
+    /// status if it is not, or an `UNAVAILABLE` status if the config service
+    /// could not be reached.
+    pub async fn check(&self, report_type: &'static str, pub_key: &[u8]) -> Result<(), Status> {
+        let Some(resolver) = &self.resolver else {
+            return Ok(());
+        };
+        let address = PublicKeyBinary::from(pub_key.to_vec());
+        let known = match self.cache.get(&address).await {
+            Some(cached) => *cached.value(),
+            None => {
+                let known = resolver
+                    .clone()
+                    .gateway_exists(&address)
+                    .await
+                    .map_err(|err| {
+                        tracing::warn!(
+                            pubkey = address.to_string(),
+                            "failed to resolve gateway: {err:?}"
+                        );
+                        Status::unavailable("unable to verify gateway, retry later")
+                    })?;
+                let ttl = if known {
+                    self.cache_ttl
+                } else {
+                    self.unknown_cache_ttl
+                };
+                self.cache.insert(address.clone(), known, ttl).await;
+                known
+            }
+        };
+
+        if known {
+            Ok(())
+        } else {
+            metrics::increment_counter!(UNKNOWN_GATEWAY_METRIC, "report_type" => report_type);
+            tracing::debug!(pubkey = address.to_string(), report_type, "unknown gateway");
+            Err(Status::permission_denied("unknown gateway"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        collections::HashSet,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+    };
+
+    #[derive(Clone, Default)]
+    struct MockResolver {
+        known: Arc<Mutex<HashSet<PublicKeyBinary>>>,
+        lookups: Arc<AtomicUsize>,
+    }
+
+    impl MockResolver {
+        fn onboard(&self, pub_key: &[u8]) {
+            self.known
+                .lock()
+                .unwrap()
+                .insert(PublicKeyBinary::from(pub_key.to_vec()));
+        }
+
+        fn lookups(&self) -> usize {
+            self.lookups.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GatewayResolver for MockResolver {
+        async fn gateway_exists(&mut self, address: &PublicKeyBinary) -> anyhow::Result<bool> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            Ok(self.known.lock().unwrap().contains(address))
+        }
+    }
+
+    #[tokio::test]
+    async fn known_gateways_are_cached_for_the_ttl() {
+        let resolver = MockResolver::default();
+        resolver.onboard(&[1]);
+        let check = GatewayCheck::new(
+            Some(resolver.clone()),
+            Duration::from_secs(60),
+            Duration::from_millis(10),
+        );
+
+        assert!(check.check("test", &[1]).await.is_ok());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(check.check("test", &[1]).await.is_ok());
+        assert_eq!(1, resolver.lookups());
+    }
+
+    #[tokio::test]
+    async fn unknown_gateways_are_looked_up_again_after_the_unknown_ttl() {
+        let resolver = MockResolver::default();
+        let check = GatewayCheck::new(
+            Some(resolver.clone()),
+            Duration::from_secs(60),
+            Duration::from_millis(100),
+        );
+
+        let status = check.check("test", &[2]).await.unwrap_err();
+        assert_eq!(tonic::Code::PermissionDenied, status.code());
+
+        // Onboarded after its first report, the miss is still cached
+        resolver.onboard(&[2]);
+        assert!(check.check("test", &[2]).await.is_err());
+        assert_eq!(1, resolver.lookups());
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(check.check("test", &[2]).await.is_ok());
+        assert_eq!(2, resolver.lookups());
+    }
+
+    #[tokio::test]
+    async fn every_gateway_is_accepted_without_a_resolver() {
+        let check = GatewayCheck::<MockResolver>::new(
+            None,
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+        assert!(check.check("test", &[3]).await.is_ok());
+    }
+}
@@ -1,3 +1,7 @@
+pub mod gateway_check;
+pub mod poc_mobile_batch;
+pub mod rate_limit;
+pub mod report_sink;
 pub mod server_iot;
 pub mod server_mobile;
 pub mod settings;
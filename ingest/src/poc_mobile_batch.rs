@@ -0,0 +1,176 @@
+//! Client-streaming batch submission of mobile reports. helium-proto doesn't
+//! carry these RPCs yet, so the messages and the tonic service are defined
+//! here, laid out as `tonic-build` would generate them. Once the RPCs land in
+//! the `poc_mobile` service this module can be replaced by the proto ones.
+use helium_proto::services::poc_mobile::{
+    CellHeartbeatReqV1, DataTransferSessionReqV1, SpeedtestReqV1,
+};
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tonic::{
+    codegen::{empty_body, http, Body, BoxFuture, Service, StdError},
+    server::{ClientStreamingService, Grpc, NamedService},
+    Request, Response, Status, Streaming,
+};
+
+/// Result of a single item of a batch, in submission order. `code` is the
+/// grpc status code the item was accepted or rejected with and `id` is the
+/// id of the stored report when accepted.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BatchResultV1 {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(int32, tag = "2")]
+    pub code: i32,
+    #[prost(string, tag = "3")]
+    pub message: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SpeedtestsRespV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub results: Vec<BatchResultV1>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CellHeartbeatsRespV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub results: Vec<BatchResultV1>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DataTransferSessionsRespV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub results: Vec<BatchResultV1>,
+}
+
+#[tonic::async_trait]
+pub trait PocMobileBatch: Send + Sync + 'static {
+    async fn submit_speedtests(
+        &self,
+        request: Request<Streaming<SpeedtestReqV1>>,
+    ) -> Result<Response<SpeedtestsRespV1>, Status>;
+
+    async fn submit_cell_heartbeats(
+        &self,
+        request: Request<Streaming<CellHeartbeatReqV1>>,
+    ) -> Result<Response<CellHeartbeatsRespV1>, Status>;
+
+    async fn submit_data_transfer_sessions(
+        &self,
+        request: Request<Streaming<DataTransferSessionReqV1>>,
+    ) -> Result<Response<DataTransferSessionsRespV1>, Status>;
+}
+
+pub struct Server<T> {
+    inner: Arc<T>,
+}
+
+impl<T: PocMobileBatch> Server<T> {
+    pub fn new(inner: T) -> Self {
+        Self::from_arc(Arc::new(inner))
+    }
+
+    pub fn from_arc(inner: Arc<T>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<T> Clone for Server<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> NamedService for Server<T> {
+    const NAME: &'static str = "helium.poc_mobile.poc_mobile_batch";
+}
+
+struct SubmitSpeedtestsSvc<T>(Arc<T>);
+
+impl<T: PocMobileBatch> ClientStreamingService<SpeedtestReqV1> for SubmitSpeedtestsSvc<T> {
+    type Response = SpeedtestsRespV1;
+    type Future = BoxFuture<Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: Request<Streaming<SpeedtestReqV1>>) -> Self::Future {
+        let inner = self.0.clone();
+        Box::pin(async move { inner.submit_speedtests(request).await })
+    }
+}
+
+struct SubmitCellHeartbeatsSvc<T>(Arc<T>);
+
+impl<T: PocMobileBatch> ClientStreamingService<CellHeartbeatReqV1> for SubmitCellHeartbeatsSvc<T> {
+    type Response = CellHeartbeatsRespV1;
+    type Future = BoxFuture<Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: Request<Streaming<CellHeartbeatReqV1>>) -> Self::Future {
+        let inner = self.0.clone();
+        Box::pin(async move { inner.submit_cell_heartbeats(request).await })
+    }
+}
+
+struct SubmitDataTransferSessionsSvc<T>(Arc<T>);
+
+impl<T: PocMobileBatch> ClientStreamingService<DataTransferSessionReqV1>
+    for SubmitDataTransferSessionsSvc<T>
+{
+    type Response = DataTransferSessionsRespV1;
+    type Future = BoxFuture<Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: Request<Streaming<DataTransferSessionReqV1>>) -> Self::Future {
+        let inner = self.0.clone();
+        Box::pin(async move { inner.submit_data_transfer_sessions(request).await })
+    }
+}
+
+impl<T, B> Service<http::Request<B>> for Server<T>
+where
+    T: PocMobileBatch,
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = std::convert::Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let inner = self.inner.clone();
+        match req.uri().path() {
+            "/helium.poc_mobile.poc_mobile_batch/submit_speedtests" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.client_streaming(SubmitSpeedtestsSvc(inner), req).await)
+            }),
+            "/helium.poc_mobile.poc_mobile_batch/submit_cell_heartbeats" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc
+                    .client_streaming(SubmitCellHeartbeatsSvc(inner), req)
+                    .await)
+            }),
+            "/helium.poc_mobile.poc_mobile_batch/submit_data_transfer_sessions" => {
+                Box::pin(async move {
+                    let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                    Ok(grpc
+                        .client_streaming(SubmitDataTransferSessionsSvc(inner), req)
+                        .await)
+                })
+            }
+            _ => Box::pin(async move {
+                Ok(http::Response::builder()
+                    .status(200)
+                    .header("grpc-status", "12")
+                    .header("content-type", "application/grpc")
+                    .body(empty_body())
+                    .unwrap())
+            }),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::settings::RateLimit;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tonic::Status;
+
+const RATE_LIMITED_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "_rate_limited");
+
+/// An in-memory token bucket rate limiter for a single report type. Every key
+/// (a public key or cbsd id) gets its own bucket holding up to `max` tokens
+/// which refills at `max` tokens per `period`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    report_type: &'static str,
+    limit: Option<RateLimit>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    buckets: HashMap<Vec<u8>, Bucket>,
+    last_purge: Instant,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(limit: &RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.max as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        let refill = elapsed * limit.max as f64 / limit.period().as_secs_f64();
+        self.tokens = (self.tokens + refill).min(limit.max as f64);
+        self.updated = now;
+    }
+
+    fn take(&mut self, limit: &RateLimit, now: Instant) -> bool {
+        self.refill(limit, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Construct a rate limiter for the given report type. Without a limit
+    /// every report is allowed.
+    pub fn new(report_type: &'static str, limit: Option<RateLimit>) -> Self {
+        metrics::register_counter!(RATE_LIMITED_METRIC, "report_type" => report_type);
+        Self {
+            report_type,
+            limit,
+            state: Mutex::new(State {
+                buckets: HashMap::new(),
+                last_purge: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take a token for the given key, returning a `RESOURCE_EXHAUSTED` status
+    /// if the key is over its limit
+    pub fn check(&self, key: &[u8]) -> Result<(), Status> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: &[u8], now: Instant) -> Result<(), Status> {
+        let Some(limit) = &self.limit else {
+            return Ok(());
+        };
+        let mut state = self.state.lock().unwrap();
+        state.maybe_purge(limit, now);
+
+        let allowed = state
+            .buckets
+            .entry(key.to_vec())
+            .or_insert_with(|| Bucket::full(limit, now))
+            .take(limit, now);
+        if allowed {
+            Ok(())
+        } else {
+            metrics::increment_counter!(RATE_LIMITED_METRIC, "report_type" => self.report_type);
+            Err(Status::resource_exhausted(format!(
+                "{} rate limit exceeded",
+                self.report_type
+            )))
+        }
+    }
+}
+
+impl State {
+    /// Drop buckets that have fully refilled since they would be recreated
+    /// identically on the next report for their key
+    fn maybe_purge(&mut self, limit: &RateLimit, now: Instant) {
+        if now.saturating_duration_since(self.last_purge) < limit.period() {
+            return;
+        }
+        self.buckets.retain(|_, bucket| {
+            bucket.refill(limit, now);
+            bucket.tokens < limit.max as f64
+        });
+        self.last_purge = now;
+    }
+}
+
+impl RateLimit {
+    fn period(&self) -> Duration {
+        Duration::from_secs(self.period_secs.max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_each_key_and_refills_over_time() {
+        let limiter = RateLimiter::new(
+            "beacon",
+            Some(RateLimit {
+                max: 2,
+                period_secs: 60,
+            }),
+        );
+        let now = Instant::now();
+
+        assert!(limiter.check_at(b"a", now).is_ok());
+        assert!(limiter.check_at(b"a", now).is_ok());
+        let err = limiter.check_at(b"a", now).unwrap_err();
+        assert_eq!(tonic::Code::ResourceExhausted, err.code());
+        assert!(limiter.check_at(b"b", now).is_ok());
+
+        assert!(limiter
+            .check_at(b"a", now + Duration::from_secs(30))
+            .is_ok());
+        assert!(limiter
+            .check_at(b"a", now + Duration::from_secs(30))
+            .is_err());
+    }
+
+    #[test]
+    fn allows_everything_without_limit() {
+        let limiter = RateLimiter::new("beacon", None);
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert!(limiter.check_at(b"a", now).is_ok());
+        }
+    }
+}
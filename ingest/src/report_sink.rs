@@ -0,0 +1,47 @@
+use file_store::file_sink::FileSinkClient;
+use std::time::Duration;
+use tonic::Status;
+
+const WRITE_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Write a verified report to the given sink. Any failure to hand the report
+/// to the sink, and when `wait_for_write` is set any failure to write it, is
+/// returned as a retryable `UNAVAILABLE` status so that the submitter resends
+/// the report rather than it being silently dropped.
+pub async fn write_report<T>(
+    sink: &FileSinkClient,
+    report: T,
+    wait_for_write: bool,
+) -> Result<(), Status>
+where
+    T: prost::Message,
+{
+    let on_write = sink.write(report, []).await.map_err(|err| {
+        tracing::warn!("failed to queue report for writing: {err:?}");
+        unavailable()
+    })?;
+
+    if !wait_for_write {
+        return Ok(());
+    }
+
+    match tokio::time::timeout(WRITE_ACK_TIMEOUT, on_write).await {
+        Ok(Ok(Ok(()))) => Ok(()),
+        Ok(Ok(Err(err))) => {
+            tracing::warn!("failed to write report: {err:?}");
+            Err(unavailable())
+        }
+        Ok(Err(_)) => {
+            tracing::warn!("report sink dropped write acknowledgement");
+            Err(unavailable())
+        }
+        Err(_) => {
+            tracing::warn!("timed out waiting for report write");
+            Err(unavailable())
+        }
+    }
+}
+
+fn unavailable() -> Status {
+    Status::unavailable("unable to store report, retry later")
+}
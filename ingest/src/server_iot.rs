@@ -1,4 +1,7 @@
-use crate::Settings;
+use crate::{
+    gateway_check::GatewayCheck, rate_limit::RateLimiter, report_sink::write_report,
+    settings::RateLimitSettings, Settings,
+};
 use anyhow::{Error, Result};
 use chrono::{Duration, Utc};
 use file_store::{
@@ -13,6 +16,7 @@ use helium_proto::services::poc_lora::{
     self, LoraBeaconIngestReportV1, LoraBeaconReportReqV1, LoraBeaconReportRespV1,
     LoraWitnessIngestReportV1, LoraWitnessReportReqV1, LoraWitnessReportRespV1,
 };
+use iot_config::client::Client as IotConfigClient;
 use std::{convert::TryFrom, path::Path};
 use tonic::{transport, Request, Response, Status};
 
@@ -23,6 +27,10 @@ pub struct GrpcServer {
     beacon_report_sink: FileSinkClient,
     witness_report_sink: FileSinkClient,
     required_network: Network,
+    wait_for_write: bool,
+    beacon_rate_limiter: RateLimiter,
+    witness_rate_limiter: RateLimiter,
+    gateway_check: GatewayCheck<IotConfigClient>,
 }
 
 impl GrpcServer {
@@ -30,11 +38,18 @@ impl GrpcServer {
         beacon_report_sink: FileSinkClient,
         witness_report_sink: FileSinkClient,
         required_network: Network,
+        wait_for_write: bool,
+        rate_limits: &RateLimitSettings,
+        gateway_check: GatewayCheck<IotConfigClient>,
     ) -> Result<Self> {
         Ok(Self {
             beacon_report_sink,
             witness_report_sink,
             required_network,
+            wait_for_write,
+            beacon_rate_limiter: RateLimiter::new("beacon", rate_limits.beacon),
+            witness_rate_limiter: RateLimiter::new("witness", rate_limits.witness),
+            gateway_check,
         })
     }
 
@@ -70,16 +85,24 @@ impl poc_lora::PocLora for GrpcServer {
         let timestamp: u64 = Utc::now().timestamp_millis() as u64;
         let event = request.into_inner();
 
-        let report = self
+        let (_, event) = self
             .verify_public_key(event.pub_key.as_ref())
             .and_then(|public_key| self.verify_network(public_key))
             .and_then(|public_key| self.verify_signature(public_key, event))
-            .map(|(_, event)| LoraBeaconIngestReportV1 {
-                received_timestamp: timestamp,
-                report: Some(event),
+            .and_then(|(public_key, event)| {
+                self.beacon_rate_limiter
+                    .check(&event.pub_key)
+                    .map(|_| (public_key, event))
             })?;
 
-        _ = self.beacon_report_sink.write(report, []).await;
+        self.gateway_check.check("beacon", &event.pub_key).await?;
+
+        let report = LoraBeaconIngestReportV1 {
+            received_timestamp: timestamp,
+            report: Some(event),
+        };
+
+        write_report(&self.beacon_report_sink, report, self.wait_for_write).await?;
 
         let id = timestamp.to_string();
         Ok(Response::new(LoraBeaconReportRespV1 { id }))
@@ -92,16 +115,24 @@ impl poc_lora::PocLora for GrpcServer {
         let timestamp: u64 = Utc::now().timestamp_millis() as u64;
         let event = request.into_inner();
 
-        let report = self
+        let (_, event) = self
             .verify_public_key(event.pub_key.as_ref())
             .and_then(|public_key| self.verify_network(public_key))
             .and_then(|public_key| self.verify_signature(public_key, event))
-            .map(|(_, event)| LoraWitnessIngestReportV1 {
-                received_timestamp: timestamp,
-                report: Some(event),
+            .and_then(|(public_key, event)| {
+                self.witness_rate_limiter
+                    .check(&event.pub_key)
+                    .map(|_| (public_key, event))
             })?;
 
-        _ = self.witness_report_sink.write(report, []).await;
+        self.gateway_check.check("witness", &event.pub_key).await?;
+
+        let report = LoraWitnessIngestReportV1 {
+            received_timestamp: timestamp,
+            report: Some(event),
+        };
+
+        write_report(&self.witness_report_sink, report, self.wait_for_write).await?;
 
         let id = timestamp.to_string();
         Ok(Response::new(LoraWitnessReportRespV1 { id }))
@@ -142,7 +173,24 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
     .create()
     .await?;
 
-    let grpc_server = GrpcServer::new(beacon_report_sink, witness_report_sink, settings.network)?;
+    let gateway_check = GatewayCheck::new(
+        settings
+            .iot_config_client
+            .as_ref()
+            .map(IotConfigClient::from_settings)
+            .transpose()?,
+        settings.gateway_cache_ttl(),
+        settings.unknown_gateway_cache_ttl(),
+    );
+
+    let grpc_server = GrpcServer::new(
+        beacon_report_sink,
+        witness_report_sink,
+        settings.network,
+        settings.wait_for_write,
+        &settings.rate_limits,
+        gateway_check,
+    )?;
 
     tracing::info!(
         "grpc listening on {grpc_addr} and server mode {:?}",
@@ -1,4 +1,14 @@
-use crate::Settings;
+use crate::{
+    gateway_check::GatewayCheck,
+    poc_mobile_batch::{
+        self, BatchResultV1, CellHeartbeatsRespV1, DataTransferSessionsRespV1, PocMobileBatch,
+        SpeedtestsRespV1,
+    },
+    rate_limit::RateLimiter,
+    report_sink::write_report,
+    settings::RateLimitSettings,
+    Settings,
+};
 use anyhow::{bail, Error, Result};
 use chrono::{Duration, Utc};
 use file_store::{
@@ -7,6 +17,7 @@ use file_store::{
     traits::MsgVerify,
     FileType,
 };
+use futures::{Future, Stream, StreamExt};
 use futures_util::TryFutureExt;
 use helium_crypto::{Network, PublicKey};
 use helium_proto::services::poc_mobile::{
@@ -15,8 +26,12 @@ use helium_proto::services::poc_mobile::{
     SpeedtestIngestReportV1, SpeedtestReqV1, SpeedtestRespV1, SubscriberLocationIngestReportV1,
     SubscriberLocationReqV1, SubscriberLocationRespV1,
 };
-use std::path::Path;
-use tonic::{metadata::MetadataValue, transport, Request, Response, Status};
+use mobile_config::client::Client as MobileConfigClient;
+use std::{path::Path, sync::Arc};
+use tonic::{
+    metadata::MetadataValue, service::interceptor::InterceptedService, transport, Request,
+    Response, Status, Streaming,
+};
 
 const INGEST_WAIT_DURATION_MINUTES: i64 = 15;
 
@@ -29,6 +44,12 @@ pub struct GrpcServer {
     data_transfer_session_sink: FileSinkClient,
     subscriber_location_report_sink: FileSinkClient,
     required_network: Network,
+    wait_for_write: bool,
+    heartbeat_rate_limiter: RateLimiter,
+    speedtest_rate_limiter: RateLimiter,
+    data_transfer_session_rate_limiter: RateLimiter,
+    subscriber_location_rate_limiter: RateLimiter,
+    gateway_check: GatewayCheck<MobileConfigClient>,
 }
 impl GrpcServer {
     fn new(
@@ -37,6 +58,9 @@ impl GrpcServer {
         data_transfer_session_sink: FileSinkClient,
         subscriber_location_report_sink: FileSinkClient,
         required_network: Network,
+        wait_for_write: bool,
+        rate_limits: &RateLimitSettings,
+        gateway_check: GatewayCheck<MobileConfigClient>,
     ) -> Result<Self> {
         Ok(Self {
             heartbeat_report_sink,
@@ -44,6 +68,18 @@ impl GrpcServer {
             data_transfer_session_sink,
             subscriber_location_report_sink,
             required_network,
+            wait_for_write,
+            heartbeat_rate_limiter: RateLimiter::new("heartbeat", rate_limits.heartbeat),
+            speedtest_rate_limiter: RateLimiter::new("speedtest", rate_limits.speedtest),
+            data_transfer_session_rate_limiter: RateLimiter::new(
+                "data_transfer_session",
+                rate_limits.data_transfer_session,
+            ),
+            subscriber_location_rate_limiter: RateLimiter::new(
+                "subscriber_location",
+                rate_limits.subscriber_location,
+            ),
+            gateway_check,
         })
     }
 
@@ -68,75 +104,184 @@ impl GrpcServer {
             .map_err(|_| Status::invalid_argument("invalid signature"))?;
         Ok((public_key, event))
     }
-}
 
-#[tonic::async_trait]
-impl poc_mobile::PocMobile for GrpcServer {
-    async fn submit_speedtest(
+    async fn ingest_speedtest(
         &self,
-        request: Request<SpeedtestReqV1>,
-    ) -> GrpcResult<SpeedtestRespV1> {
-        let timestamp: u64 = Utc::now().timestamp_millis() as u64;
-        let event = request.into_inner();
-
-        let report = self
+        timestamp: u64,
+        event: SpeedtestReqV1,
+    ) -> VerifyResult<SpeedtestRespV1> {
+        let (_, event) = self
             .verify_public_key(event.pub_key.as_ref())
             .and_then(|public_key| self.verify_network(public_key))
             .and_then(|public_key| self.verify_signature(public_key, event))
-            .map(|(_, event)| SpeedtestIngestReportV1 {
-                received_timestamp: timestamp,
-                report: Some(event),
+            .and_then(|(public_key, event)| {
+                self.speedtest_rate_limiter
+                    .check(&event.pub_key)
+                    .map(|_| (public_key, event))
             })?;
 
-        _ = self.speedtest_report_sink.write(report, []).await;
+        self.gateway_check
+            .check("speedtest", &event.pub_key)
+            .await?;
+
+        let report = SpeedtestIngestReportV1 {
+            received_timestamp: timestamp,
+            report: Some(event),
+        };
+
+        write_report(&self.speedtest_report_sink, report, self.wait_for_write).await?;
 
         let id = timestamp.to_string();
-        Ok(Response::new(SpeedtestRespV1 { id }))
+        Ok(SpeedtestRespV1 { id })
     }
 
-    async fn submit_cell_heartbeat(
+    async fn ingest_cell_heartbeat(
         &self,
-        request: Request<CellHeartbeatReqV1>,
-    ) -> GrpcResult<CellHeartbeatRespV1> {
-        let timestamp: u64 = Utc::now().timestamp_millis() as u64;
-        let event = request.into_inner();
-
-        let report = self
+        timestamp: u64,
+        event: CellHeartbeatReqV1,
+    ) -> VerifyResult<CellHeartbeatRespV1> {
+        let (_, event) = self
             .verify_public_key(event.pub_key.as_ref())
             .and_then(|public_key| self.verify_network(public_key))
             .and_then(|public_key| self.verify_signature(public_key, event))
-            .map(|(_, event)| CellHeartbeatIngestReportV1 {
-                received_timestamp: timestamp,
-                report: Some(event),
+            .and_then(|(public_key, event)| {
+                self.heartbeat_rate_limiter
+                    .check(event.cbsd_id.as_bytes())
+                    .map(|_| (public_key, event))
             })?;
 
-        _ = self.heartbeat_report_sink.write(report, []).await;
+        self.gateway_check
+            .check("heartbeat", &event.pub_key)
+            .await?;
+
+        let report = CellHeartbeatIngestReportV1 {
+            received_timestamp: timestamp,
+            report: Some(event),
+        };
+
+        write_report(&self.heartbeat_report_sink, report, self.wait_for_write).await?;
 
         let id = timestamp.to_string();
-        Ok(Response::new(CellHeartbeatRespV1 { id }))
+        Ok(CellHeartbeatRespV1 { id })
     }
 
-    async fn submit_data_transfer_session(
+    async fn ingest_data_transfer_session(
         &self,
-        request: Request<DataTransferSessionReqV1>,
-    ) -> GrpcResult<DataTransferSessionRespV1> {
-        let timestamp = Utc::now().timestamp_millis() as u64;
-        let event = request.into_inner();
-
-        let report = self
+        timestamp: u64,
+        event: DataTransferSessionReqV1,
+    ) -> VerifyResult<DataTransferSessionRespV1> {
+        let (_, event) = self
             .verify_public_key(event.pub_key.as_ref())
             .and_then(|public_key| self.verify_network(public_key))
             .and_then(|public_key| self.verify_signature(public_key, event))
-            .map(|(_, event)| DataTransferSessionIngestReportV1 {
-                received_timestamp: timestamp,
-                report: Some(event),
+            .and_then(|(public_key, event)| {
+                self.data_transfer_session_rate_limiter
+                    .check(&event.pub_key)
+                    .map(|_| (public_key, event))
             })?;
 
-        _ = self.data_transfer_session_sink.write(report, []).await;
+        if let Some(usage) = &event.data_transfer_usage {
+            self.gateway_check
+                .check("data_transfer_session", &usage.pub_key)
+                .await?;
+        }
+
+        let report = DataTransferSessionIngestReportV1 {
+            received_timestamp: timestamp,
+            report: Some(event),
+        };
+
+        write_report(
+            &self.data_transfer_session_sink,
+            report,
+            self.wait_for_write,
+        )
+        .await?;
 
-        Ok(Response::new(DataTransferSessionRespV1 {
+        Ok(DataTransferSessionRespV1 {
             id: timestamp.to_string(),
-        }))
+        })
+    }
+}
+
+/// Receive timestamps for the items of a batch. Each item is stamped as it is
+/// taken from the batch, strictly increasing so that every item's report id
+/// is unique.
+#[derive(Default)]
+struct BatchClock {
+    last: u64,
+}
+
+impl BatchClock {
+    fn next(&mut self) -> u64 {
+        self.next_at(Utc::now().timestamp_millis() as u64)
+    }
+
+    fn next_at(&mut self, now: u64) -> u64 {
+        self.last = now.max(self.last + 1);
+        self.last
+    }
+}
+
+/// Ingest each item of a submitted batch on its own, returning a result for
+/// each in submission order. Only a failure to receive the batch itself fails
+/// the whole call.
+async fn ingest_batch<S, T, F, Fut>(mut events: S, ingest: F) -> VerifyResult<Vec<BatchResultV1>>
+where
+    S: Stream<Item = std::result::Result<T, Status>> + Unpin,
+    F: Fn(u64, T) -> Fut,
+    Fut: Future<Output = VerifyResult<String>>,
+{
+    let mut clock = BatchClock::default();
+    let mut results = Vec::new();
+    while let Some(event) = events.next().await {
+        let result = match ingest(clock.next(), event?).await {
+            Ok(id) => BatchResultV1 {
+                id,
+                code: tonic::Code::Ok as i32,
+                message: String::new(),
+            },
+            Err(status) => BatchResultV1 {
+                id: String::new(),
+                code: status.code() as i32,
+                message: status.message().to_string(),
+            },
+        };
+        results.push(result);
+    }
+    Ok(results)
+}
+
+#[tonic::async_trait]
+impl poc_mobile::PocMobile for GrpcServer {
+    async fn submit_speedtest(
+        &self,
+        request: Request<SpeedtestReqV1>,
+    ) -> GrpcResult<SpeedtestRespV1> {
+        let timestamp: u64 = Utc::now().timestamp_millis() as u64;
+        self.ingest_speedtest(timestamp, request.into_inner())
+            .await
+            .map(Response::new)
+    }
+
+    async fn submit_cell_heartbeat(
+        &self,
+        request: Request<CellHeartbeatReqV1>,
+    ) -> GrpcResult<CellHeartbeatRespV1> {
+        let timestamp: u64 = Utc::now().timestamp_millis() as u64;
+        self.ingest_cell_heartbeat(timestamp, request.into_inner())
+            .await
+            .map(Response::new)
+    }
+
+    async fn submit_data_transfer_session(
+        &self,
+        request: Request<DataTransferSessionReqV1>,
+    ) -> GrpcResult<DataTransferSessionRespV1> {
+        let timestamp = Utc::now().timestamp_millis() as u64;
+        self.ingest_data_transfer_session(timestamp, request.into_inner())
+            .await
+            .map(Response::new)
     }
 
     async fn submit_subscriber_location(
@@ -152,6 +297,11 @@ impl poc_mobile::PocMobile for GrpcServer {
             .verify_public_key(event.carrier_pub_key.as_ref())
             .and_then(|public_key| self.verify_network(public_key))
             .and_then(|public_key| self.verify_signature(public_key, event))
+            .and_then(|(public_key, event)| {
+                self.subscriber_location_rate_limiter
+                    .check(&event.carrier_pub_key)
+                    .map(|_| (public_key, event))
+            })
             .map(|(_, event)| SubscriberLocationIngestReportV1 {
                 received_timestamp: timestamp,
                 report: Some(event),
@@ -165,7 +315,12 @@ impl poc_mobile::PocMobile for GrpcServer {
                 status
             })?;
 
-        _ = self.subscriber_location_report_sink.write(report, []).await;
+        write_report(
+            &self.subscriber_location_report_sink,
+            report,
+            self.wait_for_write,
+        )
+        .await?;
 
         Ok(Response::new(SubscriberLocationRespV1 {
             id: timestamp.to_string(),
@@ -173,6 +328,45 @@ impl poc_mobile::PocMobile for GrpcServer {
     }
 }
 
+#[tonic::async_trait]
+impl PocMobileBatch for GrpcServer {
+    async fn submit_speedtests(
+        &self,
+        request: Request<Streaming<SpeedtestReqV1>>,
+    ) -> GrpcResult<SpeedtestsRespV1> {
+        let results = ingest_batch(request.into_inner(), |timestamp, event| {
+            self.ingest_speedtest(timestamp, event)
+                .map_ok(|resp| resp.id)
+        })
+        .await?;
+        Ok(Response::new(SpeedtestsRespV1 { results }))
+    }
+
+    async fn submit_cell_heartbeats(
+        &self,
+        request: Request<Streaming<CellHeartbeatReqV1>>,
+    ) -> GrpcResult<CellHeartbeatsRespV1> {
+        let results = ingest_batch(request.into_inner(), |timestamp, event| {
+            self.ingest_cell_heartbeat(timestamp, event)
+                .map_ok(|resp| resp.id)
+        })
+        .await?;
+        Ok(Response::new(CellHeartbeatsRespV1 { results }))
+    }
+
+    async fn submit_data_transfer_sessions(
+        &self,
+        request: Request<Streaming<DataTransferSessionReqV1>>,
+    ) -> GrpcResult<DataTransferSessionsRespV1> {
+        let results = ingest_batch(request.into_inner(), |timestamp, event| {
+            self.ingest_data_transfer_session(timestamp, event)
+                .map_ok(|resp| resp.id)
+        })
+        .await?;
+        Ok(Response::new(DataTransferSessionsRespV1 { results }))
+    }
+}
+
 pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) -> Result<()> {
     let grpc_addr = settings.listen_addr()?;
 
@@ -235,13 +429,26 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
         .create()
         .await?;
 
-    let grpc_server = GrpcServer::new(
+    let gateway_check = GatewayCheck::new(
+        settings
+            .mobile_config_client
+            .as_ref()
+            .map(MobileConfigClient::from_settings)
+            .transpose()?,
+        settings.gateway_cache_ttl(),
+        settings.unknown_gateway_cache_ttl(),
+    );
+
+    let grpc_server = Arc::new(GrpcServer::new(
         heartbeat_report_sink,
         speedtest_report_sink,
         data_transfer_session_sink,
         subscriber_location_report_sink,
         settings.network,
-    )?;
+        settings.wait_for_write,
+        &settings.rate_limits,
+        gateway_check,
+    )?);
 
     let Some(api_token) = settings
         .token
@@ -254,6 +461,11 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
             bail!("expected valid api token in settings");
         };
 
+    let authorize = move |req: Request<()>| match req.metadata().get("authorization") {
+        Some(t) if api_token == t => Ok(req),
+        _ => Err(Status::unauthenticated("No valid auth token")),
+    };
+
     tracing::info!(
         "grpc listening on {grpc_addr} and server mode {:?}",
         settings.mode
@@ -263,12 +475,13 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
     //     use _server_mode (set above ) to decide
     let server = transport::Server::builder()
         .layer(poc_metrics::request_layer!("ingest_server_grpc_connection"))
-        .add_service(poc_mobile::Server::with_interceptor(
-            grpc_server,
-            move |req: Request<()>| match req.metadata().get("authorization") {
-                Some(t) if api_token == t => Ok(req),
-                _ => Err(Status::unauthenticated("No valid auth token")),
-            },
+        .add_service(InterceptedService::new(
+            poc_mobile::Server::from_arc(grpc_server.clone()),
+            authorize.clone(),
+        ))
+        .add_service(InterceptedService::new(
+            poc_mobile_batch::Server::from_arc(grpc_server),
+            authorize,
         ))
         .serve_with_shutdown(grpc_addr, shutdown.clone())
         .map_err(Error::from);
@@ -285,3 +498,49 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
     )
     .map(|_| ())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_timestamps_are_unique() {
+        let mut clock = BatchClock::default();
+        assert_eq!(1_000, clock.next_at(1_000));
+        assert_eq!(1_001, clock.next_at(1_000));
+        assert_eq!(1_002, clock.next_at(999));
+        assert_eq!(2_000, clock.next_at(2_000));
+    }
+
+    #[tokio::test]
+    async fn ingests_each_item_of_a_batch() {
+        let events = futures::stream::iter(vec![Ok(1), Ok(2), Ok(3)]);
+        let results = ingest_batch(events, |timestamp, event: u32| async move {
+            if event == 2 {
+                Err(Status::invalid_argument("invalid signature"))
+            } else {
+                Ok(timestamp.to_string())
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(3, results.len());
+        assert_eq!(tonic::Code::Ok as i32, results[0].code);
+        assert_eq!(tonic::Code::InvalidArgument as i32, results[1].code);
+        assert_eq!("invalid signature", results[1].message);
+        assert_eq!(tonic::Code::Ok as i32, results[2].code);
+        assert!(results[0].id < results[2].id);
+    }
+
+    #[tokio::test]
+    async fn fails_batch_when_receiving_fails() {
+        let events = futures::stream::iter(vec![Ok(1), Err(Status::cancelled("gone"))]);
+        let err = ingest_batch(events, |timestamp, _event: u32| async move {
+            Ok(timestamp.to_string())
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(tonic::Code::Cancelled, err.code());
+    }
+}
@@ -5,6 +5,7 @@ use std::{
     net::{AddrParseError, SocketAddr},
     path::Path,
     str::FromStr,
+    time::Duration,
 };
 
 #[derive(Debug, Deserialize)]
@@ -28,6 +29,26 @@ pub struct Settings {
     /// API token required as part of a Bearer authentication GRPC request
     /// header. Used only by the mobile mode currently
     pub token: Option<String>,
+    /// Wait for each report to be written to the local sink file before
+    /// acknowledging the submission. Default false
+    #[serde(default)]
+    pub wait_for_write: bool,
+    /// Per key rate limits for each report type. Default no limits
+    #[serde(default)]
+    pub rate_limits: RateLimitSettings,
+    /// Optional iot config client settings. When set in iot mode, reports
+    /// from gateways unknown to the iot config service are rejected
+    pub iot_config_client: Option<iot_config::client::Settings>,
+    /// Optional mobile config client settings. When set in mobile mode,
+    /// reports from gateways unknown to the mobile config service are rejected
+    pub mobile_config_client: Option<mobile_config::client::Settings>,
+    /// Time in seconds to cache lookups of known gateways. Default 3600
+    #[serde(default = "default_gateway_cache_ttl_secs")]
+    pub gateway_cache_ttl_secs: u64,
+    /// Time in seconds to cache lookups of unknown gateways, kept short so a
+    /// newly onboarded gateway is accepted soon. Default 60
+    #[serde(default = "default_unknown_gateway_cache_ttl_secs")]
+    pub unknown_gateway_cache_ttl_secs: u64,
     /// Target output bucket details Metrics settings
     pub metrics: poc_metrics::Settings,
 }
@@ -40,10 +61,39 @@ pub fn default_log() -> String {
     "ingest=debug,poc_store=info".to_string()
 }
 
+pub fn default_gateway_cache_ttl_secs() -> u64 {
+    60 * 60
+}
+
+pub fn default_unknown_gateway_cache_ttl_secs() -> u64 {
+    60
+}
+
 pub fn default_sink() -> String {
     "/var/data/ingest".to_string()
 }
 
+/// Optional rate limits for each submitted report type. Beacons, witnesses,
+/// speedtests and data transfer sessions are limited per public key,
+/// heartbeats per cbsd id and subscriber locations per carrier key.
+#[derive(Debug, Deserialize, Default)]
+pub struct RateLimitSettings {
+    pub beacon: Option<RateLimit>,
+    pub witness: Option<RateLimit>,
+    pub heartbeat: Option<RateLimit>,
+    pub speedtest: Option<RateLimit>,
+    pub data_transfer_session: Option<RateLimit>,
+    pub subscriber_location: Option<RateLimit>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct RateLimit {
+    /// Maximum number of reports accepted per key within the period
+    pub max: u32,
+    /// Period in seconds over which `max` reports are accepted
+    pub period_secs: u64,
+}
+
 /// Mode to deploy the ingest engine in. Each mode exposes different submission
 /// grpc methods
 #[derive(Debug, Deserialize)]
@@ -79,4 +129,12 @@ impl Settings {
     pub fn listen_addr(&self) -> Result<SocketAddr, AddrParseError> {
         SocketAddr::from_str(&self.listen)
     }
+
+    pub fn gateway_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.gateway_cache_ttl_secs)
+    }
+
+    pub fn unknown_gateway_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.unknown_gateway_cache_ttl_secs)
+    }
 }
@@ -0,0 +1,10 @@
+CREATE TABLE org_lock_events (
+       oui BIGINT NOT NULL,
+       payer TEXT NOT NULL,
+       locked BOOLEAN NOT NULL,
+       balance BIGINT NOT NULL,
+       threshold BIGINT NOT NULL,
+       timestamp TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX org_lock_events_oui_timestamp_idx ON org_lock_events (oui, timestamp);
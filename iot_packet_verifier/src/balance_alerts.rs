@@ -0,0 +1,104 @@
+use crate::{
+    balances::BalanceStore,
+    settings::Settings,
+    verifier::{ConfigServer, Org},
+};
+use anyhow::{Error, Result};
+use file_store::{file_sink::FileSink, file_upload::MessageSender, FileSinkBuilder, FileType};
+use futures_util::TryFutureExt;
+use payer_alerts::PayerAlerts;
+use std::{path::Path, time::Duration};
+use tokio::time::{self, MissedTickBehavior};
+
+/// Periodically checks the cached balance of the payer of each org, warning
+/// payers whose balance is running low
+pub struct BalanceAlerts<C> {
+    alerts: PayerAlerts,
+    alerts_sink_server: FileSink,
+    balances: BalanceStore,
+    config_server: C,
+    check_period: Duration,
+}
+
+impl<C> BalanceAlerts<C>
+where
+    C: ConfigServer,
+    C::Error: std::fmt::Display,
+{
+    pub async fn from_settings(
+        settings: &Settings,
+        balances: BalanceStore,
+        config_server: C,
+        file_upload_tx: MessageSender,
+        shutdown: triggered::Listener,
+    ) -> Result<Option<Self>> {
+        let Some(alert_settings) = &settings.payer_alerts else {
+            return Ok(None);
+        };
+
+        let (alerts_sink, alerts_sink_server) = FileSinkBuilder::new(
+            FileType::PayerLowBalance,
+            Path::new(&settings.cache),
+            concat!(env!("CARGO_PKG_NAME"), "_payer_low_balance"),
+            shutdown,
+        )
+        .deposits(Some(file_upload_tx))
+        .auto_commit(false)
+        .create()
+        .await?;
+
+        Ok(Some(Self {
+            alerts: PayerAlerts::from_settings(alert_settings, alerts_sink)?,
+            alerts_sink_server,
+            balances,
+            config_server,
+            check_period: Duration::from_secs(60 * settings.balance_check_period),
+        }))
+    }
+
+    pub async fn run(self, shutdown: &triggered::Listener) -> Result<()> {
+        let Self {
+            alerts,
+            mut alerts_sink_server,
+            balances,
+            config_server,
+            check_period,
+        } = self;
+        let mut check_timer = time::interval(check_period);
+        check_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        tokio::try_join!(alerts_sink_server.run().map_err(Error::from), async {
+            loop {
+                tokio::select! {
+                    _ = shutdown.clone() => return Ok::<(), Error>(()),
+                    _ = check_timer.tick() => {
+                        check_balances(&alerts, &balances, &config_server).await
+                    }
+                }
+            }
+        })
+        .map(|_| ())
+    }
+}
+
+async fn check_balances<C>(alerts: &PayerAlerts, balances: &BalanceStore, config_server: &C)
+where
+    C: ConfigServer,
+    C::Error: std::fmt::Display,
+{
+    let orgs = match config_server.list_orgs().await {
+        Ok(orgs) => orgs,
+        Err(err) => {
+            tracing::error!("failed to list orgs for balance alerts: {err}");
+            return;
+        }
+    };
+    for Org { oui, payer, .. } in orgs {
+        // Payers which haven't been debited have no cached balance
+        let balance = match balances.lock().await.get(&payer) {
+            Some(balance) => balance.balance.saturating_sub(balance.burned),
+            None => continue,
+        };
+        alerts.observe(Some(oui), &payer, balance).await;
+    }
+}
@@ -1,14 +1,17 @@
 use crate::{
+    balance_alerts::BalanceAlerts,
     balances::BalanceCache,
     burner::Burner,
+    org_locks::{OrgLockEvents, OrgLocks},
     settings::Settings,
-    verifier::{CachedOrgClient, ConfigServer, Verifier},
+    verifier::{refresh_org_cache, CachedOrgClient, ConfigServer, PacketCopies, Verifier},
 };
 use anyhow::{bail, Error, Result};
 use file_store::{
     file_info_poller::{FileInfoStream, LookbackBehavior},
     file_sink::FileSinkClient,
     file_source, file_upload,
+    iot_org_lock::IotOrgLockEvent,
     iot_packet::PacketRouterPacketReport,
     FileSinkBuilder, FileStore, FileType,
 };
@@ -23,11 +26,11 @@ use tokio::{
 
 struct Daemon {
     pool: Pool<Postgres>,
-    verifier: Verifier<BalanceCache<Option<Arc<SolanaRpc>>>, Arc<Mutex<CachedOrgClient>>>,
+    verifier: Verifier<BalanceCache<Option<Arc<SolanaRpc>>>, CachedOrgClient>,
     report_files: Receiver<FileInfoStream<PacketRouterPacketReport>>,
     valid_packets: FileSinkClient,
     invalid_packets: FileSinkClient,
-    minimum_allowed_balance: u64,
+    org_lock_events: OrgLockEvents,
 }
 
 impl Daemon {
@@ -58,18 +61,24 @@ impl Daemon {
         let mut transaction = self.pool.begin().await?;
         let reports = report_file.into_stream(&mut transaction).await?;
 
+        let org_lock_log: Arc<Mutex<Vec<IotOrgLockEvent>>> = Arc::default();
         self.verifier
             .verify(
-                self.minimum_allowed_balance,
                 &mut transaction,
                 reports,
                 &self.valid_packets,
                 &self.invalid_packets,
+                org_lock_log.clone(),
             )
             .await?;
+        let org_lock_events = std::mem::take(&mut *org_lock_log.lock().await);
+        for event in &org_lock_events {
+            OrgLockEvents::insert(&mut transaction, event).await?;
+        }
         transaction.commit().await?;
         self.valid_packets.commit().await?;
         self.invalid_packets.commit().await?;
+        self.org_lock_events.report(org_lock_events).await?;
 
         Ok(())
     }
@@ -96,7 +105,7 @@ impl Cmd {
             .database
             .connect(env!("CARGO_PKG_NAME"), shutdown_listener.clone())
             .await?;
-        sqlx::migrate!().run(&pool).await?;
+        db_store::schema::migrate(&pool, &sqlx::migrate!()).await?;
 
         let solana = if settings.enable_solana_integration {
             let Some(ref solana_settings) = settings.solana else {
@@ -155,7 +164,29 @@ impl Cmd {
         .create()
         .await?;
 
+        let (org_lock_sink, mut org_lock_sink_server) = FileSinkBuilder::new(
+            FileType::IotOrgLockEvent,
+            store_base_path,
+            concat!(env!("CARGO_PKG_NAME"), "_org_lock_events"),
+            shutdown_listener.clone(),
+        )
+        .deposits(Some(file_upload_tx.clone()))
+        .auto_commit(false)
+        .create()
+        .await?;
+        let org_lock_events = OrgLockEvents {
+            pool: pool.clone(),
+            sink: org_lock_sink,
+        };
+        let org_locks = OrgLocks::load(
+            &pool,
+            settings.minimum_allowed_balance,
+            settings.unlock_margin,
+        )
+        .await?;
+
         let org_client = settings.connect_org();
+        let route_client = settings.connect_route();
 
         let file_store = FileStore::from_settings(&settings.ingest).await?;
 
@@ -170,8 +201,26 @@ impl Cmd {
                 .await?;
 
         let config_keypair = settings.config_keypair()?;
-        let config_server = CachedOrgClient::new(org_client, config_keypair);
+        let config_server = CachedOrgClient::new(
+            org_client,
+            route_client,
+            config_keypair,
+            settings.config_pubkey()?,
+        );
         let balance_store = balances.balances();
+        let balance_alerts = BalanceAlerts::from_settings(
+            settings,
+            balance_store.clone(),
+            config_server.clone(),
+            file_upload_tx.clone(),
+            shutdown_listener.clone(),
+        )
+        .await?;
+        let org_cache_refresh = refresh_org_cache(
+            config_server.clone(),
+            Duration::from_secs(60 * settings.org_cache_refresh_period),
+            shutdown_listener.clone(),
+        );
         let verifier_daemon = Daemon {
             pool,
             report_files,
@@ -180,8 +229,10 @@ impl Cmd {
             verifier: Verifier {
                 debiter: balances,
                 config_server: config_server.clone(),
+                packet_copies: PacketCopies::new(settings.packet_copies_window()),
+                org_locks: org_locks.clone(),
             },
-            minimum_allowed_balance: settings.minimum_allowed_balance,
+            org_lock_events: org_lock_events.clone(),
         };
 
         // Run the services:
@@ -192,15 +243,24 @@ impl Cmd {
             verifier_daemon.run(&shutdown_listener).map_err(Error::from),
             valid_packets_server.run().map_err(Error::from),
             invalid_packets_server.run().map_err(Error::from),
+            org_lock_sink_server.run().map_err(Error::from),
             config_server
                 .monitor_funds(
                     solana,
                     balance_store,
-                    settings.minimum_allowed_balance,
+                    org_locks,
+                    org_lock_events,
                     Duration::from_secs(60 * settings.monitor_funds_period),
                     shutdown_listener.clone(),
                 )
                 .map_err(Error::from),
+            org_cache_refresh,
+            async {
+                match balance_alerts {
+                    Some(balance_alerts) => balance_alerts.run(&shutdown_listener).await,
+                    None => Ok(()),
+                }
+            },
             source_join_handle.map_err(Error::from),
             sol_balance_monitor.map_err(Error::from),
         )?;
@@ -1,6 +1,8 @@
+pub mod balance_alerts;
 pub mod balances;
 pub mod burner;
 pub mod daemon;
+pub mod org_locks;
 pub mod pending_burns;
 pub mod settings;
 pub mod verifier;
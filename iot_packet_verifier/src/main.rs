@@ -32,12 +32,20 @@ impl Cli {
 #[derive(clap::Subcommand)]
 pub enum Cmd {
     Server(daemon::Cmd),
+    Migrate(db_store::schema::Cmd),
 }
 
 impl Cmd {
     async fn run(self, settings: Settings) -> Result<()> {
         match self {
             Self::Server(cmd) => cmd.run(&settings).await,
+            Self::Migrate(cmd) => Ok(cmd
+                .run(
+                    &settings.database,
+                    env!("CARGO_PKG_NAME"),
+                    &sqlx::migrate!(),
+                )
+                .await?),
         }
     }
 }
@@ -0,0 +1,214 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use file_store::{
+    file_sink::FileSinkClient,
+    iot_org_lock::{IotOrgLockEvent, IotOrgLockEventV1},
+};
+use helium_crypto::PublicKeyBinary;
+use sqlx::{PgExecutor, Pool, Postgres, Row};
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
+use tokio::sync::Mutex;
+
+/// The locked state of each org as set by the packet verifier. An org is
+/// locked once when the balance of its payer falls below the minimum allowed
+/// balance, rather than on every packet after, and is unlocked only once the
+/// balance has recovered to the unlock margin above the minimum.
+#[derive(Clone)]
+pub struct OrgLocks {
+    minimum_allowed_balance: u64,
+    unlock_margin: u64,
+    locked: Arc<Mutex<HashMap<u64, bool>>>,
+}
+
+impl OrgLocks {
+    pub fn new(minimum_allowed_balance: u64, unlock_margin: u64) -> Self {
+        Self {
+            minimum_allowed_balance,
+            unlock_margin,
+            locked: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Resume from the last transition of each org recorded in the database
+    pub async fn load(
+        pool: &Pool<Postgres>,
+        minimum_allowed_balance: u64,
+        unlock_margin: u64,
+    ) -> Result<Self, sqlx::Error> {
+        let locked = sqlx::query(
+            r#"
+            SELECT DISTINCT ON (oui) oui, locked FROM org_lock_events
+            ORDER BY oui, timestamp DESC
+            "#,
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.get::<i64, &str>("oui") as u64, row.get("locked")))
+        .collect();
+        Ok(Self {
+            minimum_allowed_balance,
+            unlock_margin,
+            locked: Arc::new(Mutex::new(locked)),
+        })
+    }
+
+    pub fn unlock_threshold(&self) -> u64 {
+        self.minimum_allowed_balance
+            .saturating_add(self.unlock_margin)
+    }
+
+    /// The transition locking the org, if the balance of its payer is below
+    /// the minimum allowed balance and it isn't locked already. The org is
+    /// only taken to be locked once the transition is committed
+    pub async fn lock_transition(
+        &self,
+        oui: u64,
+        payer: &PublicKeyBinary,
+        balance: u64,
+    ) -> Option<IotOrgLockEvent> {
+        if balance >= self.minimum_allowed_balance || self.is_locked(oui).await {
+            return None;
+        }
+        Some(IotOrgLockEvent {
+            timestamp: Utc::now(),
+            oui,
+            payer: payer.clone(),
+            locked: true,
+            balance,
+            threshold: self.minimum_allowed_balance,
+        })
+    }
+
+    /// The transition unlocking the org, if it is locked and the balance of
+    /// its payer has reached the unlock threshold. The org is only taken to
+    /// be unlocked once the transition is committed
+    pub async fn unlock_transition(
+        &self,
+        oui: u64,
+        payer: &PublicKeyBinary,
+        balance: u64,
+    ) -> Option<IotOrgLockEvent> {
+        let threshold = self.unlock_threshold();
+        if balance < threshold || !self.is_locked(oui).await {
+            return None;
+        }
+        Some(IotOrgLockEvent {
+            timestamp: Utc::now(),
+            oui,
+            payer: payer.clone(),
+            locked: false,
+            balance,
+            threshold,
+        })
+    }
+
+    /// Take up a transition once the config server has applied it
+    pub async fn commit(&self, event: &IotOrgLockEvent) {
+        self.locked.lock().await.insert(event.oui, event.locked);
+    }
+
+    async fn is_locked(&self, oui: u64) -> bool {
+        self.locked
+            .lock()
+            .await
+            .get(&oui)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The locked state of the org, taking up the given state as seen by the
+    /// config server for an org the verifier has never transitioned
+    pub async fn observe(&self, oui: u64, locked: bool) -> bool {
+        *self.locked.lock().await.entry(oui).or_insert(locked)
+    }
+}
+
+#[async_trait]
+pub trait OrgLockLog {
+    type Error;
+
+    async fn record(&mut self, event: IotOrgLockEvent) -> Result<(), Self::Error>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum OrgLockLogError {
+    #[error("Sql error: {0}")]
+    SqlError(#[from] sqlx::Error),
+    #[error("File store error: {0}")]
+    FileStoreError(#[from] file_store::Error),
+}
+
+/// Records org lock transitions to the database and writes them out as
+/// org lock event reports
+#[derive(Clone)]
+pub struct OrgLockEvents {
+    pub pool: Pool<Postgres>,
+    pub sink: FileSinkClient,
+}
+
+impl OrgLockEvents {
+    pub async fn insert<'c, E>(executor: E, event: &IotOrgLockEvent) -> Result<(), sqlx::Error>
+    where
+        E: PgExecutor<'c>,
+    {
+        tracing::info!(
+            oui = event.oui,
+            locked = event.locked,
+            balance = event.balance,
+            threshold = event.threshold,
+            "org lock transition"
+        );
+        sqlx::query(
+            r#"
+            INSERT INTO org_lock_events (oui, payer, locked, balance, threshold, timestamp)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(event.oui as i64)
+        .bind(&event.payer)
+        .bind(event.locked)
+        .bind(event.balance as i64)
+        .bind(event.threshold as i64)
+        .bind(event.timestamp)
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+
+    /// Write out the reports of transitions already recorded to the database
+    pub async fn report(
+        &self,
+        events: impl IntoIterator<Item = IotOrgLockEvent>,
+    ) -> Result<(), file_store::Error> {
+        for event in events {
+            self.sink.write(IotOrgLockEventV1::from(event), []).await?;
+        }
+        self.sink.commit().await?;
+        Ok(())
+    }
+}
+
+/// Records the transitions of the funds monitor, which are made outside of
+/// the verification of any file. Transitions made while verifying a file are
+/// recorded within the transaction of the file instead
+#[async_trait]
+impl OrgLockLog for OrgLockEvents {
+    type Error = OrgLockLogError;
+
+    async fn record(&mut self, event: IotOrgLockEvent) -> Result<(), Self::Error> {
+        Self::insert(&self.pool, &event).await?;
+        self.report([event]).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OrgLockLog for Arc<Mutex<Vec<IotOrgLockEvent>>> {
+    type Error = Infallible;
+
+    async fn record(&mut self, event: IotOrgLockEvent) -> Result<(), Infallible> {
+        self.lock().await.push(event);
+        Ok(())
+    }
+}
@@ -1,8 +1,14 @@
 use chrono::{DateTime, TimeZone, Utc};
 use config::{Config, ConfigError, Environment, File};
-use helium_proto::services::{iot_config::config_org_client::OrgClient, Channel, Endpoint};
+use helium_proto::services::{
+    iot_config::{config_org_client::OrgClient, config_route_client::RouteClient},
+    Channel, Endpoint,
+};
 use serde::Deserialize;
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct Settings {
@@ -14,6 +20,11 @@ pub struct Settings {
     pub cache: String,
     /// Path to the keypair for signing config changes
     pub config_keypair: PathBuf,
+    /// B58 encoded public key of the iot config server for verifying responses
+    pub config_pubkey: String,
+    /// Number of minutes between refreshes of the cached orgs. Default is 5.
+    #[serde(default = "default_org_cache_refresh_period")]
+    pub org_cache_refresh_period: u64,
     /// Data credit burn period in minutes. Default is 1.
     #[serde(default = "default_burn_period")]
     pub burn_period: u64,
@@ -28,6 +39,11 @@ pub struct Settings {
     /// Minimum data credit balance required for a payer before we disable them
     #[serde(default = "default_minimum_allowed_balance")]
     pub minimum_allowed_balance: u64,
+    /// Data credits above the minimum allowed balance that the balance of a
+    /// payer must reach before its locked orgs are unlocked. Default is
+    /// 500_000.
+    #[serde(default = "default_unlock_margin")]
+    pub unlock_margin: u64,
     pub solana: Option<solana::Settings>,
     #[serde(default = "default_start_after")]
     pub start_after: u64,
@@ -35,6 +51,17 @@ pub struct Settings {
     /// any disabled orgs.
     #[serde(default = "default_monitor_funds_period")]
     pub monitor_funds_period: u64,
+    /// Window in seconds from the first report of a packet within which
+    /// reports of the same payload to the same org are copies of it, charged
+    /// for up to the max copies of the routes of the org. Default is 60.
+    #[serde(default = "default_packet_copies_window")]
+    pub packet_copies_window: i64,
+    /// Low balance warnings to the payers of orgs. Disabled if not set
+    pub payer_alerts: Option<payer_alerts::Settings>,
+    /// Number of minutes between checks of the balances of org payers for
+    /// low balance warnings. Default is 10.
+    #[serde(default = "default_balance_check_period")]
+    pub balance_check_period: u64,
 }
 
 pub fn default_start_after() -> u64 {
@@ -53,10 +80,26 @@ pub fn default_minimum_allowed_balance() -> u64 {
     3_500_000
 }
 
+pub fn default_unlock_margin() -> u64 {
+    500_000
+}
+
 pub fn default_monitor_funds_period() -> u64 {
     30
 }
 
+pub fn default_packet_copies_window() -> i64 {
+    60
+}
+
+pub fn default_org_cache_refresh_period() -> u64 {
+    5
+}
+
+pub fn default_balance_check_period() -> u64 {
+    10
+}
+
 impl Settings {
     /// Load Settings from a given path. Settings are loaded from a given
     /// optional path and can be overriden with environment variables.
@@ -84,11 +127,23 @@ impl Settings {
         OrgClient::new(Endpoint::from(self.org_url.clone()).connect_lazy())
     }
 
+    pub fn connect_route(&self) -> RouteClient<Channel> {
+        RouteClient::new(Endpoint::from(self.org_url.clone()).connect_lazy())
+    }
+
+    pub fn packet_copies_window(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.packet_copies_window)
+    }
+
     pub fn config_keypair(&self) -> Result<helium_crypto::Keypair, Box<helium_crypto::Error>> {
         let data = std::fs::read(&self.config_keypair).map_err(helium_crypto::Error::from)?;
         Ok(helium_crypto::Keypair::try_from(&data[..])?)
     }
 
+    pub fn config_pubkey(&self) -> Result<helium_crypto::PublicKey, helium_crypto::Error> {
+        helium_crypto::PublicKey::from_str(&self.config_pubkey)
+    }
+
     pub fn start_after(&self) -> DateTime<Utc> {
         Utc.timestamp_opt(self.start_after as i64, 0)
             .single()
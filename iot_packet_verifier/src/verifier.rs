@@ -1,25 +1,33 @@
-use crate::pending_burns::PendingBurns;
+use crate::{
+    org_locks::{OrgLockLog, OrgLocks},
+    pending_burns::PendingBurns,
+};
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use file_store::{
-    file_sink::FileSinkClient, iot_packet::PacketRouterPacketReport, traits::MsgTimestamp,
+    file_sink::FileSinkClient,
+    iot_packet::PacketRouterPacketReport,
+    traits::{MsgTimestamp, MsgVerify},
 };
 use futures::{Stream, StreamExt};
-use helium_crypto::{Keypair, PublicKeyBinary, Sign};
+use helium_crypto::{Keypair, PublicKey, PublicKeyBinary, Sign};
 use helium_proto::services::{
     iot_config::OrgGetReqV1,
     packet_verifier::{InvalidPacket, InvalidPacketReason, ValidPacket},
 };
 use helium_proto::{
     services::{
-        iot_config::{config_org_client::OrgClient, OrgDisableReqV1, OrgEnableReqV1, OrgListReqV1},
+        iot_config::{
+            config_org_client::OrgClient, config_route_client::RouteClient, OrgDisableReqV1,
+            OrgEnableReqV1, OrgListReqV1, OrgResV1, RouteListReqV1,
+        },
         Channel,
     },
     Message,
 };
 use solana::SolanaNetwork;
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, HashMap, HashSet},
     convert::Infallible,
     fmt::Debug,
     sync::Arc,
@@ -33,10 +41,60 @@ use tokio::{
 pub struct Verifier<D, C> {
     pub debiter: D,
     pub config_server: C,
+    pub packet_copies: PacketCopies,
+    pub org_locks: OrgLocks,
+}
+
+/// Reason given for the copies of a packet in excess of the max copies of
+/// the routes of its org. The helium proto `InvalidPacketReason` has no
+/// variant for these, so the value following `InsufficientBalance` is
+/// reserved for them here
+pub const INVALID_PACKET_REASON_EXCESS_COPIES: i32 = 1;
+
+/// Count of the copies of each packet reported by different gateways, keyed
+/// by the oui and payload hash of the packet. Reports received within the
+/// window of the first report of a packet are copies of it, later reports
+/// start a new count. Repeat reports by a gateway are not further copies.
+/// Kept across report files, as the copies of a packet can be split between
+/// them
+pub struct PacketCopies {
+    window: chrono::Duration,
+    copies: HashMap<(u64, Vec<u8>), (DateTime<Utc>, HashSet<PublicKeyBinary>)>,
+}
+
+impl PacketCopies {
+    pub fn new(window: chrono::Duration) -> Self {
+        Self {
+            window,
+            copies: HashMap::new(),
+        }
+    }
+
+    /// Count the report as a copy of its packet, returning the number of
+    /// gateways which reported the packet so far including this one
+    fn count(&mut self, report: &PacketRouterPacketReport) -> u32 {
+        let (first_received, gateways) = self
+            .copies
+            .entry((report.oui, report.payload_hash.clone()))
+            .or_insert_with(|| (report.received_timestamp, HashSet::new()));
+        if report.received_timestamp - *first_received > self.window {
+            *first_received = report.received_timestamp;
+            gateways.clear();
+        }
+        gateways.insert(report.gateway.clone());
+        gateways.len() as u32
+    }
+
+    /// Forget the packets first received more than a window before `latest`
+    fn prune(&mut self, latest: DateTime<Utc>) {
+        let window = self.window;
+        self.copies
+            .retain(|_, (first_received, _)| latest - *first_received <= window);
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
-pub enum VerificationError<DE, CE, BE, VPE, IPE> {
+pub enum VerificationError<DE, CE, BE, VPE, IPE, LE> {
     #[error("Debit error: {0}")]
     DebitError(DE),
     #[error("Config server error: {0}")]
@@ -47,6 +105,8 @@ pub enum VerificationError<DE, CE, BE, VPE, IPE> {
     ValidPacketWriterError(VPE),
     #[error("Invalid packet writer error: {0}")]
     InvalidPacketWriterError(IPE),
+    #[error("Org lock log error: {0}")]
+    OrgLockLogError(LE),
 }
 
 impl<D, C> Verifier<D, C>
@@ -54,31 +114,54 @@ where
     D: Debiter,
     C: ConfigServer,
 {
-    /// Verify a stream of packet reports. Writes out `valid_packets` and `invalid_packets`.
-    pub async fn verify<B, R, VP, IP>(
+    /// Verify a stream of packet reports. Writes out `valid_packets` and `invalid_packets`,
+    /// and records the orgs locked for running low on balance to `org_lock_log`.
+    pub async fn verify<B, R, VP, IP, L>(
         &mut self,
-        minimum_allowed_balance: u64,
         mut pending_burns: B,
         reports: R,
         mut valid_packets: VP,
         mut invalid_packets: IP,
-    ) -> Result<(), VerificationError<D::Error, C::Error, B::Error, VP::Error, IP::Error>>
+        mut org_lock_log: L,
+    ) -> Result<(), VerificationError<D::Error, C::Error, B::Error, VP::Error, IP::Error, L::Error>>
     where
         B: PendingBurns,
         R: Stream<Item = PacketRouterPacketReport>,
         VP: PacketWriter<ValidPacket>,
         IP: PacketWriter<InvalidPacket>,
+        L: OrgLockLog,
     {
-        let mut org_cache = HashMap::<u64, PublicKeyBinary>::new();
+        let mut max_copies_cache = HashMap::<u64, u32>::new();
+        let mut latest_received = None;
 
         tokio::pin!(reports);
 
         while let Some(report) = reports.next().await {
+            latest_received = latest_received.max(Some(report.received_timestamp));
+
+            let max_copies = self
+                .config_server
+                .fetch_max_copies(report.oui, &mut max_copies_cache)
+                .await
+                .map_err(VerificationError::ConfigError)?;
+            if self.packet_copies.count(&report) > max_copies {
+                invalid_packets
+                    .write(InvalidPacket {
+                        payload_size: report.payload_size,
+                        gateway: report.gateway.into(),
+                        payload_hash: report.payload_hash,
+                        reason: INVALID_PACKET_REASON_EXCESS_COPIES,
+                    })
+                    .await
+                    .map_err(VerificationError::InvalidPacketWriterError)?;
+                continue;
+            }
+
             let debit_amount = payload_size_to_dc(report.payload_size as u64);
 
             let payer = self
                 .config_server
-                .fetch_org(report.oui, &mut org_cache)
+                .fetch_org(report.oui)
                 .await
                 .map_err(VerificationError::ConfigError)?;
             let remaining_balance = self
@@ -103,11 +186,20 @@ where
                     .await
                     .map_err(VerificationError::ValidPacketWriterError)?;
 
-                if remaining_balance < minimum_allowed_balance {
+                if let Some(event) = self
+                    .org_locks
+                    .lock_transition(report.oui, &payer, remaining_balance)
+                    .await
+                {
                     self.config_server
                         .disable_org(report.oui)
                         .await
                         .map_err(VerificationError::ConfigError)?;
+                    self.org_locks.commit(&event).await;
+                    org_lock_log
+                        .record(event)
+                        .await
+                        .map_err(VerificationError::OrgLockLogError)?;
                 }
             } else {
                 invalid_packets
@@ -122,6 +214,10 @@ where
             }
         }
 
+        if let Some(latest_received) = latest_received {
+            self.packet_copies.prune(latest_received);
+        }
+
         Ok(())
     }
 }
@@ -165,6 +261,7 @@ impl Debiter for Arc<Mutex<HashMap<PublicKeyBinary, u64>>> {
 
 // TODO: Move these to a separate module
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Org {
     pub oui: u64,
     pub payer: PublicKeyBinary,
@@ -175,11 +272,16 @@ pub struct Org {
 pub trait ConfigServer: Sized + Send + Sync + 'static {
     type Error: Send + Sync + 'static;
 
-    async fn fetch_org(
+    /// The payer of the org
+    async fn fetch_org(&self, oui: u64) -> Result<PublicKeyBinary, Self::Error>;
+
+    /// The max number of copies of a packet charged for, being the greatest
+    /// max copies of the active routes of the org
+    async fn fetch_max_copies(
         &self,
         oui: u64,
-        cache: &mut HashMap<u64, PublicKeyBinary>,
-    ) -> Result<PublicKeyBinary, Self::Error>;
+        cache: &mut HashMap<u64, u32>,
+    ) -> Result<u32, Self::Error>;
 
     async fn disable_org(&self, oui: u64) -> Result<(), Self::Error>;
 
@@ -187,17 +289,20 @@ pub trait ConfigServer: Sized + Send + Sync + 'static {
 
     async fn list_orgs(&self) -> Result<Vec<Org>, Self::Error>;
 
-    async fn monitor_funds<S, B>(
+    async fn monitor_funds<S, B, L>(
         self,
         solana: S,
         balances: B,
-        minimum_allowed_balance: u64,
+        org_locks: OrgLocks,
+        mut org_lock_log: L,
         monitor_period: Duration,
         shutdown: triggered::Listener,
-    ) -> Result<(), MonitorError<S::Error, Self::Error>>
+    ) -> Result<(), MonitorError<S::Error, Self::Error, L::Error>>
     where
         S: SolanaNetwork,
         B: BalanceStore,
+        L: OrgLockLog + Send + 'static,
+        L::Error: Send,
     {
         let join_handle = tokio::spawn(async move {
             loop {
@@ -209,16 +314,30 @@ pub trait ConfigServer: Sized + Send + Sync + 'static {
                     .map_err(MonitorError::ConfigClientError)?
                     .into_iter()
                 {
-                    if locked {
+                    if org_locks.observe(oui, locked).await {
                         let balance = solana
                             .payer_balance(&payer)
                             .await
                             .map_err(MonitorError::SolanaError)?;
-                        if balance >= minimum_allowed_balance {
+                        if let Some(event) = org_locks.unlock_transition(oui, &payer, balance).await
+                        {
                             balances.set_balance(&payer, balance).await;
                             self.enable_org(oui)
                                 .await
                                 .map_err(MonitorError::ConfigClientError)?;
+                            org_locks.commit(&event).await;
+                            org_lock_log
+                                .record(event)
+                                .await
+                                .map_err(MonitorError::OrgLockLogError)?;
+                        } else if !locked {
+                            // The org was enabled on the config server behind
+                            // the verifier's back, while its payer is still
+                            // short of the unlock threshold
+                            tracing::warn!(oui, "re-disabling org locked by the verifier");
+                            self.disable_org(oui)
+                                .await
+                                .map_err(MonitorError::ConfigClientError)?;
                         }
                     }
                 }
@@ -258,46 +377,90 @@ impl BalanceStore for Arc<Mutex<HashMap<PublicKeyBinary, u64>>> {
 }
 
 #[derive(thiserror::Error, Debug)]
-pub enum MonitorError<S, E> {
+pub enum MonitorError<S, E, L> {
     #[error("Join error: {0}")]
     JoinError(#[from] JoinError),
     #[error("Config client error: {0}")]
     ConfigClientError(E),
     #[error("Solana error: {0}")]
     SolanaError(S),
+    #[error("Org lock log error: {0}")]
+    OrgLockLogError(L),
 }
 
 // Probably should change name to something like OrgClientCache to be more
 // consistent with BalanceCache
+#[derive(Clone)]
 pub struct CachedOrgClient {
-    pub keypair: Keypair,
+    pub keypair: Arc<Keypair>,
+    /// Key the iot config server signs its responses with
+    pub config_pubkey: PublicKey,
     pub client: OrgClient<Channel>,
+    pub route_client: RouteClient<Channel>,
+    /// Orgs as last fetched from the config server, kept for the life of
+    /// the process and refreshed by [`refresh_org_cache`]. Only locked to
+    /// read or update the orgs, never across a call to the config server
+    pub orgs: Arc<Mutex<HashMap<u64, Org>>>,
 }
 
 impl CachedOrgClient {
-    pub fn new(client: OrgClient<Channel>, keypair: Keypair) -> Arc<Mutex<Self>> {
-        Arc::new(Mutex::new(CachedOrgClient { keypair, client }))
+    pub fn new(
+        client: OrgClient<Channel>,
+        route_client: RouteClient<Channel>,
+        keypair: Keypair,
+        config_pubkey: PublicKey,
+    ) -> Self {
+        CachedOrgClient {
+            keypair: Arc::new(keypair),
+            config_pubkey,
+            client,
+            route_client,
+            orgs: Arc::default(),
+        }
     }
 
-    async fn enable_org(&mut self, oui: u64) -> Result<(), OrgClientError> {
-        tracing::info!(%oui, "enabling org");
-
-        let mut req = OrgEnableReqV1 {
-            oui,
-            timestamp: Utc::now().timestamp_millis() as u64,
-            signer: self.keypair.public_key().into(),
-            signature: vec![],
-        };
-        let signature = self.keypair.sign(&req.encode_to_vec())?;
-        req.signature = signature;
-        let _ = self.client.enable(req).await?;
-        Ok(())
+    async fn get_org(&self, oui: u64) -> Result<Org, OrgClientError> {
+        let response = self
+            .client
+            .clone()
+            .get(OrgGetReqV1 { oui })
+            .await?
+            .into_inner();
+        verified_org(response, &self.config_pubkey)
     }
 
-    async fn disable_org(&mut self, oui: u64) -> Result<(), OrgClientError> {
-        tracing::info!(%oui, "disabling org");
+    /// Fetch every org, replacing the cached orgs with them
+    async fn refresh_orgs(&self) -> Result<Vec<Org>, OrgClientError> {
+        let response = self
+            .client
+            .clone()
+            .list(OrgListReqV1 {})
+            .await?
+            .into_inner();
+        response.verify(&self.config_pubkey)?;
+        let orgs: Vec<Org> = response
+            .orgs
+            .into_iter()
+            .map(|org| Org {
+                oui: org.oui,
+                payer: PublicKeyBinary::from(org.payer),
+                locked: org.locked,
+            })
+            .collect();
+        let mut cached_orgs = self.orgs.lock().await;
+        for org in &orgs {
+            if let Some(cached) = cached_orgs.get(&org.oui) {
+                if cached.payer != org.payer {
+                    tracing::info!(oui = %org.oui, payer = %org.payer, "org payer changed");
+                }
+            }
+        }
+        *cached_orgs = orgs.iter().map(|org| (org.oui, org.clone())).collect();
+        Ok(orgs)
+    }
 
-        let mut req = OrgDisableReqV1 {
+    async fn max_copies(&self, oui: u64) -> Result<u32, OrgClientError> {
+        let mut req = RouteListReqV1 {
             oui,
             timestamp: Utc::now().timestamp_millis() as u64,
             signer: self.keypair.public_key().into(),
@@ -305,73 +468,139 @@ impl CachedOrgClient {
         };
         let signature = self.keypair.sign(&req.encode_to_vec())?;
         req.signature = signature;
-        let _ = self.client.disable(req).await?;
-        Ok(())
+        let max_copies = self
+            .route_client
+            .clone()
+            .list(req)
+            .await?
+            .into_inner()
+            .routes
+            .into_iter()
+            .filter(|route| route.active)
+            .map(|route| route.max_copies)
+            .max()
+            .unwrap_or_default();
+        // A packet reported at all was routed, so the first copy is always
+        // charged for
+        Ok(max_copies.max(1))
+    }
+
+    async fn set_org_locked(&self, oui: u64, locked: bool) {
+        if let Some(org) = self.orgs.lock().await.get_mut(&oui) {
+            org.locked = locked;
+        }
     }
 }
 
+/// The org of a config server response, provided the response is signed by
+/// the config server
+pub fn verified_org(response: OrgResV1, config_pubkey: &PublicKey) -> Result<Org, OrgClientError> {
+    response.verify(config_pubkey)?;
+    let org = response.org.ok_or(OrgClientError::NoOrg)?;
+    Ok(Org {
+        oui: org.oui,
+        payer: PublicKeyBinary::from(org.payer),
+        locked: org.locked,
+    })
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum OrgClientError {
     #[error("Rpc error: {0}")]
     RpcError(#[from] tonic::Status),
     #[error("Crypto error: {0}")]
     CryptoError(#[from] helium_crypto::Error),
+    #[error("Response verification error: {0}")]
+    VerificationError(#[from] file_store::Error),
     #[error("No org")]
     NoOrg,
 }
 
 #[async_trait]
-impl ConfigServer for Arc<Mutex<CachedOrgClient>> {
+impl ConfigServer for CachedOrgClient {
     type Error = OrgClientError;
 
-    async fn fetch_org(
+    async fn fetch_org(&self, oui: u64) -> Result<PublicKeyBinary, Self::Error> {
+        if let Some(org) = self.orgs.lock().await.get(&oui) {
+            return Ok(org.payer.clone());
+        }
+        let org = self.get_org(oui).await?;
+        let payer = org.payer.clone();
+        self.orgs.lock().await.insert(oui, org);
+        Ok(payer)
+    }
+
+    async fn fetch_max_copies(
         &self,
         oui: u64,
-        cache: &mut HashMap<u64, PublicKeyBinary>,
-    ) -> Result<PublicKeyBinary, Self::Error> {
+        cache: &mut HashMap<u64, u32>,
+    ) -> Result<u32, Self::Error> {
         if let Entry::Vacant(e) = cache.entry(oui) {
-            let req = OrgGetReqV1 { oui };
-            let pubkey = PublicKeyBinary::from(
-                self.lock()
-                    .await
-                    .client
-                    .get(req)
-                    .await?
-                    .into_inner()
-                    .org
-                    .ok_or(OrgClientError::NoOrg)?
-                    .payer,
-            );
-            e.insert(pubkey);
+            let max_copies = self.max_copies(oui).await?;
+            e.insert(max_copies);
         }
-        Ok(cache.get(&oui).unwrap().clone())
+        Ok(*cache.get(&oui).unwrap())
     }
 
     async fn disable_org(&self, oui: u64) -> Result<(), Self::Error> {
-        self.lock().await.disable_org(oui).await
+        tracing::info!(%oui, "disabling org");
+
+        let mut req = OrgDisableReqV1 {
+            oui,
+            timestamp: Utc::now().timestamp_millis() as u64,
+            signer: self.keypair.public_key().into(),
+            signature: vec![],
+        };
+        let signature = self.keypair.sign(&req.encode_to_vec())?;
+        req.signature = signature;
+        let _ = self.client.clone().disable(req).await?;
+        self.set_org_locked(oui, true).await;
+        Ok(())
     }
 
     async fn enable_org(&self, oui: u64) -> Result<(), Self::Error> {
-        self.lock().await.enable_org(oui).await
+        tracing::info!(%oui, "enabling org");
+
+        let mut req = OrgEnableReqV1 {
+            oui,
+            timestamp: Utc::now().timestamp_millis() as u64,
+            signer: self.keypair.public_key().into(),
+            signature: vec![],
+        };
+        let signature = self.keypair.sign(&req.encode_to_vec())?;
+        req.signature = signature;
+        let _ = self.client.clone().enable(req).await?;
+        self.set_org_locked(oui, false).await;
+        Ok(())
     }
 
     async fn list_orgs(&self) -> Result<Vec<Org>, Self::Error> {
-        Ok(self
-            .lock()
-            .await
-            .client
-            .list(OrgListReqV1 {})
-            .await
-            .map_err(OrgClientError::RpcError)?
-            .into_inner()
-            .orgs
-            .into_iter()
-            .map(|org| Org {
-                oui: org.oui,
-                payer: PublicKeyBinary::from(org.payer),
-                locked: org.locked,
-            })
-            .collect())
+        self.refresh_orgs().await
+    }
+}
+
+/// Periodically refresh the cached orgs, picking up payer rotations and
+/// lock changes made outside of the verifier. The iot config server has no
+/// stream of org changes to subscribe to, so the orgs are listed instead. A
+/// failed refresh keeps the cached orgs until the next
+pub async fn refresh_org_cache(
+    config_server: CachedOrgClient,
+    refresh_period: Duration,
+    shutdown: triggered::Listener,
+) -> anyhow::Result<()> {
+    let mut refresh_timer = tokio::time::interval(refresh_period);
+    refresh_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            _ = shutdown.clone() => return Ok(()),
+            _ = refresh_timer.tick() => {
+                match config_server.refresh_orgs().await {
+                    Ok(orgs) => tracing::info!("refreshed org cache with {} orgs", orgs.len()),
+                    Err(err) => tracing::warn!("failed to refresh org cache: {err}"),
+                }
+            }
+        }
     }
 }
 
@@ -1,25 +1,34 @@
 use async_trait::async_trait;
 use chrono::{TimeZone, Utc};
-use file_store::iot_packet::PacketRouterPacketReport;
+use file_store::{iot_org_lock::IotOrgLockEvent, iot_packet::PacketRouterPacketReport};
 use futures::{Stream, StreamExt};
 use futures_util::stream;
-use helium_crypto::PublicKeyBinary;
+use helium_crypto::{KeyTag, Keypair, PublicKeyBinary, Sign};
 use helium_proto::{
-    services::packet_verifier::{InvalidPacket, InvalidPacketReason, ValidPacket},
-    DataRate, Region,
+    services::{
+        iot_config::{OrgResV1, OrgV1},
+        packet_verifier::{InvalidPacket, InvalidPacketReason, ValidPacket},
+    },
+    DataRate, Message, Region,
 };
 use iot_packet_verifier::{
     balances::BalanceCache,
     burner::Burner,
+    org_locks::OrgLocks,
     pending_burns::{Burn, PendingBurns},
-    verifier::{payload_size_to_dc, ConfigServer, Debiter, Org, Verifier, BYTES_PER_DC},
+    verifier::{
+        payload_size_to_dc, verified_org, ConfigServer, Debiter, Org, OrgClientError, PacketCopies,
+        Verifier, BYTES_PER_DC, INVALID_PACKET_REASON_EXCESS_COPIES,
+    },
 };
+use rand::rngs::OsRng;
 use std::{collections::HashMap, pin::Pin, sync::Arc, time::Duration};
 use tokio::sync::Mutex;
 
 struct MockConfig {
     payer: PublicKeyBinary,
     enabled: bool,
+    max_copies: u32,
 }
 
 #[derive(Default, Clone)]
@@ -29,11 +38,16 @@ struct MockConfigServer {
 
 impl MockConfigServer {
     async fn insert(&self, oui: u64, payer: PublicKeyBinary) {
+        self.insert_with_max_copies(oui, payer, 1).await;
+    }
+
+    async fn insert_with_max_copies(&self, oui: u64, payer: PublicKeyBinary, max_copies: u32) {
         self.payers.lock().await.insert(
             oui,
             MockConfig {
                 payer,
                 enabled: true,
+                max_copies,
             },
         );
     }
@@ -43,14 +57,14 @@ impl MockConfigServer {
 impl ConfigServer for MockConfigServer {
     type Error = ();
 
-    async fn fetch_org(
-        &self,
-        oui: u64,
-        _cache: &mut HashMap<u64, PublicKeyBinary>,
-    ) -> Result<PublicKeyBinary, ()> {
+    async fn fetch_org(&self, oui: u64) -> Result<PublicKeyBinary, ()> {
         Ok(self.payers.lock().await.get(&oui).unwrap().payer.clone())
     }
 
+    async fn fetch_max_copies(&self, oui: u64, _cache: &mut HashMap<u64, u32>) -> Result<u32, ()> {
+        Ok(self.payers.lock().await.get(&oui).unwrap().max_copies)
+    }
+
     async fn disable_org(&self, oui: u64) -> Result<(), ()> {
         self.payers.lock().await.get_mut(&oui).unwrap().enabled = false;
         Ok(())
@@ -134,6 +148,16 @@ fn packet_report(
     timestamp: u64,
     payload_size: u32,
     payload_hash: Vec<u8>,
+) -> PacketRouterPacketReport {
+    gateway_packet_report(oui, timestamp, payload_size, payload_hash, vec![])
+}
+
+fn gateway_packet_report(
+    oui: u64,
+    timestamp: u64,
+    payload_size: u32,
+    payload_hash: Vec<u8>,
+    gateway: Vec<u8>,
 ) -> PacketRouterPacketReport {
     PacketRouterPacketReport {
         received_timestamp: Utc.timestamp_opt(timestamp as i64, 0).unwrap(),
@@ -144,7 +168,7 @@ fn packet_report(
         snr: 0.0,
         data_rate: DataRate::Fsk50,
         region: Region::As9231,
-        gateway: PublicKeyBinary::from(vec![]),
+        gateway: PublicKeyBinary::from(gateway),
         payload_hash,
         payload_size,
     }
@@ -183,16 +207,20 @@ async fn test_config_unlocking() {
     cache.insert(PublicKeyBinary::from(vec![0]), 3);
     let cache = Arc::new(Mutex::new(cache));
     let balances = InstantBurnedBalance(cache.clone());
+    // Set up org locks:
+    let org_locks = OrgLocks::new(1, 0);
+    let org_lock_log: Arc<Mutex<Vec<IotOrgLockEvent>>> = Arc::default();
     // Set up verifier:
     let mut verifier = Verifier {
         debiter: balances.clone(),
         config_server: orgs.clone(),
+        packet_copies: PacketCopies::new(chrono::Duration::seconds(60)),
+        org_locks: org_locks.clone(),
     };
     let mut valid_packets = Vec::new();
     let mut invalid_packets = Vec::new();
     verifier
         .verify(
-            1,
             balances.clone(),
             stream::iter(vec![
                 packet_report(0, 0, 24, vec![1]),
@@ -201,6 +229,7 @@ async fn test_config_unlocking() {
             ]),
             &mut valid_packets,
             &mut invalid_packets,
+            org_lock_log.clone(),
         )
         .await
         .unwrap();
@@ -221,12 +250,15 @@ async fn test_config_unlocking() {
     let solana = solana_network.clone();
     let balance_cache = cache.clone();
     let orgs_clone = orgs.clone();
+    let org_locks_clone = org_locks.clone();
+    let org_lock_log_clone = org_lock_log.clone();
     tokio::spawn(async move {
         orgs_clone
             .monitor_funds(
                 solana.clone(),
                 balance_cache,
-                1,
+                org_locks_clone,
+                org_lock_log_clone,
                 Duration::from_secs(100),
                 listener,
             )
@@ -237,6 +269,15 @@ async fn test_config_unlocking() {
 
     // We should be re-enabled
     assert!(orgs.payers.lock().await.get(&0).unwrap().enabled);
+    assert_eq!(
+        org_lock_log
+            .lock()
+            .await
+            .iter()
+            .map(|event| (event.locked, event.balance))
+            .collect::<Vec<_>>(),
+        vec![(true, 0), (false, 50)]
+    );
     assert_eq!(
         *cache
             .lock()
@@ -250,15 +291,15 @@ async fn test_config_unlocking() {
 
     verifier
         .verify(
-            1,
             balances.clone(),
             stream::iter(vec![
-                packet_report(0, 0, 24, vec![1]),
-                packet_report(0, 1, 48, vec![2]),
-                packet_report(0, 2, 1, vec![3]),
+                packet_report(0, 3, 24, vec![4]),
+                packet_report(0, 4, 48, vec![5]),
+                packet_report(0, 5, 1, vec![6]),
             ]),
             &mut valid_packets,
             &mut invalid_packets,
+            org_lock_log.clone(),
         )
         .await
         .unwrap();
@@ -303,20 +344,25 @@ async fn test_verifier() {
     // Set up output:
     let mut valid_packets = Vec::new();
     let mut invalid_packets = Vec::new();
+    // Set up org locks:
+    let org_locks = OrgLocks::new(1, 0);
+    let org_lock_log: Arc<Mutex<Vec<IotOrgLockEvent>>> = Arc::default();
     // Set up verifier:
     let mut verifier = Verifier {
         debiter: balances.clone(),
         config_server: orgs,
+        packet_copies: PacketCopies::new(chrono::Duration::seconds(60)),
+        org_locks: org_locks.clone(),
     };
 
     // Run the verifier:
     verifier
         .verify(
-            1,
             balances.clone(),
             stream::iter(packets),
             &mut valid_packets,
             &mut invalid_packets,
+            org_lock_log.clone(),
         )
         .await
         .unwrap();
@@ -380,16 +426,20 @@ async fn test_end_to_end() {
     let mut valid_packets = Vec::new();
     let mut invalid_packets = Vec::new();
 
+    // Set up org locks:
+    let org_locks = OrgLocks::new(1, 0);
+    let org_lock_log: Arc<Mutex<Vec<IotOrgLockEvent>>> = Arc::default();
     // Set up verifier:
     let mut verifier = Verifier {
         debiter: balance_cache,
         config_server: orgs,
+        packet_copies: PacketCopies::new(chrono::Duration::seconds(60)),
+        org_locks: org_locks.clone(),
     };
 
     // Verify four packets, each costing one DC. The last one should be invalid
     verifier
         .verify(
-            1,
             pending_burns.clone(),
             stream::iter(vec![
                 packet_report(0, 0, BYTES_PER_DC as u32, vec![1]),
@@ -399,6 +449,7 @@ async fn test_end_to_end() {
             ]),
             &mut valid_packets,
             &mut invalid_packets,
+            org_lock_log.clone(),
         )
         .await
         .unwrap();
@@ -469,11 +520,11 @@ async fn test_end_to_end() {
 
     verifier
         .verify(
-            1,
             pending_burns.clone(),
             stream::iter(vec![packet_report(0, 4, BYTES_PER_DC as u32, vec![5])]),
             &mut valid_packets,
             &mut invalid_packets,
+            org_lock_log.clone(),
         )
         .await
         .unwrap();
@@ -495,7 +546,6 @@ async fn test_end_to_end() {
     // should clear
     verifier
         .verify(
-            1,
             pending_burns.clone(),
             stream::iter(vec![
                 packet_report(0, 5, 2 * BYTES_PER_DC as u32, vec![6]),
@@ -503,6 +553,7 @@ async fn test_end_to_end() {
             ]),
             &mut valid_packets,
             &mut invalid_packets,
+            org_lock_log.clone(),
         )
         .await
         .unwrap();
@@ -524,3 +575,272 @@ async fn test_end_to_end() {
     assert_eq!(balance.balance, 1);
     assert_eq!(balance.burned, 1);
 }
+
+#[tokio::test]
+async fn test_excess_copies() {
+    let copy = |timestamp, gateway| gateway_packet_report(0, timestamp, 24, vec![1], vec![gateway]);
+    // Set up orgs:
+    let orgs = MockConfigServer::default();
+    orgs.insert_with_max_copies(0_u64, PublicKeyBinary::from(vec![0]), 2)
+        .await;
+    // Set up balances:
+    let mut balances = HashMap::new();
+    balances.insert(PublicKeyBinary::from(vec![0]), 10);
+    let balances = InstantBurnedBalance(Arc::new(Mutex::new(balances)));
+    // Set up output:
+    let mut valid_packets = Vec::new();
+    let mut invalid_packets = Vec::new();
+    // Set up org locks:
+    let org_locks = OrgLocks::new(1, 0);
+    let org_lock_log: Arc<Mutex<Vec<IotOrgLockEvent>>> = Arc::default();
+    // Set up verifier:
+    let mut verifier = Verifier {
+        debiter: balances.clone(),
+        config_server: orgs,
+        packet_copies: PacketCopies::new(chrono::Duration::seconds(60)),
+        org_locks: org_locks.clone(),
+    };
+
+    // Three copies of the packet in one file and a fourth in the next:
+    verifier
+        .verify(
+            balances.clone(),
+            stream::iter(vec![copy(0, 1), copy(1, 2), copy(2, 3)]),
+            &mut valid_packets,
+            &mut invalid_packets,
+            org_lock_log.clone(),
+        )
+        .await
+        .unwrap();
+    verifier
+        .verify(
+            balances.clone(),
+            stream::iter(vec![copy(30, 4)]),
+            &mut valid_packets,
+            &mut invalid_packets,
+            org_lock_log.clone(),
+        )
+        .await
+        .unwrap();
+
+    // Only the first two copies are charged for:
+    assert_eq!(valid_packets.len(), 2);
+    assert_eq!(
+        valid_packets
+            .iter()
+            .map(|packet| packet.gateway.clone())
+            .collect::<Vec<_>>(),
+        vec![vec![1], vec![2]]
+    );
+    assert_eq!(
+        invalid_packets
+            .iter()
+            .map(|packet| (packet.gateway.clone(), packet.reason))
+            .collect::<Vec<_>>(),
+        vec![
+            (vec![3], INVALID_PACKET_REASON_EXCESS_COPIES),
+            (vec![4], INVALID_PACKET_REASON_EXCESS_COPIES),
+        ]
+    );
+    assert_eq!(
+        *balances
+            .0
+            .lock()
+            .await
+            .get(&PublicKeyBinary::from(vec![0]))
+            .unwrap(),
+        8
+    );
+
+    // The same payload after the window is a new packet:
+    valid_packets.clear();
+    verifier
+        .verify(
+            balances.clone(),
+            stream::iter(vec![copy(120, 5)]),
+            &mut valid_packets,
+            &mut invalid_packets,
+            org_lock_log.clone(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(valid_packets.len(), 1);
+}
+
+#[tokio::test]
+async fn test_repeat_reports_are_not_copies() {
+    let copy = |timestamp, gateway| gateway_packet_report(0, timestamp, 24, vec![1], vec![gateway]);
+    // Set up orgs:
+    let orgs = MockConfigServer::default();
+    orgs.insert_with_max_copies(0_u64, PublicKeyBinary::from(vec![0]), 2)
+        .await;
+    // Set up balances:
+    let mut balances = HashMap::new();
+    balances.insert(PublicKeyBinary::from(vec![0]), 10);
+    let balances = InstantBurnedBalance(Arc::new(Mutex::new(balances)));
+    // Set up output:
+    let mut valid_packets = Vec::new();
+    let mut invalid_packets = Vec::new();
+    // Set up verifier:
+    let mut verifier = Verifier {
+        debiter: balances.clone(),
+        config_server: orgs,
+        packet_copies: PacketCopies::new(chrono::Duration::seconds(60)),
+        org_locks: OrgLocks::new(1, 0),
+    };
+
+    // The first gateway reports the packet three times before a second one:
+    verifier
+        .verify(
+            balances.clone(),
+            stream::iter(vec![copy(0, 1), copy(1, 1), copy(2, 1), copy(3, 2)]),
+            &mut valid_packets,
+            &mut invalid_packets,
+            Arc::<Mutex<Vec<IotOrgLockEvent>>>::default(),
+        )
+        .await
+        .unwrap();
+
+    // Both gateways are within the max copies:
+    assert!(invalid_packets.is_empty());
+    assert_eq!(
+        valid_packets
+            .iter()
+            .map(|packet| packet.gateway.clone())
+            .collect::<Vec<_>>(),
+        vec![vec![1], vec![1], vec![1], vec![2]]
+    );
+}
+
+#[tokio::test]
+async fn test_org_lock_hysteresis() {
+    let payer = PublicKeyBinary::from(vec![0]);
+    // Set up orgs:
+    let orgs = MockConfigServer::default();
+    orgs.insert(0_u64, payer.clone()).await;
+    // Set up balances:
+    let mut balances = HashMap::new();
+    balances.insert(payer.clone(), 10);
+    let balances = InstantBurnedBalance(Arc::new(Mutex::new(balances)));
+    // Set up org locks, unlocking 3 DC above the minimum of 5 DC:
+    let org_locks = OrgLocks::new(5, 3);
+    let org_lock_log: Arc<Mutex<Vec<IotOrgLockEvent>>> = Arc::default();
+    // Set up verifier:
+    let mut verifier = Verifier {
+        debiter: balances.clone(),
+        config_server: orgs.clone(),
+        packet_copies: PacketCopies::new(chrono::Duration::seconds(60)),
+        org_locks: org_locks.clone(),
+    };
+    let mut valid_packets = Vec::new();
+    let mut invalid_packets = Vec::new();
+
+    // Spend the balance down to 3 DC, below the minimum from 4 DC on:
+    verifier
+        .verify(
+            balances.clone(),
+            stream::iter((0..7).map(|i| packet_report(0, i, 24, vec![i as u8]))),
+            &mut valid_packets,
+            &mut invalid_packets,
+            org_lock_log.clone(),
+        )
+        .await
+        .unwrap();
+
+    // The org is locked once:
+    assert!(!orgs.payers.lock().await.get(&0).unwrap().enabled);
+    assert_eq!(
+        org_lock_log
+            .lock()
+            .await
+            .iter()
+            .map(|event| (event.locked, event.balance, event.threshold))
+            .collect::<Vec<_>>(),
+        vec![(true, 4, 5)]
+    );
+
+    // and stays locked until the balance reaches the margin above the minimum:
+    assert!(org_locks.unlock_transition(0, &payer, 7).await.is_none());
+    let unlocked = org_locks.unlock_transition(0, &payer, 8).await.unwrap();
+    assert!(!unlocked.locked);
+    assert_eq!(unlocked.threshold, 8);
+    // and is unlocked once the transition is committed:
+    assert!(org_locks.unlock_transition(0, &payer, 8).await.is_some());
+    org_locks.commit(&unlocked).await;
+    assert!(org_locks.unlock_transition(0, &payer, 8).await.is_none());
+}
+
+#[tokio::test]
+async fn test_org_lock_reconciliation() {
+    let payer = PublicKeyBinary::from(vec![0]);
+    // Set up orgs:
+    let orgs = MockConfigServer::default();
+    orgs.insert(0_u64, payer.clone()).await;
+    // Set up balances, short of the unlock threshold:
+    let mut solana_network = HashMap::new();
+    solana_network.insert(payer.clone(), 3);
+    let solana_network = Arc::new(Mutex::new(solana_network));
+    let cache: Arc<Mutex<HashMap<PublicKeyBinary, u64>>> = Arc::default();
+    // Set up org locks, with the org locked by the verifier:
+    let org_locks = OrgLocks::new(5, 0);
+    let locked = org_locks.lock_transition(0, &payer, 3).await.unwrap();
+    org_locks.commit(&locked).await;
+    let org_lock_log: Arc<Mutex<Vec<IotOrgLockEvent>>> = Arc::default();
+
+    // The org remains enabled on the config server:
+    assert!(orgs.payers.lock().await.get(&0).unwrap().enabled);
+
+    let (trigger, listener) = triggered::trigger();
+    let orgs_clone = orgs.clone();
+    let org_lock_log_clone = org_lock_log.clone();
+    tokio::spawn(async move {
+        orgs_clone
+            .monitor_funds(
+                solana_network,
+                cache,
+                org_locks,
+                org_lock_log_clone,
+                Duration::from_secs(100),
+                listener,
+            )
+            .await
+    });
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    // Monitoring funds disables the org again, without a new transition:
+    assert!(!orgs.payers.lock().await.get(&0).unwrap().enabled);
+    assert!(org_lock_log.lock().await.is_empty());
+
+    trigger.trigger();
+}
+
+#[test]
+fn test_org_responses_are_verified() {
+    let config_key = Keypair::generate(KeyTag::default(), &mut OsRng);
+    let payer = PublicKeyBinary::from(vec![0]);
+    let response_signed_by = |keypair: &Keypair| {
+        let mut response = OrgResV1 {
+            org: Some(OrgV1 {
+                oui: 1,
+                payer: payer.clone().into(),
+                ..Default::default()
+            }),
+            timestamp: Utc::now().timestamp() as u64,
+            signer: keypair.public_key().into(),
+            ..Default::default()
+        };
+        response.signature = keypair.sign(&response.encode_to_vec()).unwrap();
+        response
+    };
+
+    let org = verified_org(response_signed_by(&config_key), config_key.public_key()).unwrap();
+    assert_eq!(org.payer, payer);
+
+    // A response not signed by the config server is rejected:
+    let other_key = Keypair::generate(KeyTag::default(), &mut OsRng);
+    assert!(matches!(
+        verified_org(response_signed_by(&other_key), config_key.public_key()),
+        Err(OrgClientError::VerificationError(_))
+    ));
+}
@@ -0,0 +1,11 @@
+-- daily count of valid witnesses of a beaconer's pocs by each witness,
+-- the graph over which witness rings are detected
+create table witness_edges (
+    beaconer text not null,
+    witness text not null,
+    day date not null,
+    count bigint not null default 0,
+    primary key(beaconer, witness, day)
+);
+
+create index idx_witness_edges_day on witness_edges (day);
@@ -0,0 +1,20 @@
+-- outcome of the most recent beacons and witnesses of each hotspot, served
+-- by the history api and pruned by the purger
+create table hotspot_history (
+    -- id of the beacon or witness report
+    id bytea primary key not null,
+    hotspot_key text not null,
+    report_type reporttype not null,
+    received_timestamp timestamptz not null,
+    status text not null,
+    invalid_reason text not null,
+    participant_side text not null,
+    -- whether a valid witness was selected for rewards
+    selected boolean not null default false,
+    hex_scale decimal,
+    reward_unit decimal
+);
+
+create index idx_hotspot_history_hotspot_key on hotspot_history (hotspot_key, report_type, received_timestamp desc);
+
+create index idx_hotspot_history_received_timestamp on hotspot_history (received_timestamp);
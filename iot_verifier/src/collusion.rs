@@ -0,0 +1,416 @@
+//! Detection of witness rings: groups of gateways which predominantly witness
+//! each other's beacons. Every valid witness of a verified poc is recorded as a
+//! daily count against the beaconer and witness pair, and periodically the
+//! graph of those counts over a rolling window is searched for clusters of
+//! gateways linked by reciprocal witnessing. Clusters are ranked by how
+//! reciprocal and exclusive their witnessing is and written out as suspicious
+//! cluster reports for review.
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use file_store::{
+    file_sink::FileSinkClient,
+    iot_suspicious_cluster::{IotSuspiciousCluster, IotSuspiciousClusterV1},
+    iot_valid_poc::IotPoc,
+};
+use helium_crypto::PublicKeyBinary;
+use helium_proto::services::poc_lora::VerificationStatus;
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use tokio::time::{self, MissedTickBehavior};
+
+/// Count of valid witnesses within the window keyed by (beaconer, witness)
+pub type WitnessEdges = HashMap<(PublicKeyBinary, PublicKeyBinary), u64>;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CollusionSettings {
+    /// How often to look for witness rings (in seconds). Default 6 hours
+    #[serde(default = "default_detection_interval")]
+    pub detection_interval: i64,
+    /// Number of days of verified pocs examined. Default 7
+    #[serde(default = "default_window_days")]
+    pub window_days: i64,
+    /// Min valid witnesses in each direction for a pair of gateways to be
+    /// linked. Default 5
+    #[serde(default = "default_min_pair_witnesses")]
+    pub min_pair_witnesses: u64,
+    /// Min number of gateways in a reported cluster. Default 3
+    #[serde(default = "default_min_cluster_size")]
+    pub min_cluster_size: usize,
+    /// Min share of the witnessing of a cluster's members which is between
+    /// members for the cluster to be reported. Default 0.5
+    #[serde(default = "default_min_exclusivity")]
+    pub min_exclusivity: f64,
+    /// Max clusters reported per run, the highest scoring are kept.
+    /// Default 100
+    #[serde(default = "default_max_reported_clusters")]
+    pub max_reported_clusters: usize,
+}
+
+fn default_detection_interval() -> i64 {
+    6 * 60 * 60
+}
+
+fn default_window_days() -> i64 {
+    7
+}
+
+fn default_min_pair_witnesses() -> u64 {
+    5
+}
+
+fn default_min_cluster_size() -> usize {
+    3
+}
+
+fn default_min_exclusivity() -> f64 {
+    0.5
+}
+
+fn default_max_reported_clusters() -> usize {
+    100
+}
+
+impl Default for CollusionSettings {
+    fn default() -> Self {
+        Self {
+            detection_interval: default_detection_interval(),
+            window_days: default_window_days(),
+            min_pair_witnesses: default_min_pair_witnesses(),
+            min_cluster_size: default_min_cluster_size(),
+            min_exclusivity: default_min_exclusivity(),
+            max_reported_clusters: default_max_reported_clusters(),
+        }
+    }
+}
+
+impl CollusionSettings {
+    pub fn detection_interval(&self) -> time::Duration {
+        time::Duration::from_secs(self.detection_interval as u64)
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::days(self.window_days)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cluster {
+    pub members: Vec<PublicKeyBinary>,
+    /// Mean over the linked pairs of the cluster of the ratio of the lesser
+    /// to the greater of their witness counts of each other
+    pub reciprocity: f64,
+    /// Share of the witnessing involving members which is between members
+    pub exclusivity: f64,
+    pub internal_witnesses: u64,
+    pub total_witnesses: u64,
+    /// reciprocity * exclusivity * ln(size), higher is more suspicious
+    pub score: f64,
+}
+
+/// Record the valid witnesses of a verified poc against the beaconer
+pub async fn record_witness_edges(
+    txn: &mut Transaction<'_, Postgres>,
+    iot_poc: &IotPoc,
+) -> Result<(), sqlx::Error> {
+    let beacon_report = &iot_poc.beacon_report;
+    let day = beacon_report.received_timestamp.date_naive();
+    // a gateway may witness a beacon more than once, and a row can only be
+    // updated once per insert
+    let mut counts: HashMap<&PublicKeyBinary, i64> = HashMap::new();
+    for witness in iot_poc
+        .selected_witnesses
+        .iter()
+        .chain(iot_poc.unselected_witnesses.iter())
+        .filter(|witness| witness.status == VerificationStatus::Valid)
+    {
+        *counts.entry(&witness.report.pub_key).or_default() += 1;
+    }
+    if counts.is_empty() {
+        return Ok(());
+    }
+    let mut query_builder: sqlx::QueryBuilder<sqlx::Postgres> =
+        sqlx::QueryBuilder::new(" insert into witness_edges (beaconer, witness, day, count) ");
+    query_builder.push_values(counts, |mut b, (witness, count)| {
+        b.push_bind(&beacon_report.report.pub_key)
+            .push_bind(witness)
+            .push_bind(day)
+            .push_bind(count);
+    });
+    query_builder.push(
+        " on conflict (beaconer, witness, day) do update set count = witness_edges.count + excluded.count ",
+    );
+    query_builder.build().execute(&mut *txn).await.map(|_| ())
+}
+
+pub async fn witness_edges(pool: &PgPool, since: NaiveDate) -> Result<WitnessEdges, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (PublicKeyBinary, PublicKeyBinary, i64)>(
+        r#"
+        select beaconer, witness, sum(count)::bigint from witness_edges
+        where day >= $1
+        group by beaconer, witness
+        "#,
+    )
+    .bind(since)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(beaconer, witness, count)| ((beaconer, witness), count as u64))
+        .collect())
+}
+
+pub async fn purge_witness_edges(pool: &PgPool, before: NaiveDate) -> Result<u64, sqlx::Error> {
+    Ok(sqlx::query("delete from witness_edges where day < $1")
+        .bind(before)
+        .execute(pool)
+        .await?
+        .rows_affected())
+}
+
+/// Group gateways into clusters linked by reciprocal witnessing and return
+/// those which meet the configured size and exclusivity, most suspicious
+/// first
+pub fn find_clusters(edges: &WitnessEdges, settings: &CollusionSettings) -> Vec<Cluster> {
+    let mut index: HashMap<&PublicKeyBinary, usize> = HashMap::new();
+    let mut gateways: Vec<&PublicKeyBinary> = Vec::new();
+    for (beaconer, witness) in edges.keys() {
+        for gateway in [beaconer, witness] {
+            index.entry(gateway).or_insert_with(|| {
+                gateways.push(gateway);
+                gateways.len() - 1
+            });
+        }
+    }
+
+    let mut sets = DisjointSets::new(gateways.len());
+    let mut links = Vec::new();
+    for ((beaconer, witness), &count) in edges {
+        let (from, to) = (index[beaconer], index[witness]);
+        // each pair is visited from both directions, only link it once
+        if from >= to || count < settings.min_pair_witnesses {
+            continue;
+        }
+        let Some(&reverse) = edges.get(&(witness.clone(), beaconer.clone())) else {
+            continue;
+        };
+        if reverse < settings.min_pair_witnesses {
+            continue;
+        }
+        sets.union(from, to);
+        links.push((from, count.min(reverse) as f64 / count.max(reverse) as f64));
+    }
+
+    let mut internal = vec![0_u64; gateways.len()];
+    let mut total = vec![0_u64; gateways.len()];
+    for ((beaconer, witness), &count) in edges {
+        let from = sets.find(index[beaconer]);
+        let to = sets.find(index[witness]);
+        total[from] += count;
+        if from == to {
+            internal[from] += count;
+        } else {
+            total[to] += count;
+        }
+    }
+
+    let mut reciprocity: HashMap<usize, (f64, usize)> = HashMap::new();
+    for (gateway, ratio) in links {
+        let entry = reciprocity.entry(sets.find(gateway)).or_default();
+        entry.0 += ratio;
+        entry.1 += 1;
+    }
+
+    let mut members: HashMap<usize, Vec<PublicKeyBinary>> = HashMap::new();
+    for (gateway, key) in gateways.iter().enumerate() {
+        members
+            .entry(sets.find(gateway))
+            .or_default()
+            .push((*key).clone());
+    }
+
+    let mut clusters: Vec<Cluster> = members
+        .into_iter()
+        .filter(|(_, members)| members.len() >= settings.min_cluster_size.max(2))
+        .filter_map(|(root, mut members)| {
+            let (ratio_sum, link_count) = reciprocity.get(&root)?;
+            let reciprocity = ratio_sum / *link_count as f64;
+            let exclusivity = internal[root] as f64 / total[root] as f64;
+            if exclusivity < settings.min_exclusivity {
+                return None;
+            }
+            members.sort_by_cached_key(|member| member.to_string());
+            Some(Cluster {
+                score: reciprocity * exclusivity * (members.len() as f64).ln(),
+                members,
+                reciprocity,
+                exclusivity,
+                internal_witnesses: internal[root],
+                total_witnesses: total[root],
+            })
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.score.total_cmp(&a.score));
+    clusters.truncate(settings.max_reported_clusters);
+    clusters
+}
+
+struct DisjointSets {
+    parents: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(size: usize) -> Self {
+        Self {
+            parents: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, mut item: usize) -> usize {
+        while self.parents[item] != item {
+            self.parents[item] = self.parents[self.parents[item]];
+            item = self.parents[item];
+        }
+        item
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parents[b] = a;
+        }
+    }
+}
+
+pub struct CollusionDetector {
+    pub pool: PgPool,
+    pub settings: CollusionSettings,
+    pub cluster_sink: FileSinkClient,
+}
+
+impl CollusionDetector {
+    pub async fn run(&self, shutdown: &triggered::Listener) -> anyhow::Result<()> {
+        tracing::info!("starting collusion detector");
+
+        let mut detection_timer = time::interval(self.settings.detection_interval());
+        detection_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            if shutdown.is_triggered() {
+                break;
+            }
+            tokio::select! {
+                _ = shutdown.clone() => break,
+                _ = detection_timer.tick() => match self.handle_tick(Utc::now()).await {
+                    Ok(()) => (),
+                    Err(err) => {
+                        tracing::error!("collusion detection failed: {err:?}");
+                    }
+                }
+            }
+        }
+        tracing::info!("stopping collusion detector");
+        Ok(())
+    }
+
+    async fn handle_tick(&self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        let window_start = now - self.settings.window();
+        let since = window_start.date_naive();
+        let edges = witness_edges(&self.pool, since).await?;
+        let clusters = find_clusters(&edges, &self.settings);
+        tracing::info!(
+            "found {} suspicious clusters over {} witness edges",
+            clusters.len(),
+            edges.len()
+        );
+        for (rank, cluster) in clusters.into_iter().enumerate() {
+            let report = IotSuspiciousCluster {
+                timestamp: now,
+                window_start,
+                window_end: now,
+                rank: rank as u32 + 1,
+                score: cluster.score,
+                reciprocity: cluster.reciprocity,
+                exclusivity: cluster.exclusivity,
+                internal_witnesses: cluster.internal_witnesses,
+                total_witnesses: cluster.total_witnesses,
+                members: cluster.members,
+            };
+            self.cluster_sink
+                .write(IotSuspiciousClusterV1::from(report), [])
+                .await?;
+        }
+        self.cluster_sink.commit().await?;
+        let purged = purge_witness_edges(&self.pool, since).await?;
+        tracing::info!("purged {purged} witness edges older than {since}");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gateway(id: u8) -> PublicKeyBinary {
+        PublicKeyBinary::from(vec![id])
+    }
+
+    fn edges(counts: &[(u8, u8, u64)]) -> WitnessEdges {
+        counts
+            .iter()
+            .map(|(beaconer, witness, count)| ((gateway(*beaconer), gateway(*witness)), *count))
+            .collect()
+    }
+
+    #[test]
+    fn finds_reciprocal_exclusive_rings() {
+        let edges = edges(&[
+            // a ring of three gateways only witnessing each other
+            (1, 2, 20),
+            (2, 1, 20),
+            (2, 3, 10),
+            (3, 2, 20),
+            (1, 3, 20),
+            (3, 1, 20),
+            // a busy neighbourhood of gateways which also witness each other
+            // but mostly witness others
+            (4, 5, 10),
+            (5, 4, 10),
+            (5, 6, 10),
+            (6, 5, 10),
+            (4, 7, 40),
+            (5, 7, 40),
+            (6, 7, 40),
+            // a pair which falls short of the cluster size
+            (9, 10, 20),
+            (10, 9, 20),
+            // one sided witnessing links nothing
+            (11, 12, 50),
+            (12, 13, 50),
+            (13, 11, 50),
+        ]);
+        let settings = CollusionSettings::default();
+
+        let clusters = find_clusters(&edges, &settings);
+        assert_eq!(1, clusters.len());
+        let ring = &clusters[0];
+        let mut expected = vec![gateway(1), gateway(2), gateway(3)];
+        expected.sort_by_cached_key(|member| member.to_string());
+        assert_eq!(expected, ring.members);
+        assert_eq!(110, ring.internal_witnesses);
+        assert_eq!(110, ring.total_witnesses);
+        assert_eq!(1.0, ring.exclusivity);
+        assert!((ring.reciprocity - 5.0 / 6.0).abs() < f64::EPSILON);
+
+        // relaxing exclusivity reports the neighbourhood, ranked below the ring
+        let settings = CollusionSettings {
+            min_exclusivity: 0.0,
+            ..settings
+        };
+        let clusters = find_clusters(&edges, &settings);
+        assert_eq!(2, clusters.len());
+        assert_eq!(ring, &clusters[0]);
+        assert_eq!(3, clusters[1].members.len());
+        assert_eq!(40, clusters[1].internal_witnesses);
+        assert_eq!(160, clusters[1].total_witnesses);
+    }
+}
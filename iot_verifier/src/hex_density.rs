@@ -1,10 +1,14 @@
-use file_store::SCALING_PRECISION;
+use file_store::{iot_hex_density::IotHexDensityRes, SCALING_PRECISION};
 use h3o::{CellIndex, Resolution};
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use std::{cmp, collections::HashMap, sync::Arc};
+use std::{
+    cmp,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 use tokio::sync::RwLock;
 
 pub struct HexResConfig {
@@ -26,6 +30,14 @@ impl HexResConfig {
 type HexMap = HashMap<CellIndex, u64>;
 
 const MAX_RES: Resolution = Resolution::Eleven;
+// Resolution at which active gateway locations are grouped into regions for
+// incremental updates of the density map
+const REGION_RES: Resolution = Resolution::Four;
+// Grid distance, at the region resolution, within which the gateways of two
+// regions can affect each other's clipping. The k-ring of a parent at any of
+// the used resolutions lies under its own or a neighbouring region, the second
+// ring allows for the distortion of the h3 hierarchy
+const REGION_REACH: u32 = 2;
 const USED_RES: [Resolution; 7] = [
     Resolution::Ten,
     Resolution::Nine,
@@ -68,6 +80,7 @@ lazy_static! {
 pub trait HexDensityMap: Clone {
     async fn get(&self, hex: u64) -> Option<Decimal>;
     async fn swap(&self, new_map: HashMap<u64, Decimal>);
+    async fn update(&self, removed: &[u64], updated: HashMap<u64, Decimal>);
 }
 
 #[derive(Debug, Clone)]
@@ -88,12 +101,21 @@ impl HexDensityMap for SharedHexDensityMap {
     async fn swap(&self, new_map: HashMap<u64, Decimal>) {
         *self.0.write().await = new_map;
     }
+
+    async fn update(&self, removed: &[u64], updated: HashMap<u64, Decimal>) {
+        let mut map = self.0.write().await;
+        for hex in removed {
+            map.remove(hex);
+        }
+        map.extend(updated);
+    }
 }
 
 #[derive(Debug)]
 pub struct GlobalHexMap {
     clipped_hexes: HexMap,
     unclipped_hexes: HexMap,
+    occupied_hexes: HexMap,
     asserted_hexes: Vec<CellIndex>,
 }
 
@@ -102,6 +124,7 @@ impl GlobalHexMap {
         Self {
             clipped_hexes: HashMap::new(),
             unclipped_hexes: HashMap::new(),
+            occupied_hexes: HashMap::new(),
             asserted_hexes: Vec::new(),
         }
     }
@@ -130,6 +153,7 @@ impl GlobalHexMap {
         reduce_hex_res(
             &mut self.unclipped_hexes,
             &mut self.clipped_hexes,
+            &mut self.occupied_hexes,
             starting_hexes,
         )
     }
@@ -156,7 +180,12 @@ fn rollup_child_count(
         .or_insert(cell_count);
 }
 
-fn reduce_hex_res(unclipped: &mut HexMap, clipped: &mut HexMap, hex_list: Vec<CellIndex>) {
+fn reduce_hex_res(
+    unclipped: &mut HexMap,
+    clipped: &mut HexMap,
+    occupied: &mut HexMap,
+    hex_list: Vec<CellIndex>,
+) {
     let mut hexes_at_res: Vec<CellIndex> = hex_list;
     for res in USED_RES {
         std::mem::take(&mut hexes_at_res)
@@ -174,6 +203,7 @@ fn reduce_hex_res(unclipped: &mut HexMap, clipped: &mut HexMap, hex_list: Vec<Ce
             .map(|parent_cell| {
                 let occupied_count = occupied_count(clipped, &parent_cell, density_tgt);
                 let limit = limit(&res, occupied_count);
+                occupied.insert(parent_cell, occupied_count);
                 if let Some(count) = unclipped.get(&parent_cell) {
                     let actual = cmp::min(limit, *count);
                     clipped.insert(parent_cell, actual);
@@ -204,9 +234,18 @@ fn limit(res: &h3o::Resolution, occupied_count: u64) -> u64 {
     cmp::min(res_config.max, res_config.target * max)
 }
 
-pub fn compute_hex_density_map(global_map: &GlobalHexMap) -> HashMap<u64, Decimal> {
-    let mut map: HashMap<u64, Decimal> = HashMap::new();
+/// The transmit scale of an asserted hex together with the clipping of its
+/// parent at each of the used resolutions
+#[derive(Debug, Clone, PartialEq)]
+pub struct HexDensity {
+    pub scale: Decimal,
+    pub resolutions: Vec<IotHexDensityRes>,
+}
+
+pub fn compute_hex_densities(global_map: &GlobalHexMap) -> HashMap<u64, HexDensity> {
+    let mut map: HashMap<u64, HexDensity> = HashMap::new();
     for hex in &global_map.asserted_hexes {
+        let mut resolutions = Vec::new();
         let scale: Decimal = SCALING_RES.iter().fold(dec!(1.0), |scale, res| {
             hex.parent(*res).map_or(scale, |parent| {
                 match (
@@ -214,6 +253,16 @@ pub fn compute_hex_density_map(global_map: &GlobalHexMap) -> HashMap<u64, Decima
                     global_map.clipped_hexes.get(&parent),
                 ) {
                     (Some(unclipped), Some(clipped)) => {
+                        if let Some(occupied) = global_map.occupied_hexes.get(&parent) {
+                            resolutions.push(IotHexDensityRes {
+                                resolution: u8::from(*res),
+                                hex: u64::from(parent),
+                                unclipped: *unclipped,
+                                clipped: *clipped,
+                                occupied_neighbors: *occupied,
+                                limit: limit(res, *occupied),
+                            });
+                        }
                         scale
                             * (Decimal::new(*clipped as i64, SCALING_PRECISION)
                                 / Decimal::new(*unclipped as i64, SCALING_PRECISION))
@@ -223,11 +272,132 @@ pub fn compute_hex_density_map(global_map: &GlobalHexMap) -> HashMap<u64, Decima
             })
         });
         let trunc_scale = scale.round_dp(SCALING_PRECISION);
-        map.insert(u64::from(*hex), trunc_scale);
+        map.insert(
+            u64::from(*hex),
+            HexDensity {
+                scale: trunc_scale,
+                resolutions,
+            },
+        );
     }
     map
 }
 
+pub fn compute_hex_density_map(global_map: &GlobalHexMap) -> HashMap<u64, Decimal> {
+    compute_hex_densities(global_map)
+        .into_iter()
+        .map(|(hex, density)| (hex, density.scale))
+        .collect()
+}
+
+/// Hex densities maintained by recomputing only the regions affected by
+/// changes to the set of active gateway locations, rather than reducing the
+/// global map on every change
+#[derive(Debug, Default)]
+pub struct IncrementalHexDensity {
+    /// Asserted locations of active gateways, by region
+    locations: HashMap<CellIndex, Vec<u64>>,
+    /// Densities of the asserted hexes of each region as last computed
+    densities: HashMap<CellIndex, HashMap<u64, HexDensity>>,
+    /// Regions whose locations changed since they were last computed
+    changed: HashSet<CellIndex>,
+}
+
+#[derive(Debug, Default)]
+pub struct DensityUpdate {
+    /// Hexes no longer asserted by any active gateway
+    pub removed: Vec<u64>,
+    /// Recomputed densities, including those which came out unchanged
+    pub updated: HashMap<u64, HexDensity>,
+}
+
+impl IncrementalHexDensity {
+    pub fn insert(&mut self, location: u64) {
+        if let Some(region) = region(location) {
+            self.locations.entry(region).or_default().push(location);
+            self.changed.insert(region);
+        }
+    }
+
+    pub fn remove(&mut self, location: u64) {
+        let Some(region) = region(location) else {
+            return;
+        };
+        if let Some(locations) = self.locations.get_mut(&region) {
+            if let Some(position) = locations.iter().position(|l| *l == location) {
+                locations.swap_remove(position);
+                if locations.is_empty() {
+                    self.locations.remove(&region);
+                }
+                self.changed.insert(region);
+            }
+        }
+    }
+
+    pub fn has_changes(&self) -> bool {
+        !self.changed.is_empty()
+    }
+
+    pub fn densities(&self) -> impl Iterator<Item = (&u64, &HexDensity)> {
+        self.densities.values().flatten()
+    }
+
+    /// Recompute the densities of the changed regions along with every region
+    /// linked to them through a chain of regions within reach of each other,
+    /// beyond which a change can't alter the outcome of the clipping
+    pub fn update(&mut self) -> DensityUpdate {
+        let affected = self.affected_regions();
+        let mut global_map = GlobalHexMap::new();
+        for region in &affected {
+            for location in self.locations.get(region).into_iter().flatten() {
+                global_map.increment_unclipped(*location);
+            }
+        }
+        global_map.reduce_global();
+        let updated = compute_hex_densities(&global_map);
+
+        let mut removed = Vec::new();
+        for region in &affected {
+            if let Some(previous) = self.densities.remove(region) {
+                removed.extend(
+                    previous
+                        .into_keys()
+                        .filter(|hex| !updated.contains_key(hex)),
+                );
+            }
+        }
+        for (hex, density) in &updated {
+            if let Some(region) = region(*hex) {
+                self.densities
+                    .entry(region)
+                    .or_default()
+                    .insert(*hex, density.clone());
+            }
+        }
+        DensityUpdate { removed, updated }
+    }
+
+    fn affected_regions(&mut self) -> HashSet<CellIndex> {
+        let mut affected = HashSet::new();
+        let mut pending: Vec<CellIndex> = self.changed.drain().collect();
+        while let Some(region) = pending.pop() {
+            if affected.insert(region) {
+                pending.extend(
+                    region
+                        .grid_disk::<Vec<_>>(REGION_REACH)
+                        .into_iter()
+                        .filter(|neighbor| self.locations.contains_key(neighbor)),
+                );
+            }
+        }
+        affected
+    }
+}
+
+fn region(location: u64) -> Option<CellIndex> {
+    CellIndex::try_from(location).ok()?.parent(REGION_RES)
+}
+
 fn get_res_tgt(res: &Resolution) -> u64 {
     HIP17_RES_CONFIG
         .get(res)
@@ -322,5 +492,76 @@ mod tests {
             (631210990515536895, dec!(0.0065)),
         ]);
         assert_eq!(hex_density_map, expected_map);
+
+        let breakdown = compute_hex_densities(&gw_map);
+        let explained = &breakdown[&631210990515536895];
+        assert_eq!(7, explained.resolutions.len());
+        assert!(explained
+            .resolutions
+            .iter()
+            .all(|res| res.clipped == cmp::min(res.unclipped, res.limit)));
+    }
+
+    #[test]
+    fn incremental_matches_full_computation() {
+        let cluster: Vec<u64> = vec![
+            631210990515536895,
+            631210990515536895,
+            631210990515537919,
+            631210990515538431,
+            631210990515564031,
+            631210990515722239,
+            631210990515722239,
+            631210990515924479,
+            631210990516363775,
+            631210990517264895,
+        ];
+        // far enough away to lie under an unrelated region
+        let distant: u64 = 631615575095659519;
+
+        let full_map = |locations: &[u64]| {
+            let mut gw_map = GlobalHexMap::new();
+            for location in locations {
+                gw_map.increment_unclipped(*location);
+            }
+            gw_map.reduce_global();
+            compute_hex_density_map(&gw_map)
+        };
+        let incremental_map = |density: &IncrementalHexDensity| {
+            density
+                .densities()
+                .map(|(hex, density)| (*hex, density.scale))
+                .collect::<HashMap<u64, Decimal>>()
+        };
+
+        let mut density = IncrementalHexDensity::default();
+        for location in &cluster {
+            density.insert(*location);
+        }
+        density.insert(distant);
+        assert!(density.has_changes());
+        let update = density.update();
+        assert!(!density.has_changes());
+        assert!(update.removed.is_empty());
+        let mut all = cluster.clone();
+        all.push(distant);
+        assert_eq!(full_map(&all), incremental_map(&density));
+
+        // the sole gateway of a hex goes inactive, only its region is redone
+        density.remove(631210990517264895);
+        let update = density.update();
+        assert_eq!(vec![631210990517264895], update.removed);
+        assert!(!update.updated.contains_key(&distant));
+        all.retain(|location| *location != 631210990517264895);
+        assert_eq!(full_map(&all), incremental_map(&density));
+
+        // one of two gateways in a hex goes inactive, the hex is kept
+        density.remove(631210990515722239);
+        let update = density.update();
+        assert!(update.removed.is_empty());
+        assert!(update.updated.contains_key(&631210990515722239));
+        let position = all.iter().position(|l| *l == 631210990515722239).unwrap();
+        all.remove(position);
+        assert_eq!(full_map(&all), incremental_map(&density));
     }
 }
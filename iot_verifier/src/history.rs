@@ -0,0 +1,249 @@
+//! Per hotspot record of the outcome of its most recent beacons and witnesses,
+//! kept so that operators can be told why a hotspot was or wasn't rewarded
+//! without trawling the invalid report files. Entries are written as pocs are
+//! verified or purged and are pruned by the purger to the most recent
+//! `max_entries` of each report type per hotspot.
+use crate::poc_report::ReportType;
+use chrono::{DateTime, Duration, Utc};
+use file_store::{
+    iot_beacon_report::IotBeaconIngestReport,
+    iot_valid_poc::{IotPoc, IotVerifiedWitnessReport},
+    iot_witness_report::IotWitnessIngestReport,
+    traits::{IngestId, ReportId},
+};
+use helium_crypto::PublicKeyBinary;
+use helium_proto::services::poc_lora::{InvalidParticipantSide, InvalidReason, VerificationStatus};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+const HISTORY_INSERT_SQL: &str = "insert into hotspot_history (
+    id,
+    hotspot_key,
+    report_type,
+    received_timestamp,
+    status,
+    invalid_reason,
+    participant_side,
+    selected,
+    hex_scale,
+    reward_unit
+) ";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistorySettings {
+    /// Number of the most recent beacons and of the most recent witnesses
+    /// kept per hotspot. Default 50
+    #[serde(default = "default_max_entries")]
+    pub max_entries: i64,
+    /// Max age of kept entries (in hours). Default 48
+    #[serde(default = "default_max_age")]
+    pub max_age: i64,
+    /// Listen address of the history grpc api. Default none, in which the api is
+    /// not served
+    pub listen: Option<std::net::SocketAddr>,
+    /// File from which to load the keypair signing history api responses.
+    /// Required when the api is served
+    pub keypair: Option<String>,
+}
+
+fn default_max_entries() -> i64 {
+    50
+}
+
+fn default_max_age() -> i64 {
+    48
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self {
+            max_entries: default_max_entries(),
+            max_age: default_max_age(),
+            listen: None,
+            keypair: None,
+        }
+    }
+}
+
+impl HistorySettings {
+    pub fn max_age(&self) -> Duration {
+        Duration::hours(self.max_age)
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct HistoryEntry {
+    pub id: Vec<u8>,
+    pub hotspot_key: PublicKeyBinary,
+    pub report_type: ReportType,
+    pub received_timestamp: DateTime<Utc>,
+    pub status: String,
+    pub invalid_reason: String,
+    pub participant_side: String,
+    pub selected: bool,
+    pub hex_scale: Option<Decimal>,
+    pub reward_unit: Option<Decimal>,
+}
+
+impl HistoryEntry {
+    /// Entries for the beaconer and every witness of a verified poc
+    pub fn from_poc(iot_poc: &IotPoc) -> Vec<Self> {
+        let beacon_report = &iot_poc.beacon_report;
+        let beacon = Self {
+            id: iot_poc.poc_id.clone(),
+            hotspot_key: beacon_report.report.pub_key.clone(),
+            report_type: ReportType::Beacon,
+            received_timestamp: beacon_report.received_timestamp,
+            status: VerificationStatus::Valid.as_str_name().to_string(),
+            invalid_reason: InvalidReason::ReasonNone.as_str_name().to_string(),
+            participant_side: InvalidParticipantSide::SideNone.as_str_name().to_string(),
+            selected: true,
+            hex_scale: Some(beacon_report.hex_scale),
+            reward_unit: Some(beacon_report.reward_unit),
+        };
+        let selected = iot_poc
+            .selected_witnesses
+            .iter()
+            .map(|witness| Self::verified_witness(witness, true));
+        let unselected = iot_poc
+            .unselected_witnesses
+            .iter()
+            .map(|witness| Self::verified_witness(witness, false));
+        std::iter::once(beacon)
+            .chain(selected)
+            .chain(unselected)
+            .collect()
+    }
+
+    fn verified_witness(witness: &IotVerifiedWitnessReport, selected: bool) -> Self {
+        Self {
+            id: witness.report.report_id(witness.received_timestamp),
+            hotspot_key: witness.report.pub_key.clone(),
+            report_type: ReportType::Witness,
+            received_timestamp: witness.received_timestamp,
+            status: witness.status.as_str_name().to_string(),
+            invalid_reason: witness.invalid_reason.as_str_name().to_string(),
+            participant_side: witness.participant_side.as_str_name().to_string(),
+            selected: selected && witness.status == VerificationStatus::Valid,
+            hex_scale: Some(witness.hex_scale),
+            reward_unit: Some(witness.reward_unit),
+        }
+    }
+
+    pub fn invalid_beacon(beacon_report: &IotBeaconIngestReport, reason: InvalidReason) -> Self {
+        Self {
+            id: beacon_report.ingest_id(),
+            hotspot_key: beacon_report.report.pub_key.clone(),
+            report_type: ReportType::Beacon,
+            received_timestamp: beacon_report.received_timestamp,
+            status: VerificationStatus::Invalid.as_str_name().to_string(),
+            invalid_reason: reason.as_str_name().to_string(),
+            participant_side: InvalidParticipantSide::Beaconer.as_str_name().to_string(),
+            selected: false,
+            hex_scale: None,
+            reward_unit: None,
+        }
+    }
+
+    pub fn invalid_witness(
+        witness_report: &IotWitnessIngestReport,
+        reason: InvalidReason,
+        participant_side: InvalidParticipantSide,
+    ) -> Self {
+        Self {
+            id: witness_report.ingest_id(),
+            hotspot_key: witness_report.report.pub_key.clone(),
+            report_type: ReportType::Witness,
+            received_timestamp: witness_report.received_timestamp,
+            status: VerificationStatus::Invalid.as_str_name().to_string(),
+            invalid_reason: reason.as_str_name().to_string(),
+            participant_side: participant_side.as_str_name().to_string(),
+            selected: false,
+            hex_scale: None,
+            reward_unit: None,
+        }
+    }
+
+    pub async fn bulk_insert<'c, E>(executor: E, entries: Vec<Self>) -> Result<(), sqlx::Error>
+    where
+        E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+    {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Postgres> =
+            sqlx::QueryBuilder::new(HISTORY_INSERT_SQL);
+        query_builder.push_values(entries, |mut b, entry| {
+            b.push_bind(entry.id)
+                .push_bind(entry.hotspot_key)
+                .push_bind(entry.report_type)
+                .push_bind(entry.received_timestamp)
+                .push_bind(entry.status)
+                .push_bind(entry.invalid_reason)
+                .push_bind(entry.participant_side)
+                .push_bind(entry.selected)
+                .push_bind(entry.hex_scale)
+                .push_bind(entry.reward_unit);
+        });
+        query_builder.push(" on conflict (id) do nothing ");
+        query_builder.build().execute(executor).await.map(|_| ())
+    }
+
+    /// The most recent entries of the given report type for a hotspot, newest
+    /// first
+    pub async fn for_hotspot(
+        pool: &PgPool,
+        hotspot_key: &PublicKeyBinary,
+        report_type: ReportType,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            r#"
+            select * from hotspot_history
+            where hotspot_key = $1 and report_type = $2
+            order by received_timestamp desc
+            limit $3
+            "#,
+        )
+        .bind(hotspot_key)
+        .bind(report_type)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Delete entries older than `max_age` and all but the most recent
+    /// `max_entries` of each report type per hotspot
+    pub async fn purge(
+        pool: &PgPool,
+        max_entries: i64,
+        max_age: Duration,
+    ) -> Result<u64, sqlx::Error> {
+        let stale_time = Utc::now() - max_age;
+        let expired = sqlx::query("delete from hotspot_history where received_timestamp < $1")
+            .bind(stale_time)
+            .execute(pool)
+            .await?
+            .rows_affected();
+        let excess = sqlx::query(
+            r#"
+            delete from hotspot_history where id in (
+                select id from (
+                    select id, row_number() over (
+                        partition by hotspot_key, report_type
+                        order by received_timestamp desc
+                    ) as position
+                    from hotspot_history
+                ) ranked
+                where position > $1
+            )
+            "#,
+        )
+        .bind(max_entries)
+        .execute(pool)
+        .await?
+        .rows_affected();
+        Ok(expired + excess)
+    }
+}
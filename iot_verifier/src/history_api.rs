@@ -0,0 +1,197 @@
+//! Read only grpc api serving the verification history of a hotspot: the most
+//! recent beacons and witnesses of the hotspot, newest first. As with the
+//! responses of the config service, each response carries the verifier's
+//! public key as its `signer` and is signed by the verifier's keypair.
+use crate::{
+    history::{HistoryEntry, HistorySettings},
+    hotspot_history::{
+        HotspotHistory, HotspotHistoryEntryV1, HotspotHistoryReqV1, HotspotHistoryResV1,
+        HotspotHistoryServer,
+    },
+    poc_report::ReportType,
+};
+use chrono::Utc;
+use file_store::{traits::TimestampEncode, SCALE_MULTIPLIER};
+use helium_crypto::{Keypair, PublicKey, PublicKeyBinary, Sign};
+use helium_proto::{
+    services::poc_lora::{InvalidParticipantSide, InvalidReason, VerificationStatus},
+    Message,
+};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use tonic::{transport, Request, Response, Status};
+
+#[derive(thiserror::Error, Debug)]
+pub enum NewHistoryApiError {
+    #[error("history api keypair not configured")]
+    MissingKeypair,
+    #[error("error loading history api keypair: {0}")]
+    Keypair(#[from] Box<helium_crypto::Error>),
+}
+
+pub struct HistoryApi {
+    pool: PgPool,
+    listen: SocketAddr,
+    signing_key: Keypair,
+    max_entries: i64,
+}
+
+impl HistoryApi {
+    /// The history api, if a listen address is configured
+    pub fn from_settings(
+        settings: &HistorySettings,
+        pool: PgPool,
+    ) -> Result<Option<Self>, NewHistoryApiError> {
+        let Some(listen) = settings.listen else {
+            return Ok(None);
+        };
+        let keypair = settings
+            .keypair
+            .as_ref()
+            .ok_or(NewHistoryApiError::MissingKeypair)?;
+        let data =
+            std::fs::read(keypair).map_err(|err| Box::new(helium_crypto::Error::from(err)))?;
+        Ok(Some(Self {
+            pool,
+            listen,
+            signing_key: Keypair::try_from(&data[..]).map_err(Box::new)?,
+            max_entries: settings.max_entries,
+        }))
+    }
+
+    pub async fn run(self, shutdown: &triggered::Listener) -> anyhow::Result<()> {
+        tracing::info!("starting history api on {}", self.listen);
+        let listen = self.listen;
+        transport::Server::builder()
+            .add_service(HotspotHistoryServer::new(self))
+            .serve_with_shutdown(listen, shutdown.clone())
+            .await?;
+        tracing::info!("stopping history api");
+        Ok(())
+    }
+
+    async fn entries(
+        &self,
+        hotspot: &PublicKeyBinary,
+        report_type: ReportType,
+    ) -> Result<Vec<HotspotHistoryEntryV1>, Status> {
+        HistoryEntry::for_hotspot(&self.pool, hotspot, report_type, self.max_entries)
+            .await
+            .map(|entries| {
+                entries
+                    .into_iter()
+                    .map(HotspotHistoryEntryV1::from)
+                    .collect()
+            })
+            .map_err(|err| {
+                tracing::warn!("failed to fetch hotspot history: {err:?}");
+                Status::internal("history lookup error")
+            })
+    }
+}
+
+#[tonic::async_trait]
+impl HotspotHistory for HistoryApi {
+    async fn history(
+        &self,
+        request: Request<HotspotHistoryReqV1>,
+    ) -> Result<Response<HotspotHistoryResV1>, Status> {
+        let request = request.into_inner();
+        let hotspot: PublicKeyBinary = PublicKey::try_from(&request.hotspot[..])
+            .map_err(|_| Status::invalid_argument("invalid hotspot key"))?
+            .into();
+
+        let resp = HotspotHistoryResV1 {
+            beacons: self.entries(&hotspot, ReportType::Beacon).await?,
+            witnesses: self.entries(&hotspot, ReportType::Witness).await?,
+            hotspot: hotspot.into(),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        Ok(Response::new(sign_response(&self.signing_key, resp)?))
+    }
+}
+
+fn sign_response(
+    signing_key: &Keypair,
+    mut resp: HotspotHistoryResV1,
+) -> Result<HotspotHistoryResV1, Status> {
+    resp.signature = signing_key
+        .sign(&resp.encode_to_vec())
+        .map_err(|_| Status::internal("response signing error"))?;
+    Ok(resp)
+}
+
+impl From<HistoryEntry> for HotspotHistoryEntryV1 {
+    fn from(entry: HistoryEntry) -> Self {
+        Self {
+            id: entry.id,
+            received_timestamp: entry.received_timestamp.encode_timestamp_millis(),
+            status: VerificationStatus::from_str_name(&entry.status).map_or(0, i32::from),
+            invalid_reason: InvalidReason::from_str_name(&entry.invalid_reason)
+                .map_or(0, i32::from),
+            participant_side: InvalidParticipantSide::from_str_name(&entry.participant_side)
+                .map_or(0, i32::from),
+            selected: entry.selected,
+            hex_scale: scaled(entry.hex_scale),
+            reward_unit: scaled(entry.reward_unit),
+        }
+    }
+}
+
+fn scaled(value: Option<Decimal>) -> u32 {
+    value
+        .and_then(|value| (value * SCALE_MULTIPLIER).to_u32())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use file_store::traits::MsgVerify;
+    use helium_crypto::KeyTag;
+    use rand::rngs::OsRng;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn entries_keep_their_outcome() {
+        let entry = HistoryEntry {
+            id: vec![1, 2, 3],
+            hotspot_key: PublicKeyBinary::from(vec![0]),
+            report_type: ReportType::Witness,
+            received_timestamp: Utc::now(),
+            status: VerificationStatus::Invalid.as_str_name().to_string(),
+            invalid_reason: InvalidReason::MaxDistanceExceeded.as_str_name().to_string(),
+            participant_side: InvalidParticipantSide::Witness.as_str_name().to_string(),
+            selected: false,
+            hex_scale: Some(dec!(0.5)),
+            reward_unit: None,
+        };
+        let entry = HotspotHistoryEntryV1::from(entry);
+        assert_eq!(entry.status(), VerificationStatus::Invalid);
+        assert_eq!(entry.invalid_reason(), InvalidReason::MaxDistanceExceeded);
+        assert_eq!(entry.participant_side(), InvalidParticipantSide::Witness);
+        assert_eq!(entry.hex_scale, 5000);
+        assert_eq!(entry.reward_unit, 0);
+    }
+
+    #[test]
+    fn responses_are_signed() {
+        let signing_key = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let resp = HotspotHistoryResV1 {
+            hotspot: vec![1, 2, 3],
+            beacons: vec![],
+            witnesses: vec![],
+            timestamp: Utc::now().encode_timestamp(),
+            signer: signing_key.public_key().into(),
+            signature: vec![],
+        };
+        let mut resp = sign_response(&signing_key, resp).unwrap();
+        resp.verify(signing_key.public_key()).unwrap();
+
+        resp.timestamp += 1;
+        assert!(resp.verify(signing_key.public_key()).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! Messages and tonic service of the hotspot history api. The pinned
+//! helium-proto doesn't carry the `HotspotHistory` service, so it is defined
+//! here, laid out as `tonic-build` would generate it. The status, invalid
+//! reason and participant side of the entries are the `poc_lora` enums.
+use file_store::traits::MsgVerify;
+use helium_crypto::{PublicKey, Verify};
+use helium_proto::{
+    services::poc_lora::{InvalidParticipantSide, InvalidReason, VerificationStatus},
+    Message,
+};
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tonic::{
+    codegen::{empty_body, http, Body, BoxFuture, Service, StdError},
+    server::{Grpc, NamedService, UnaryService},
+    Request, Response, Status,
+};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HotspotHistoryReqV1 {
+    /// Public key of the hotspot
+    #[prost(bytes = "vec", tag = "1")]
+    pub hotspot: Vec<u8>,
+}
+
+/// Outcome of a single beacon or witness report of the hotspot
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HotspotHistoryEntryV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub id: Vec<u8>,
+    /// Timestamp the report was received, in millis
+    #[prost(uint64, tag = "2")]
+    pub received_timestamp: u64,
+    #[prost(enumeration = "VerificationStatus", tag = "3")]
+    pub status: i32,
+    #[prost(enumeration = "InvalidReason", tag = "4")]
+    pub invalid_reason: i32,
+    #[prost(enumeration = "InvalidParticipantSide", tag = "5")]
+    pub participant_side: i32,
+    /// Whether a witness was selected for rewards
+    #[prost(bool, tag = "6")]
+    pub selected: bool,
+    /// Hex scale of a valid report, scaled by `SCALE_MULTIPLIER`
+    #[prost(uint32, tag = "7")]
+    pub hex_scale: u32,
+    /// Reward unit of a valid report, scaled by `SCALE_MULTIPLIER`
+    #[prost(uint32, tag = "8")]
+    pub reward_unit: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HotspotHistoryResV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hotspot: Vec<u8>,
+    /// Most recent beacons of the hotspot, newest first
+    #[prost(message, repeated, tag = "2")]
+    pub beacons: Vec<HotspotHistoryEntryV1>,
+    /// Most recent witnesses of the hotspot, newest first
+    #[prost(message, repeated, tag = "3")]
+    pub witnesses: Vec<HotspotHistoryEntryV1>,
+    /// Time the response was generated, in seconds
+    #[prost(uint64, tag = "4")]
+    pub timestamp: u64,
+    /// Public key of the verifier signing the response
+    #[prost(bytes = "vec", tag = "5")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub signature: Vec<u8>,
+}
+
+impl MsgVerify for HotspotHistoryResV1 {
+    fn verify(&self, verifier: &PublicKey) -> file_store::Result {
+        let mut msg = self.clone();
+        msg.signature = vec![];
+        verifier
+            .verify(&msg.encode_to_vec(), &self.signature)
+            .map_err(file_store::Error::from)
+    }
+}
+
+#[tonic::async_trait]
+pub trait HotspotHistory: Send + Sync + 'static {
+    async fn history(
+        &self,
+        request: Request<HotspotHistoryReqV1>,
+    ) -> Result<Response<HotspotHistoryResV1>, Status>;
+}
+
+pub struct HotspotHistoryServer<T> {
+    inner: Arc<T>,
+}
+
+impl<T: HotspotHistory> HotspotHistoryServer<T> {
+    pub fn new(inner: T) -> Self {
+        Self::from_arc(Arc::new(inner))
+    }
+
+    pub fn from_arc(inner: Arc<T>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<T> Clone for HotspotHistoryServer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> NamedService for HotspotHistoryServer<T> {
+    const NAME: &'static str = "helium.poc_lora.hotspot_history";
+}
+
+struct HistorySvc<T>(Arc<T>);
+
+impl<T: HotspotHistory> UnaryService<HotspotHistoryReqV1> for HistorySvc<T> {
+    type Response = HotspotHistoryResV1;
+    type Future = BoxFuture<Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: Request<HotspotHistoryReqV1>) -> Self::Future {
+        let inner = self.0.clone();
+        Box::pin(async move { inner.history(request).await })
+    }
+}
+
+impl<T, B> Service<http::Request<B>> for HotspotHistoryServer<T>
+where
+    T: HotspotHistory,
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = std::convert::Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let inner = self.inner.clone();
+        match req.uri().path() {
+            "/helium.poc_lora.hotspot_history/history" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.unary(HistorySvc(inner), req).await)
+            }),
+            _ => Box::pin(async move {
+                Ok(http::Response::builder()
+                    .status(200)
+                    .header("grpc-status", "12")
+                    .header("content-type", "application/grpc")
+                    .body(empty_body())
+                    .unwrap())
+            }),
+        }
+    }
+}
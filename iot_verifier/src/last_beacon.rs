@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use file_store::traits::TimestampDecode;
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 
 #[derive(sqlx::FromRow, Deserialize, Serialize, Debug)]
 #[sqlx(type_name = "last_beacon")]
@@ -111,3 +112,17 @@ impl LastBeacon {
         Ok(())
     }
 }
+
+/// Source of the last valid beacon of each gateway, consulted when verifying
+/// the beacon interval
+#[async_trait::async_trait]
+pub trait LastBeaconLookup: Sync {
+    async fn last_beacon(&self, id: &[u8]) -> Result<Option<LastBeacon>, LastBeaconError>;
+}
+
+#[async_trait::async_trait]
+impl LastBeaconLookup for PgPool {
+    async fn last_beacon(&self, id: &[u8]) -> Result<Option<LastBeacon>, LastBeaconError> {
+        LastBeacon::get(self, id).await
+    }
+}
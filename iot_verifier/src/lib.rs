@@ -1,20 +1,27 @@
+pub mod collusion;
 pub mod entropy;
 pub mod entropy_loader;
 pub mod gateway_cache;
 pub mod gateway_updater;
 mod hex_density;
+pub mod history;
+pub mod history_api;
+pub mod hotspot_history;
 pub mod last_beacon;
 pub mod loader;
-pub mod meta;
 pub mod metrics;
 pub mod packet_loader;
 pub mod poc;
 pub mod poc_report;
+pub mod poc_rules;
 pub mod purger;
 pub mod region_cache;
+pub mod reward_preview;
 pub mod reward_share;
 pub mod rewarder;
 pub mod runner;
 mod settings;
+pub mod simulate;
+pub mod terrain;
 pub mod tx_scaler;
 pub use settings::Settings;
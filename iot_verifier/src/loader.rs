@@ -1,12 +1,12 @@
 use crate::{
     gateway_cache::GatewayCache,
-    meta::Meta,
     metrics::LoaderMetricTracker,
     poc_report::{InsertBindings, IotStatus, Report, ReportType},
     Settings,
 };
 use chrono::DateTime;
-use chrono::{Duration as ChronoDuration, Utc};
+use chrono::{Duration as ChronoDuration, TimeZone, Utc};
+use db_store::meta::keys;
 use denylist::DenyList;
 use file_store::{
     iot_beacon_report::IotBeaconIngestReport,
@@ -25,8 +25,6 @@ use tokio::{
 use twox_hash::XxHash64;
 use xorf::{Filter as XorFilter, Xor16};
 
-const REPORTS_META_NAME: &str = "report";
-
 pub struct Loader {
     ingest_store: FileStore,
     pool: PgPool,
@@ -142,8 +140,10 @@ impl Loader {
         tracing::info!(
             "default window: {window_default_lookback}, max window: {window_max_lookback}"
         );
-        let after = Meta::last_timestamp(&self.pool, REPORTS_META_NAME)
+        let after = keys::IOT_VERIFIER_REPORT_WINDOW_END
+            .fetch_optional(&self.pool)
             .await?
+            .and_then(|millis| Utc.timestamp_millis_opt(millis).single())
             .unwrap_or(window_default_lookback)
             .max(window_max_lookback);
         let before_max = after + self.window_width;
@@ -162,7 +162,9 @@ impl Loader {
             return Ok(());
         }
         self.process_window(gateway_cache, after, before).await?;
-        Meta::update_last_timestamp(&self.pool, REPORTS_META_NAME, Some(before)).await?;
+        keys::IOT_VERIFIER_REPORT_WINDOW_END
+            .store(&self.pool, before.timestamp_millis())
+            .await?;
         Report::pending_beacons_to_ready(&self.pool, now).await?;
         tracing::info!("completed handling poc_report tick");
         Ok(())
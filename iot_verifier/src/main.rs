@@ -8,9 +8,11 @@ use file_store::{
 use futures::TryFutureExt;
 use iot_config::client::Client as IotConfigClient;
 use iot_verifier::{
-    entropy_loader, gateway_cache::GatewayCache, gateway_updater::GatewayUpdater, loader,
-    metrics::Metrics, packet_loader, poc_report::Report, purger, region_cache::RegionCache,
-    rewarder::Rewarder, runner, tx_scaler::Server as DensityScaler, Settings,
+    collusion::CollusionDetector, entropy_loader, gateway_cache::GatewayCache,
+    gateway_updater::GatewayUpdater, history_api::HistoryApi, loader, metrics::Metrics,
+    packet_loader, poc_report::Report, purger, region_cache::RegionCache, reward_preview,
+    reward_preview::RewardPreviewer, rewarder::Rewarder, runner, simulate,
+    tx_scaler::Server as DensityScaler, Settings,
 };
 use price::PriceTracker;
 use std::path;
@@ -42,12 +44,24 @@ impl Cli {
 #[derive(Debug, clap::Subcommand)]
 pub enum Cmd {
     Server(Server),
+    Simulate(simulate::Cmd),
+    RewardPreview(reward_preview::Cmd),
+    Migrate(db_store::schema::Cmd),
 }
 
 impl Cmd {
     pub async fn run(&self, settings: Settings) -> Result<()> {
         match self {
             Self::Server(cmd) => cmd.run(&settings).await,
+            Self::Simulate(cmd) => cmd.run(&settings).await,
+            Self::RewardPreview(cmd) => cmd.run(&settings).await,
+            Self::Migrate(cmd) => Ok(cmd
+                .run(
+                    &settings.database,
+                    env!("CARGO_PKG_NAME"),
+                    &sqlx::migrate!(),
+                )
+                .await?),
         }
     }
 }
@@ -61,6 +75,7 @@ impl Server {
             .with(tracing_subscriber::EnvFilter::new(&settings.log))
             .with(tracing_subscriber::fmt::layer())
             .init();
+        settings.warn_deprecated_keys();
 
         // Install the prometheus metrics exporter
         poc_metrics::start_metrics(&settings.metrics)?;
@@ -80,7 +95,7 @@ impl Server {
             .database
             .connect(env!("CARGO_PKG_NAME"), shutdown.clone())
             .await?;
-        sqlx::migrate!().run(&pool).await?;
+        db_store::schema::migrate(&pool, &sqlx::migrate!()).await?;
 
         let count_all_beacons = Report::count_all_beacons(&pool).await?;
         Metrics::num_beacons(count_all_beacons);
@@ -122,13 +137,47 @@ impl Server {
         .create()
         .await?;
 
+        // Suspicious witness cluster reports
+        let (cluster_sink, mut cluster_sink_server) = file_sink::FileSinkBuilder::new(
+            FileType::IotSuspiciousCluster,
+            store_base_path,
+            concat!(env!("CARGO_PKG_NAME"), "_suspicious_cluster"),
+            shutdown.clone(),
+        )
+        .deposits(Some(file_upload_tx.clone()))
+        .auto_commit(false)
+        .create()
+        .await?;
+
+        // Hex density snapshots
+        let (density_sink, mut density_sink_server) = file_sink::FileSinkBuilder::new(
+            FileType::IotHexDensity,
+            store_base_path,
+            concat!(env!("CARGO_PKG_NAME"), "_hex_density"),
+            shutdown.clone(),
+        )
+        .deposits(Some(file_upload_tx.clone()))
+        .auto_commit(false)
+        .create()
+        .await?;
+
+        let collusion_detector = CollusionDetector {
+            pool: pool.clone(),
+            settings: settings.collusion.clone(),
+            cluster_sink,
+        };
+
+        settings.emission_schedule.validate()?;
         let rewarder = Rewarder {
             pool: pool.clone(),
             rewards_sink,
             reward_manifests_sink,
             reward_period_hours: settings.rewards,
             reward_offset: settings.reward_offset_duration(),
+            emission_schedule: settings.emission_schedule.clone(),
         };
+        let reward_previewer =
+            RewardPreviewer::from_settings(settings, pool.clone(), shutdown.clone()).await?;
 
         // setup the entropy loader continious source
         let max_lookback_age = settings.loader_window_max_lookback_age();
@@ -167,8 +216,14 @@ impl Server {
         let mut loader = loader::Loader::from_settings(settings, pool.clone()).await?;
         let mut runner = runner::Runner::from_settings(settings, pool.clone()).await?;
         let purger = purger::Purger::from_settings(settings, pool.clone()).await?;
-        let mut density_scaler =
-            DensityScaler::from_settings(settings, pool, gateway_updater_receiver.clone()).await?;
+        let history_api = HistoryApi::from_settings(&settings.history, pool.clone())?;
+        let mut density_scaler = DensityScaler::from_settings(
+            settings,
+            pool,
+            gateway_updater_receiver.clone(),
+            density_sink,
+        )
+        .await?;
         let (price_tracker, price_receiver) =
             PriceTracker::start(&settings.price_tracker, shutdown.clone()).await?;
 
@@ -177,6 +232,8 @@ impl Server {
             gateway_updater.run(&shutdown).map_err(Error::from),
             gateway_rewards_server.run().map_err(Error::from),
             reward_manifests_server.run().map_err(Error::from),
+            cluster_sink_server.run().map_err(Error::from),
+            density_sink_server.run().map_err(Error::from),
             file_upload.run(&shutdown).map_err(Error::from),
             runner.run(
                 file_upload_tx.clone(),
@@ -194,7 +251,20 @@ impl Server {
                 file_upload_tx.clone()
             ),
             purger.run(&shutdown),
+            collusion_detector.run(&shutdown),
+            async {
+                match history_api {
+                    Some(history_api) => history_api.run(&shutdown).await,
+                    None => Ok(()),
+                }
+            },
             rewarder.run(price_tracker, &shutdown),
+            async {
+                match reward_previewer {
+                    Some(reward_previewer) => reward_previewer.run(&shutdown).await,
+                    None => Ok(()),
+                }
+            },
             density_scaler.run(&shutdown).map_err(Error::from),
             price_receiver.map_err(Error::from),
             entropy_loader_source_join_handle.map_err(anyhow::Error::from),
@@ -11,6 +11,8 @@ const LOADER_DROPPED_WITNESS_COUNTER: &str =
 const BEACON_GUAGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "num_beacons");
 const INVALID_WITNESS_COUNTER: &str =
     concat!(env!("CARGO_PKG_NAME"), "_", "invalid_witness_report");
+const WITNESS_TIMING_ANOMALY_COUNTER: &str =
+    concat!(env!("CARGO_PKG_NAME"), "_", "witness_timing_anomaly");
 
 pub struct Metrics;
 
@@ -53,6 +55,10 @@ impl Metrics {
     pub fn increment_invalid_witnesses(labels: &[(&'static str, &'static str)]) {
         metrics::increment_counter!(INVALID_WITNESS_COUNTER, labels);
     }
+
+    pub fn increment_witness_timing_anomaly(kind: &'static str) {
+        metrics::increment_counter!(WITNESS_TIMING_ANOMALY_COUNTER, "kind" => kind);
+    }
 }
 
 #[derive(Default)]
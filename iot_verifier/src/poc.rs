@@ -3,8 +3,11 @@ use crate::{
     gateway_cache::GatewayCache,
     gateway_cache::GatewayCacheError,
     hex_density::HexDensityMap,
-    last_beacon::{LastBeacon, LastBeaconError},
+    last_beacon::{LastBeacon, LastBeaconError, LastBeaconLookup},
+    metrics::Metrics,
+    poc_rules::{PocRules, RssiModel},
     region_cache::{RegionCache, RegionCacheError},
+    terrain::Terrain,
 };
 use beacon;
 use chrono::{DateTime, Duration, Utc};
@@ -22,23 +25,20 @@ use helium_proto::{
 use iot_config::gateway_info::{GatewayInfo, GatewayMetadata};
 use lazy_static::lazy_static;
 use rust_decimal::Decimal;
-use sqlx::PgPool;
-use std::f64::consts::PI;
+use std::{collections::HashSet, f64::consts::PI, sync::Arc};
 
 pub type GenericVerifyResult<T = ()> = std::result::Result<T, InvalidReason>;
 
+/// Invalid reason given to witnesses failing the timing verifications. The
+/// pinned helium-proto has no dedicated timing reason, so these are told apart
+/// from stale reports by the timing anomaly metric and log
+pub const INVALID_TIMING_REASON: InvalidReason = InvalidReason::Stale;
+
 /// C is the speed of light in air in meters per second
 pub const C: f64 = 2.998e8;
 /// R is the (average) radius of the earth
 pub const R: f64 = 6.371e6;
 
-/// max permitted distance of a witness from a beaconer measured in KM
-const POC_DISTANCE_LIMIT: u32 = 100;
-/// the minimum distance in cells between a beaconer and witness
-const POC_CELL_DISTANCE_MINIMUM: u32 = 8;
-/// the resolution at which parent cell distance is derived
-const POC_CELL_PARENT_RES: Resolution = Resolution::Eleven;
-
 lazy_static! {
     /// Scaling factor when inactive gateway is not found in the tx scaling map (20%).
     /// A default tx scale is required to allow for inactive hotspots to become active
@@ -54,6 +54,8 @@ pub struct Poc {
     entropy_start: DateTime<Utc>,
     entropy_end: DateTime<Utc>,
     entropy_version: i32,
+    rules: PocRules,
+    terrain: Arc<Terrain>,
 }
 
 pub struct VerifyBeaconResult {
@@ -88,6 +90,8 @@ impl Poc {
         witness_reports: Vec<IotWitnessIngestReport>,
         entropy_start: DateTime<Utc>,
         entropy_version: i32,
+        rules: PocRules,
+        terrain: Arc<Terrain>,
     ) -> Self {
         let entropy_end = entropy_start + Duration::seconds(ENTROPY_LIFESPAN);
         Self {
@@ -96,6 +100,8 @@ impl Poc {
             entropy_start,
             entropy_end,
             entropy_version,
+            rules,
+            terrain,
         }
     }
 
@@ -104,9 +110,7 @@ impl Poc {
         hex_density_map: impl HexDensityMap,
         gateway_cache: &GatewayCache,
         region_cache: &RegionCache,
-        pool: &PgPool,
-        beacon_interval: Duration,
-        beacon_interval_tolerance: Duration,
+        last_beacons: &impl LastBeaconLookup,
     ) -> Result<VerifyBeaconResult, VerificationError> {
         let beacon = &self.beacon_report.report;
         let beaconer_pub_key = beacon.pub_key.clone();
@@ -135,7 +139,7 @@ impl Poc {
             Err(err) => return Err(VerificationError::RegionCache(err)),
         };
         // we have beaconer info, proceed to verifications
-        let last_beacon = LastBeacon::get(pool, beaconer_pub_key.as_ref()).await?;
+        let last_beacon = last_beacons.last_beacon(beaconer_pub_key.as_ref()).await?;
         match do_beacon_verifications(
             self.entropy_start,
             self.entropy_end,
@@ -144,8 +148,7 @@ impl Poc {
             &self.beacon_report,
             &beaconer_info,
             &beaconer_region_info.region_params,
-            beacon_interval,
-            beacon_interval_tolerance,
+            &self.rules,
         ) {
             Ok(()) => {
                 let tx_scale = hex_density_map
@@ -205,6 +208,15 @@ impl Poc {
                 verified_witnesses.push(dup_witness)
             }
         }
+        if let Some(ref beaconer_metadata) = beacon_info.metadata {
+            verify_witness_timing_clusters(
+                &self.beacon_report,
+                beaconer_metadata.location,
+                &mut verified_witnesses,
+                self.rules.min_witness_timing_spread_us,
+                self.rules.witness_timing_cluster_size,
+            );
+        }
         let resp = VerifyWitnessesResult {
             verified_witnesses,
             failed_witnesses,
@@ -264,7 +276,7 @@ impl Poc {
                 0,
                 0,
                 InvalidParticipantSide::Beaconer,
-            ))
+            ));
         };
         // run the witness verifications
         match do_witness_verifications(
@@ -274,6 +286,8 @@ impl Poc {
             &witness_info,
             &self.beacon_report,
             beaconer_metadata,
+            &self.rules,
+            &self.terrain,
         ) {
             Ok(()) => {
                 let tx_scale = hex_density_map
@@ -311,8 +325,7 @@ pub fn do_beacon_verifications(
     beacon_report: &IotBeaconIngestReport,
     beaconer_info: &GatewayInfo,
     beaconer_region_params: &[BlockchainRegionParamV1],
-    beacon_interval: Duration,
-    beacon_interval_tolerance: Duration,
+    rules: &PocRules,
 ) -> GenericVerifyResult {
     tracing::debug!(
         "verifying beacon from beaconer: {:?}",
@@ -328,8 +341,8 @@ pub fn do_beacon_verifications(
     verify_beacon_schedule(
         &last_beacon,
         beacon_received_ts,
-        beacon_interval,
-        beacon_interval_tolerance,
+        rules.beacon_interval(),
+        rules.beacon_interval_tolerance(),
     )?;
     verify_beacon_payload(
         &beacon_report.report,
@@ -353,6 +366,8 @@ pub fn do_witness_verifications(
     witness_info: &GatewayInfo,
     beacon_report: &IotBeaconIngestReport,
     beaconer_metadata: &GatewayMetadata,
+    rules: &PocRules,
+    terrain: &Terrain,
 ) -> GenericVerifyResult {
     tracing::debug!(
         "verifying witness from gateway: {:?}",
@@ -377,10 +392,35 @@ pub fn do_witness_verifications(
     verify_witness_freq(
         beacon_report.report.frequency,
         witness_report.report.frequency,
+        rules.max_witness_freq_diff_hz,
     )?;
     verify_witness_region(beaconer_metadata.region, witness_metadata.region)?;
-    verify_witness_cell_distance(beaconer_metadata.location, witness_metadata.location)?;
-    verify_witness_distance(beaconer_metadata.location, witness_metadata.location)?;
+    verify_witness_cell_distance(
+        beaconer_metadata.location,
+        witness_metadata.location,
+        rules.min_witness_cell_distance,
+        rules.witness_cell_resolution,
+    )?;
+    verify_witness_distance(
+        beaconer_metadata.location,
+        witness_metadata.location,
+        rules.max_witness_distance_km,
+    )?;
+    if let Err(invalid_reason) = verify_witness_timing(
+        beacon_report.report.timestamp,
+        witness_report.report.timestamp,
+        beaconer_metadata.location,
+        witness_metadata.location,
+        rules.max_witness_clock_skew_ms,
+    ) {
+        tracing::info!(
+            gateway = %witness_report.report.pub_key,
+            kind = "clock_skew",
+            "witness timing anomaly"
+        );
+        Metrics::increment_witness_timing_anomaly("clock_skew");
+        return Err(invalid_reason);
+    }
     verify_witness_rssi(
         witness_report.report.signal,
         witness_report.report.frequency,
@@ -389,7 +429,20 @@ pub fn do_witness_verifications(
         witness_metadata.gain,
         beaconer_metadata.location,
         witness_metadata.location,
+        rules.rssi_model,
+        rules.rssi_margin_db,
     )?;
+    if rules.rssi_model == RssiModel::Terrain {
+        verify_witness_terrain(
+            witness_report.report.signal,
+            witness_report.report.frequency,
+            beacon_report.report.tx_power,
+            beaconer_metadata,
+            witness_metadata,
+            terrain,
+            rules.rssi_margin_db,
+        )?;
+    }
     tracing::debug!(
         "valid witness from gateway: {:?}",
         witness_info.address.clone()
@@ -539,9 +592,13 @@ fn verify_self_witness(
 }
 
 /// verify witness is utilizing same freq and that of the beaconer
-/// tolerance is 100Khz
-fn verify_witness_freq(beacon_freq: u64, witness_freq: u64) -> GenericVerifyResult {
-    if (beacon_freq.abs_diff(witness_freq) as i32) > 1000 * 100 {
+/// within the given tolerance in Hz
+fn verify_witness_freq(
+    beacon_freq: u64,
+    witness_freq: u64,
+    max_freq_diff_hz: u64,
+) -> GenericVerifyResult {
+    if beacon_freq.abs_diff(witness_freq) > max_freq_diff_hz {
         tracing::debug!(
             "witness verification failed, reason: {:?}. beaconer freq: {beacon_freq}, witness freq: {witness_freq}",
             InvalidReason::InvalidFrequency
@@ -567,12 +624,16 @@ fn verify_witness_region(
 }
 
 /// verify witness does not exceed max distance from beaconer
-fn verify_witness_distance(beacon_loc: u64, witness_loc: u64) -> GenericVerifyResult {
+fn verify_witness_distance(
+    beacon_loc: u64,
+    witness_loc: u64,
+    max_distance_km: u32,
+) -> GenericVerifyResult {
     let witness_distance = match calc_distance(beacon_loc, witness_loc) {
         Ok(d) => d,
         Err(_) => return Err(InvalidReason::MaxDistanceExceeded),
     };
-    if witness_distance / 1000 > POC_DISTANCE_LIMIT {
+    if witness_distance / 1000 > max_distance_km {
         tracing::debug!(
             "witness verification failed, reason: {:?}. distance {witness_distance}",
             InvalidReason::MaxDistanceExceeded
@@ -582,13 +643,106 @@ fn verify_witness_distance(beacon_loc: u64, witness_loc: u64) -> GenericVerifyRe
     Ok(())
 }
 
+/// verify the witness receive time is consistent with the beacon transmit
+/// time and the time of flight between the beaconer and witness
+fn verify_witness_timing(
+    beacon_ts: DateTime<Utc>,
+    witness_ts: DateTime<Utc>,
+    beacon_loc: u64,
+    witness_loc: u64,
+    max_clock_skew_ms: u64,
+) -> GenericVerifyResult {
+    if max_clock_skew_ms == 0 {
+        return Ok(());
+    }
+    let offset = match witness_timing_offset(beacon_ts, witness_ts, beacon_loc, witness_loc) {
+        Ok(offset) => offset,
+        Err(_) => return Err(INVALID_TIMING_REASON),
+    };
+    if offset.num_milliseconds().unsigned_abs() > max_clock_skew_ms {
+        tracing::debug!(
+            "witness verification failed, reason: {:?}. timing offset {}ms",
+            INVALID_TIMING_REASON,
+            offset.num_milliseconds()
+        );
+        return Err(INVALID_TIMING_REASON);
+    }
+    Ok(())
+}
+
+/// invalidate the valid witnesses of a beacon whose time of flight adjusted
+/// receive times fall within `min_spread_us` of each other in groups of at
+/// least `cluster_size`. independent gateway clocks do not agree that closely
+fn verify_witness_timing_clusters(
+    beacon_report: &IotBeaconIngestReport,
+    beacon_loc: u64,
+    witnesses: &mut [IotVerifiedWitnessReport],
+    min_spread_us: u64,
+    cluster_size: usize,
+) {
+    if min_spread_us == 0 || cluster_size < 2 {
+        return;
+    }
+    let mut offsets: Vec<(i64, usize)> = witnesses
+        .iter()
+        .enumerate()
+        .filter(|(_, witness)| witness.status == VerificationStatus::Valid)
+        .filter_map(|(idx, witness)| {
+            let offset = witness_timing_offset(
+                beacon_report.report.timestamp,
+                witness.report.timestamp,
+                beacon_loc,
+                witness.location?,
+            )
+            .ok()?;
+            Some((offset.num_microseconds()?, idx))
+        })
+        .collect();
+    offsets.sort_unstable();
+    let clustered: HashSet<usize> = offsets
+        .windows(cluster_size)
+        .filter(|window| window[cluster_size - 1].0 - window[0].0 < min_spread_us as i64)
+        .flat_map(|window| window.iter().map(|(_, idx)| *idx))
+        .collect();
+    for idx in clustered {
+        let witness = &mut witnesses[idx];
+        tracing::info!(
+            gateway = %witness.report.pub_key,
+            kind = "cluster",
+            "witness timing anomaly"
+        );
+        Metrics::increment_witness_timing_anomaly("cluster");
+        witness.status = VerificationStatus::Invalid;
+        witness.invalid_reason = INVALID_TIMING_REASON;
+        witness.participant_side = InvalidParticipantSide::Witness;
+    }
+}
+
+/// offset of the witness receive time from the beacon transmit time plus the
+/// time of flight between the beaconer and witness
+fn witness_timing_offset(
+    beacon_ts: DateTime<Utc>,
+    witness_ts: DateTime<Utc>,
+    beacon_loc: u64,
+    witness_loc: u64,
+) -> Result<Duration, CalcDistanceError> {
+    let distance = calc_distance(beacon_loc, witness_loc)?;
+    let time_of_flight = Duration::nanoseconds((distance as f64 / C * 1e9) as i64);
+    Ok(witness_ts - beacon_ts - time_of_flight)
+}
+
 /// verify min hex distance between beaconer and witness
-fn verify_witness_cell_distance(beacon_loc: u64, witness_loc: u64) -> GenericVerifyResult {
-    let cell_distance = match calc_cell_distance(beacon_loc, witness_loc) {
+fn verify_witness_cell_distance(
+    beacon_loc: u64,
+    witness_loc: u64,
+    min_cell_distance: u32,
+    resolution: Resolution,
+) -> GenericVerifyResult {
+    let cell_distance = match calc_cell_distance(beacon_loc, witness_loc, resolution) {
         Ok(d) => d,
         Err(_) => return Err(InvalidReason::BelowMinDistance),
     };
-    if cell_distance < POC_CELL_DISTANCE_MINIMUM {
+    if cell_distance < min_cell_distance {
         tracing::debug!(
             "witness verification failed, reason: {:?}. cell distance {cell_distance}",
             InvalidReason::BelowMinDistance
@@ -599,6 +753,7 @@ fn verify_witness_cell_distance(beacon_loc: u64, witness_loc: u64) -> GenericVer
 }
 
 /// verify witness rssi
+#[allow(clippy::too_many_arguments)]
 fn verify_witness_rssi(
     witness_signal: i32,
     witness_freq: u64,
@@ -607,7 +762,12 @@ fn verify_witness_rssi(
     witness_gain: i32,
     beacon_loc: u64,
     witness_loc: u64,
+    rssi_model: RssiModel,
+    rssi_margin_db: f64,
 ) -> GenericVerifyResult {
+    if rssi_model == RssiModel::Disabled {
+        return Ok(());
+    }
     let distance = match calc_distance(beacon_loc, witness_loc) {
         Ok(d) => d,
         Err(_) => return Err(InvalidReason::BadRssi),
@@ -621,7 +781,7 @@ fn verify_witness_rssi(
     );
     // signal is submitted as DBM * 10
     // min_rcv_signal is plain old DBM
-    if witness_signal as f64 / 10.0 > min_rcv_signal {
+    if witness_signal as f64 / 10.0 > min_rcv_signal + rssi_margin_db {
         tracing::debug!(
             "witness verification failed, reason: {:?}
             beaconer tx_power: {beacon_tx_power},
@@ -637,6 +797,51 @@ fn verify_witness_rssi(
     Ok(())
 }
 
+/// verify witness rssi is plausible once the terrain obstructing the path
+/// between the beaconer and witness is accounted for. Paths not entirely
+/// covered by loaded elevation tiles are not checked
+fn verify_witness_terrain(
+    witness_signal: i32,
+    witness_freq: u64,
+    beacon_tx_power: i32,
+    beaconer_metadata: &GatewayMetadata,
+    witness_metadata: &GatewayMetadata,
+    terrain: &Terrain,
+    rssi_margin_db: f64,
+) -> GenericVerifyResult {
+    let Some(obstruction_loss) = terrain
+        .obstruction_loss(beaconer_metadata, witness_metadata, witness_freq)
+        .map_err(|_| InvalidReason::BadRssi)?
+    else {
+        return Ok(());
+    };
+    let distance = calc_distance(beaconer_metadata.location, witness_metadata.location)
+        .map_err(|_| InvalidReason::BadRssi)?;
+    let max_rcv_signal = calc_expected_rssi(
+        beacon_tx_power,
+        witness_freq,
+        distance,
+        beaconer_metadata.gain,
+        witness_metadata.gain,
+    ) - obstruction_loss;
+    // signal is submitted as DBM * 10
+    if witness_signal as f64 / 10.0 > max_rcv_signal + rssi_margin_db {
+        tracing::debug!(
+            "witness verification failed, reason: {:?}, obstructed path
+            beaconer elevation: {},
+            witness elevation: {},
+            witness signal: {witness_signal},
+            obstruction loss: {obstruction_loss},
+            max_rcv_signal: {max_rcv_signal}",
+            InvalidReason::BadRssi,
+            beaconer_metadata.elevation,
+            witness_metadata.elevation,
+        );
+        return Err(InvalidReason::BadRssi);
+    }
+    Ok(())
+}
+
 fn verify_witness_data(beacon_data: &Vec<u8>, witness_data: &Vec<u8>) -> GenericVerifyResult {
     if witness_data != beacon_data {
         tracing::debug!(
@@ -676,14 +881,14 @@ pub enum CalcDistanceError {
     H3DistanceError(#[from] h3o::error::LocalIjError),
 }
 
-fn calc_cell_distance(p1: u64, p2: u64) -> Result<u32, CalcDistanceError> {
+fn calc_cell_distance(p1: u64, p2: u64, resolution: Resolution) -> Result<u32, CalcDistanceError> {
     let p1_cell = CellIndex::try_from(p1)?;
     let p2_cell = CellIndex::try_from(p2)?;
     let source_parent = p1_cell
-        .parent(POC_CELL_PARENT_RES)
+        .parent(resolution)
         .ok_or(CalcDistanceError::H3ParentError)?;
     let dest_parent = p2_cell
-        .parent(POC_CELL_PARENT_RES)
+        .parent(resolution)
         .ok_or(CalcDistanceError::H3ParentError)?;
     let cell_distance = source_parent.grid_distance(dest_parent)? as u32;
     Ok(cell_distance)
@@ -834,7 +1039,7 @@ mod tests {
         // converted co-ords to h3 index at resolution 15
         let loc1 = 644459695463521437;
         let loc2 = 644460986971331488;
-        let dist = calc_cell_distance(loc1, loc2).unwrap();
+        let dist = calc_cell_distance(loc1, loc2, Resolution::Eleven).unwrap();
         // verify the calculated cell distance is more than min cell distance
         // the correct cell distance between the two locations at resolution 11 = 360
         assert_eq!(360, dist);
@@ -1025,11 +1230,11 @@ mod tests {
         // over the tolerance level
         let witness3_freq = beacon_freq + (1000 * 110);
 
-        assert!(verify_witness_freq(beacon_freq, witness1_freq).is_ok());
-        assert!(verify_witness_freq(beacon_freq, witness2_freq).is_ok());
+        assert!(verify_witness_freq(beacon_freq, witness1_freq, 100_000).is_ok());
+        assert!(verify_witness_freq(beacon_freq, witness2_freq, 100_000).is_ok());
         assert_eq!(
             Err(InvalidReason::InvalidFrequency),
-            verify_witness_freq(beacon_freq, witness3_freq)
+            verify_witness_freq(beacon_freq, witness3_freq, 100_000)
         );
     }
 
@@ -1050,10 +1255,10 @@ mod tests {
         let beacon_loc = LOC0;
         let witness1_loc = LOC1;
         let witness2_loc = LOC2;
-        assert!(verify_witness_distance(beacon_loc, witness1_loc).is_ok());
+        assert!(verify_witness_distance(beacon_loc, witness1_loc, 100).is_ok());
         assert_eq!(
             Err(InvalidReason::MaxDistanceExceeded),
-            verify_witness_distance(beacon_loc, witness2_loc)
+            verify_witness_distance(beacon_loc, witness2_loc, 100)
         );
     }
 
@@ -1066,10 +1271,73 @@ mod tests {
         // witness 1 location is 7 cells from the beaconer and thus invalid
         assert_eq!(
             Err(InvalidReason::BelowMinDistance),
-            verify_witness_cell_distance(beacon_loc, witness1_loc)
+            verify_witness_cell_distance(beacon_loc, witness1_loc, 8, Resolution::Eleven)
         );
         // witness 2's location is 28 cells from the beaconer and thus valid
-        assert!(verify_witness_cell_distance(beacon_loc, witness2_loc).is_ok());
+        assert!(
+            verify_witness_cell_distance(beacon_loc, witness2_loc, 8, Resolution::Eleven).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_verify_witness_timing() {
+        let beacon_ts = Utc.timestamp_millis_opt(ENTROPY_TIMESTAMP).unwrap();
+        let on_time = beacon_ts + Duration::milliseconds(500);
+        let late = beacon_ts + Duration::milliseconds(1500);
+        let early = beacon_ts - Duration::milliseconds(1500);
+        assert!(verify_witness_timing(beacon_ts, on_time, LOC0, LOC1, 1000).is_ok());
+        assert_eq!(
+            Err(INVALID_TIMING_REASON),
+            verify_witness_timing(beacon_ts, late, LOC0, LOC1, 1000)
+        );
+        assert_eq!(
+            Err(INVALID_TIMING_REASON),
+            verify_witness_timing(beacon_ts, early, LOC0, LOC1, 1000)
+        );
+        // a zero skew budget disables the verification
+        assert!(verify_witness_timing(beacon_ts, late, LOC0, LOC1, 0).is_ok());
+    }
+
+    #[test]
+    fn test_verify_witness_timing_clusters() {
+        let beacon_ts = Utc.timestamp_millis_opt(ENTROPY_TIMESTAMP).unwrap();
+        let mut beacon_report = valid_beacon_report(beacon_ts);
+        beacon_report.report.timestamp = beacon_ts;
+        let witness = |offset: Duration| {
+            let mut report = valid_witness_report(beacon_ts).report;
+            report.timestamp = beacon_ts + offset;
+            IotVerifiedWitnessReport::valid(&report, beacon_ts, Some(LOC1), 12, 0, Decimal::ONE)
+        };
+        let witnesses = vec![
+            witness(Duration::milliseconds(100)),
+            witness(Duration::milliseconds(100) + Duration::microseconds(2)),
+            witness(Duration::milliseconds(300)),
+            witness(Duration::milliseconds(100) + Duration::microseconds(4)),
+        ];
+
+        // disabled without a min spread
+        let mut unchecked = witnesses.clone();
+        verify_witness_timing_clusters(&beacon_report, LOC0, &mut unchecked, 0, 3);
+        assert!(unchecked
+            .iter()
+            .all(|witness| witness.status == VerificationStatus::Valid));
+
+        let mut checked = witnesses;
+        verify_witness_timing_clusters(&beacon_report, LOC0, &mut checked, 10, 3);
+        let reasons: Vec<InvalidReason> = checked
+            .iter()
+            .map(|witness| witness.invalid_reason)
+            .collect();
+        assert_eq!(
+            vec![
+                INVALID_TIMING_REASON,
+                INVALID_TIMING_REASON,
+                InvalidReason::ReasonNone,
+                INVALID_TIMING_REASON
+            ],
+            reasons
+        );
+        assert_eq!(VerificationStatus::Valid, checked[2].status);
     }
 
     #[test]
@@ -1091,6 +1359,8 @@ mod tests {
             witness1_gain,
             beacon_loc,
             witness1_loc,
+            RssiModel::FreeSpace,
+            0.0,
         )
         .is_ok());
         let beacon2_tx_power = 27;
@@ -1108,6 +1378,88 @@ mod tests {
                 witness2_gain,
                 beacon_loc,
                 witness2_loc,
+                RssiModel::FreeSpace,
+                0.0,
+            )
+        );
+    }
+
+    #[test]
+    fn test_verify_witness_terrain() {
+        let metadata = |lat: f64, lng: f64| GatewayMetadata {
+            location: u64::from(LatLng::new(lat, lng).unwrap().to_cell(Resolution::Twelve)),
+            elevation: 10,
+            gain: 12,
+            region: ProtoRegion::Us915,
+        };
+        let beaconer = metadata(37.5, -121.9);
+        let witness = metadata(37.5, -121.1);
+        // a flat tile at 100m with a 1500m north south ridge half way across
+        let size = 121;
+        let mut samples = vec![100_i16; size * size];
+        for row in 0..size {
+            samples[row * size + size / 2] = 1500;
+        }
+        let tile: Vec<u8> = samples.iter().flat_map(|s| s.to_be_bytes()).collect();
+        let ridge = Terrain::from_tiles([("N37W122.hgt", tile)]).unwrap();
+
+        let tx_power = 27;
+        let freq = 904_700_000;
+        let distance = calc_distance(beaconer.location, witness.location).unwrap();
+        let free_space_rssi =
+            calc_expected_rssi(tx_power, freq, distance, beaconer.gain, witness.gain);
+        // plausible in free space but not through the ridge
+        let strong_signal = ((free_space_rssi - 10.0) * 10.0) as i32;
+        let weak_signal = ((free_space_rssi - 80.0) * 10.0) as i32;
+
+        assert_eq!(
+            Err(InvalidReason::BadRssi),
+            verify_witness_terrain(
+                strong_signal,
+                freq,
+                tx_power,
+                &beaconer,
+                &witness,
+                &ridge,
+                0.0
+            )
+        );
+        assert_eq!(
+            Ok(()),
+            verify_witness_terrain(
+                weak_signal,
+                freq,
+                tx_power,
+                &beaconer,
+                &witness,
+                &ridge,
+                0.0
+            )
+        );
+        // without a tile covering the path the check is skipped
+        assert_eq!(
+            Ok(()),
+            verify_witness_terrain(
+                strong_signal,
+                freq,
+                tx_power,
+                &beaconer,
+                &witness,
+                &Terrain::default(),
+                0.0
+            )
+        );
+        // nor with a path running off the tile
+        assert_eq!(
+            Ok(()),
+            verify_witness_terrain(
+                strong_signal,
+                freq,
+                tx_power,
+                &beaconer,
+                &metadata(38.5, -121.1),
+                &ridge,
+                0.0
             )
         );
     }
@@ -1138,8 +1490,11 @@ mod tests {
         let beaconer_info = beaconer_gateway_info(Some(LOC0), ProtoRegion::Eu868, true);
         let entropy_start = Utc.timestamp_millis_opt(ENTROPY_TIMESTAMP).unwrap();
         let entropy_end = entropy_start + Duration::minutes(3);
-        let beacon_interval = Duration::minutes(5);
-        let beacon_interval_tolerance = Duration::seconds(60);
+        let rules = PocRules {
+            beacon_interval: 5 * 60,
+            beacon_interval_tolerance: 60,
+            ..Default::default()
+        };
 
         // test entropy lifepsan verification is active in the beacon validation list
         let beacon_report1 = valid_beacon_report(entropy_start + Duration::minutes(4));
@@ -1151,8 +1506,7 @@ mod tests {
             &beacon_report1,
             &beaconer_info,
             &default_region_params(),
-            beacon_interval,
-            beacon_interval_tolerance,
+            &rules,
         );
         assert_eq!(Err(InvalidReason::EntropyExpired), resp1);
 
@@ -1167,8 +1521,7 @@ mod tests {
             &beacon_report2,
             &beacon_info2,
             &default_region_params(),
-            beacon_interval,
-            beacon_interval_tolerance,
+            &rules,
         );
         assert_eq!(Err(InvalidReason::NotAsserted), resp2);
 
@@ -1186,8 +1539,7 @@ mod tests {
             &beacon_report3,
             &beaconer_info,
             &default_region_params(),
-            beacon_interval,
-            beacon_interval_tolerance,
+            &rules,
         );
         assert_eq!(Err(InvalidReason::IrregularInterval), resp3);
 
@@ -1202,8 +1554,7 @@ mod tests {
             &beacon_report4,
             &beacon_info4,
             &default_region_params(),
-            beacon_interval,
-            beacon_interval_tolerance,
+            &rules,
         );
         assert_eq!(Err(InvalidReason::InvalidCapability), resp4);
 
@@ -1217,8 +1568,7 @@ mod tests {
             &beacon_report5,
             &beaconer_info,
             &default_region_params(),
-            beacon_interval,
-            beacon_interval_tolerance,
+            &rules,
         );
         assert_eq!(Err(InvalidReason::InvalidPacket), resp5);
 
@@ -1232,8 +1582,7 @@ mod tests {
             &beacon_report6,
             &beaconer_info,
             &default_region_params(),
-            beacon_interval,
-            beacon_interval_tolerance,
+            &rules,
         );
         assert_eq!(Ok(()), resp6);
     }
@@ -1267,6 +1616,8 @@ mod tests {
             &witness_info,
             &beacon_report,
             &beaconer_metadata,
+            &PocRules::default(),
+            &Terrain::default(),
         );
         assert_eq!(Err(InvalidReason::SelfWitness), resp1);
 
@@ -1279,6 +1630,8 @@ mod tests {
             &witness_info,
             &beacon_report,
             &beaconer_metadata,
+            &PocRules::default(),
+            &Terrain::default(),
         );
         assert_eq!(Err(InvalidReason::EntropyExpired), resp2);
 
@@ -1291,6 +1644,8 @@ mod tests {
             &witness_info,
             &beacon_report,
             &beaconer_metadata,
+            &PocRules::default(),
+            &Terrain::default(),
         );
         assert_eq!(Err(InvalidReason::InvalidPacket), resp3);
 
@@ -1304,6 +1659,8 @@ mod tests {
             &witness_info4,
             &beacon_report,
             &beaconer_metadata,
+            &PocRules::default(),
+            &Terrain::default(),
         );
         assert_eq!(Err(InvalidReason::NotAsserted), resp4);
 
@@ -1316,6 +1673,8 @@ mod tests {
             &witness_info,
             &beacon_report,
             &beaconer_metadata,
+            &PocRules::default(),
+            &Terrain::default(),
         );
         assert_eq!(Err(InvalidReason::InvalidFrequency), resp5);
 
@@ -1329,6 +1688,8 @@ mod tests {
             &witness_info6,
             &beacon_report,
             &beaconer_metadata,
+            &PocRules::default(),
+            &Terrain::default(),
         );
         assert_eq!(Err(InvalidReason::InvalidRegion), resp6);
 
@@ -1342,6 +1703,8 @@ mod tests {
             &witness_info7,
             &beacon_report,
             &beaconer_metadata,
+            &PocRules::default(),
+            &Terrain::default(),
         );
         assert_eq!(Err(InvalidReason::BelowMinDistance), resp7);
 
@@ -1355,6 +1718,8 @@ mod tests {
             &witness_info8,
             &beacon_report,
             &beaconer_metadata,
+            &PocRules::default(),
+            &Terrain::default(),
         );
         assert_eq!(Err(InvalidReason::MaxDistanceExceeded), resp8);
 
@@ -1367,6 +1732,8 @@ mod tests {
             &witness_info,
             &beacon_report,
             &beaconer_metadata,
+            &PocRules::default(),
+            &Terrain::default(),
         );
         assert_eq!(Err(InvalidReason::BadRssi), resp9);
 
@@ -1380,6 +1747,8 @@ mod tests {
             &witness_info10,
             &beacon_report,
             &beaconer_metadata,
+            &PocRules::default(),
+            &Terrain::default(),
         );
         assert_eq!(Err(InvalidReason::InvalidCapability), resp10);
 
@@ -1393,6 +1762,8 @@ mod tests {
             &witness_info11,
             &beacon_report,
             &beaconer_metadata,
+            &PocRules::default(),
+            &Terrain::default(),
         );
         assert_eq!(Ok(()), resp11);
     }
@@ -12,7 +12,7 @@ const REPORT_INSERT_SQL: &str = "insert into poc_report (
     status
 ) ";
 
-#[derive(sqlx::Type, Serialize, Deserialize, Debug)]
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy)]
 #[sqlx(type_name = "reporttype", rename_all = "lowercase")]
 pub enum ReportType {
     Witness,
@@ -135,7 +135,7 @@ impl Report {
         packet_data: &'q Vec<u8>,
     ) -> Result<(), ReportError>
     where
-        E: sqlx::Executor<'c, Database = sqlx::Postgres> + Clone,
+        E: sqlx::Executor<'c, Database = sqlx::Postgres>,
     {
         sqlx::query(
             r#"
@@ -144,7 +144,7 @@ impl Report {
             "#,
         )
         .bind(packet_data)
-        .execute(executor.clone())
+        .execute(executor)
         .await?;
         Ok(())
     }
@@ -0,0 +1,269 @@
+use chrono::{DateTime, Duration, Utc};
+use h3o::Resolution;
+use serde::{Deserialize, Deserializer};
+
+/// Model used to derive the strongest signal a witness could plausibly have
+/// received a beacon at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RssiModel {
+    /// Free space path loss between the asserted beaconer and witness
+    /// locations
+    FreeSpace,
+    /// Free space path loss less the diffraction loss of terrain and earth
+    /// curvature obstructing the path between the beaconer and witness
+    Terrain,
+    /// Witness signal strength is not verified
+    Disabled,
+}
+
+/// The thresholds applied by the beacon and witness verifications
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PocRules {
+    /// The cadence at which hotspots are permitted to beacon (in seconds).
+    /// Default 6 hours
+    #[serde(default = "default_beacon_interval")]
+    pub beacon_interval: i64,
+    /// Tolerance applied to beacon intervals within which beacons will be
+    /// accepted (in seconds). Default 10 minutes
+    #[serde(default = "default_beacon_interval_tolerance")]
+    pub beacon_interval_tolerance: i64,
+    /// Max permitted distance of a witness from a beaconer (in km). Default 100
+    #[serde(default = "default_max_witness_distance_km")]
+    pub max_witness_distance_km: u32,
+    /// Min distance in cells between a beaconer and witness. Default 8
+    #[serde(default = "default_min_witness_cell_distance")]
+    pub min_witness_cell_distance: u32,
+    /// The h3 resolution at which the cell distance between a beaconer and
+    /// witness is derived. Default 11
+    #[serde(
+        default = "default_witness_cell_resolution",
+        deserialize_with = "deserialize_resolution"
+    )]
+    pub witness_cell_resolution: Resolution,
+    /// Max permitted difference between the beacon and witness frequency (in
+    /// Hz). Default 100 KHz
+    #[serde(default = "default_max_witness_freq_diff_hz")]
+    pub max_witness_freq_diff_hz: u64,
+    /// Model used to verify witness signal strength. Default free_space
+    #[serde(default = "default_rssi_model")]
+    pub rssi_model: RssiModel,
+    /// Margin (in dBm) a witness signal may exceed the modelled maximum by.
+    /// Default 0
+    #[serde(default)]
+    pub rssi_margin_db: f64,
+    /// Max deviation (in ms) of a witness receive time from the beacon
+    /// transmit time plus the time of flight between them. Default 0, disabled
+    #[serde(default)]
+    pub max_witness_clock_skew_ms: u64,
+    /// Min spread (in microseconds) of the time of flight adjusted receive
+    /// times of a group of witnesses, below which they are taken to come
+    /// from a single clock. Default 0, disabled
+    #[serde(default)]
+    pub min_witness_timing_spread_us: u64,
+    /// Number of witnesses making up a timing cluster. Default 3
+    #[serde(default = "default_witness_timing_cluster_size")]
+    pub witness_timing_cluster_size: usize,
+}
+
+fn default_beacon_interval() -> i64 {
+    6 * 60 * 60
+}
+
+fn default_beacon_interval_tolerance() -> i64 {
+    10 * 60
+}
+
+fn default_max_witness_distance_km() -> u32 {
+    100
+}
+
+fn default_min_witness_cell_distance() -> u32 {
+    8
+}
+
+fn default_witness_cell_resolution() -> Resolution {
+    Resolution::Eleven
+}
+
+fn default_max_witness_freq_diff_hz() -> u64 {
+    100_000
+}
+
+fn default_rssi_model() -> RssiModel {
+    RssiModel::FreeSpace
+}
+
+fn default_witness_timing_cluster_size() -> usize {
+    3
+}
+
+impl Default for PocRules {
+    fn default() -> Self {
+        Self {
+            beacon_interval: default_beacon_interval(),
+            beacon_interval_tolerance: default_beacon_interval_tolerance(),
+            max_witness_distance_km: default_max_witness_distance_km(),
+            min_witness_cell_distance: default_min_witness_cell_distance(),
+            witness_cell_resolution: default_witness_cell_resolution(),
+            max_witness_freq_diff_hz: default_max_witness_freq_diff_hz(),
+            rssi_model: default_rssi_model(),
+            rssi_margin_db: 0.0,
+            max_witness_clock_skew_ms: 0,
+            min_witness_timing_spread_us: 0,
+            witness_timing_cluster_size: default_witness_timing_cluster_size(),
+        }
+    }
+}
+
+impl PocRules {
+    pub fn beacon_interval(&self) -> Duration {
+        Duration::seconds(self.beacon_interval)
+    }
+
+    pub fn beacon_interval_tolerance(&self) -> Duration {
+        Duration::seconds(self.beacon_interval_tolerance)
+    }
+}
+
+/// A scheduled change to the poc rules, applying to reports received at or
+/// after `activation_time`. Rules not set in the change keep the value in
+/// effect before it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PocRuleChange {
+    pub activation_time: DateTime<Utc>,
+    pub beacon_interval: Option<i64>,
+    pub beacon_interval_tolerance: Option<i64>,
+    pub max_witness_distance_km: Option<u32>,
+    pub min_witness_cell_distance: Option<u32>,
+    #[serde(default, deserialize_with = "deserialize_optional_resolution")]
+    pub witness_cell_resolution: Option<Resolution>,
+    pub max_witness_freq_diff_hz: Option<u64>,
+    pub rssi_model: Option<RssiModel>,
+    pub rssi_margin_db: Option<f64>,
+    pub max_witness_clock_skew_ms: Option<u64>,
+    pub min_witness_timing_spread_us: Option<u64>,
+    pub witness_timing_cluster_size: Option<usize>,
+}
+
+impl PocRuleChange {
+    fn apply(&self, rules: &mut PocRules) {
+        macro_rules! apply {
+            ($($field:ident),*) => {
+                $(if let Some(value) = self.$field {
+                    rules.$field = value;
+                })*
+            };
+        }
+        apply!(
+            beacon_interval,
+            beacon_interval_tolerance,
+            max_witness_distance_km,
+            min_witness_cell_distance,
+            witness_cell_resolution,
+            max_witness_freq_diff_hz,
+            rssi_model,
+            rssi_margin_db,
+            max_witness_clock_skew_ms,
+            min_witness_timing_spread_us,
+            witness_timing_cluster_size
+        );
+    }
+}
+
+/// The poc rules in effect over time
+#[derive(Debug, Clone, Default)]
+pub struct PocRuleSchedule {
+    rules: PocRules,
+    changes: Vec<PocRuleChange>,
+}
+
+impl PocRuleSchedule {
+    pub fn new(rules: PocRules, mut changes: Vec<PocRuleChange>) -> Self {
+        changes.sort_by_key(|change| change.activation_time);
+        Self { rules, changes }
+    }
+
+    /// The rules in effect for a report received at the given time
+    pub fn rules_at(&self, timestamp: DateTime<Utc>) -> PocRules {
+        let mut rules = self.rules.clone();
+        self.changes
+            .iter()
+            .take_while(|change| change.activation_time <= timestamp)
+            .for_each(|change| change.apply(&mut rules));
+        rules
+    }
+}
+
+fn deserialize_resolution<'de, D>(deserializer: D) -> Result<Resolution, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let resolution = u8::deserialize(deserializer)?;
+    Resolution::try_from(resolution).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_optional_resolution<'de, D>(deserializer: D) -> Result<Option<Resolution>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_resolution(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn applies_changes_from_their_activation_time() {
+        let activation = Utc.timestamp_opt(1_680_000_000, 0).single().unwrap();
+        let later = activation + Duration::days(7);
+        let schedule = PocRuleSchedule::new(
+            PocRules::default(),
+            vec![
+                PocRuleChange {
+                    activation_time: later,
+                    beacon_interval: None,
+                    beacon_interval_tolerance: None,
+                    max_witness_distance_km: None,
+                    min_witness_cell_distance: Some(10),
+                    witness_cell_resolution: None,
+                    max_witness_freq_diff_hz: None,
+                    rssi_model: Some(RssiModel::Disabled),
+                    rssi_margin_db: None,
+                    max_witness_clock_skew_ms: None,
+                    min_witness_timing_spread_us: None,
+                    witness_timing_cluster_size: None,
+                },
+                PocRuleChange {
+                    activation_time: activation,
+                    beacon_interval: None,
+                    beacon_interval_tolerance: None,
+                    max_witness_distance_km: Some(80),
+                    min_witness_cell_distance: None,
+                    witness_cell_resolution: None,
+                    max_witness_freq_diff_hz: None,
+                    rssi_model: None,
+                    rssi_margin_db: None,
+                    max_witness_clock_skew_ms: None,
+                    min_witness_timing_spread_us: None,
+                    witness_timing_cluster_size: None,
+                },
+            ],
+        );
+
+        let before = schedule.rules_at(activation - Duration::milliseconds(1));
+        assert_eq!(PocRules::default(), before);
+
+        let at = schedule.rules_at(activation);
+        assert_eq!(80, at.max_witness_distance_km);
+        assert_eq!(8, at.min_witness_cell_distance);
+        assert_eq!(RssiModel::FreeSpace, at.rssi_model);
+
+        let after = schedule.rules_at(later);
+        assert_eq!(80, after.max_witness_distance_km);
+        assert_eq!(10, after.min_witness_cell_distance);
+        assert_eq!(RssiModel::Disabled, after.rssi_model);
+    }
+}
@@ -1,4 +1,6 @@
-use crate::{entropy::Entropy, metrics::Metrics, poc_report::Report, Settings};
+use crate::{
+    entropy::Entropy, history::HistoryEntry, metrics::Metrics, poc_report::Report, Settings,
+};
 use chrono::Duration;
 use file_store::{
     file_sink::{self, FileSinkClient},
@@ -39,6 +41,8 @@ pub struct Purger {
     cache: String,
     output: file_store::Settings,
     base_stale_period: Duration,
+    history_max_entries: i64,
+    history_max_age: Duration,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -55,6 +59,8 @@ impl Purger {
             cache,
             output,
             base_stale_period,
+            history_max_entries: settings.history.max_entries,
+            history_max_age: settings.history.max_age(),
         })
     }
 
@@ -180,6 +186,11 @@ impl Purger {
 
         // purge any stale entropy, no need to output anything to s3 here
         _ = Entropy::purge(&self.pool, self.base_stale_period + *ENTROPY_STALE_PERIOD).await;
+
+        // prune the hotspot history to the most recent entries
+        let pruned =
+            HistoryEntry::purge(&self.pool, self.history_max_entries, self.history_max_age).await?;
+        tracing::info!("pruned {pruned} hotspot history entries");
         Ok(())
     }
 
@@ -207,8 +218,11 @@ impl Purger {
                 &[("reason", InvalidReason::Stale.as_str_name())],
             )
             .await?;
+        let history = HistoryEntry::invalid_beacon(&beacon_report, InvalidReason::Stale);
+        let mut tx = tx.lock().await;
+        HistoryEntry::bulk_insert(tx.deref_mut(), vec![history]).await?;
         // delete the report from the DB
-        Report::delete_report(tx.lock().await.deref_mut(), &beacon_id).await?;
+        Report::delete_report(tx.deref_mut(), &beacon_id).await?;
         Metrics::decrement_num_beacons();
         Ok(())
     }
@@ -223,6 +237,11 @@ impl Purger {
         let witness_report = IotWitnessIngestReport::decode(witness_buf)?;
         let witness_id = witness_report.ingest_id();
         let received_timestamp = witness_report.received_timestamp;
+        let history = HistoryEntry::invalid_witness(
+            &witness_report,
+            InvalidReason::Stale,
+            InvalidParticipantSide::Witness,
+        );
         let invalid_witness_report_proto: LoraInvalidWitnessReportV1 = IotInvalidWitnessReport {
             received_timestamp,
             report: witness_report.report,
@@ -238,8 +257,10 @@ impl Purger {
             )
             .await?;
 
+        let mut tx = tx.lock().await;
+        HistoryEntry::bulk_insert(tx.deref_mut(), vec![history]).await?;
         // delete the report from the DB
-        Report::delete_report(tx.lock().await.deref_mut(), &witness_id).await?;
+        Report::delete_report(tx.deref_mut(), &witness_id).await?;
         Ok(())
     }
 }
@@ -0,0 +1,307 @@
+//! Previews of the rewards of a window of gateway shares, computed exactly as
+//! the rewarder would compute them but without writing to the reward sinks or
+//! clearing the rewarded shares from the database. Previews are either printed
+//! for an arbitrary window by the `reward-preview` command or, in shadow mode,
+//! written periodically to a separate bucket for the window since the last
+//! rewarded period.
+use crate::{
+    reward_share::{operational_rewards, GatewayShares, IotEmissionSchedule},
+    rewarder::fetch_rewarded_timestamp,
+    Settings,
+};
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use db_store::meta::keys;
+use file_store::{
+    file_sink::{self, FileSink, FileSinkClient},
+    file_upload::{self, FileUpload},
+    FileType,
+};
+use futures::TryFutureExt;
+use helium_crypto::PublicKeyBinary;
+use helium_proto::{
+    services::poc_lora::{self as proto, iot_reward_share::Reward as ProtoReward},
+    BlockchainTokenTypeV1,
+};
+use price::PriceTracker;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::{ops::Range, path::Path};
+use tokio::time::{self, MissedTickBehavior};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RewardPreviewSettings {
+    /// Interval between shadow previews (in minutes). Default 60
+    #[serde(default = "default_interval")]
+    pub interval: i64,
+    /// Bucket the previews are written to. Must not be the rewards output
+    pub output: file_store::Settings,
+}
+
+fn default_interval() -> i64 {
+    60
+}
+
+impl RewardPreviewSettings {
+    pub fn interval(&self) -> time::Duration {
+        time::Duration::from_secs(60 * self.interval as u64)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HotspotRewards {
+    pub hotspot_key: String,
+    pub beacon_amount: u64,
+    pub witness_amount: u64,
+    pub dc_transfer_amount: u64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RewardTotals {
+    pub beacon_amount: u64,
+    pub witness_amount: u64,
+    pub dc_transfer_amount: u64,
+    pub operations_amount: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RewardPreview {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub iot_price: u64,
+    pub emission_schedule_version: String,
+    pub totals: RewardTotals,
+    pub hotspots: Vec<HotspotRewards>,
+    /// The reward shares as the rewarder would have written them
+    #[serde(skip)]
+    pub reward_shares: Vec<proto::IotRewardShare>,
+}
+
+impl RewardPreview {
+    pub async fn compute(
+        pool: &PgPool,
+        schedule: &IotEmissionSchedule,
+        period: &Range<DateTime<Utc>>,
+        iot_price: u64,
+    ) -> Result<Self, sqlx::Error> {
+        let gateway_shares = GatewayShares::aggregate(pool, period).await?;
+        let reward_shares: Vec<_> = gateway_shares
+            .into_iot_reward_shares(schedule, period, Decimal::from(iot_price))
+            .chain(std::iter::once(operational_rewards::compute(
+                schedule, period,
+            )))
+            .collect();
+
+        let mut totals = RewardTotals::default();
+        let mut hotspots = Vec::new();
+        for reward_share in &reward_shares {
+            match &reward_share.reward {
+                Some(ProtoReward::GatewayReward(reward)) => {
+                    totals.beacon_amount += reward.beacon_amount;
+                    totals.witness_amount += reward.witness_amount;
+                    totals.dc_transfer_amount += reward.dc_transfer_amount;
+                    hotspots.push(HotspotRewards {
+                        hotspot_key: PublicKeyBinary::from(reward.hotspot_key.clone()).to_string(),
+                        beacon_amount: reward.beacon_amount,
+                        witness_amount: reward.witness_amount,
+                        dc_transfer_amount: reward.dc_transfer_amount,
+                    });
+                }
+                Some(ProtoReward::OperationalReward(reward)) => {
+                    totals.operations_amount += reward.amount;
+                }
+                _ => (),
+            }
+        }
+        totals.total = totals.beacon_amount
+            + totals.witness_amount
+            + totals.dc_transfer_amount
+            + totals.operations_amount;
+        hotspots.sort_by(|a, b| a.hotspot_key.cmp(&b.hotspot_key));
+
+        Ok(Self {
+            start: period.start,
+            end: period.end,
+            iot_price,
+            emission_schedule_version: schedule.version.clone(),
+            totals,
+            hotspots,
+            reward_shares,
+        })
+    }
+}
+
+/// Print the rewards of the gateway shares of a window as json, without
+/// writing rewards or modifying the database
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    #[clap(long)]
+    start: NaiveDateTime,
+    #[clap(long)]
+    end: NaiveDateTime,
+    /// Iot price to apply to data transfer rewards. Defaults to the price
+    /// currently tracked from the price oracle
+    #[clap(long)]
+    iot_price: Option<u64>,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        let start = DateTime::from_utc(self.start, Utc);
+        let end = DateTime::from_utc(self.end, Utc);
+
+        let (shutdown_trigger, shutdown) = triggered::trigger();
+        let (pool, _db_handle) = settings
+            .database
+            .connect(env!("CARGO_PKG_NAME"), shutdown.clone())
+            .await?;
+
+        let iot_price = match self.iot_price {
+            Some(iot_price) => iot_price,
+            None => {
+                let (price_tracker, _price_receiver) =
+                    PriceTracker::start(&settings.price_tracker, shutdown.clone()).await?;
+                price_tracker.price(&BlockchainTokenTypeV1::Iot).await?
+            }
+        };
+
+        let preview =
+            RewardPreview::compute(&pool, &settings.emission_schedule, &(start..end), iot_price)
+                .await;
+        shutdown_trigger.trigger();
+
+        println!("{}", serde_json::to_string_pretty(&preview?)?);
+        Ok(())
+    }
+}
+
+/// Shadow mode, periodically writing the preview of the rewards since the
+/// last rewarded period to a separate bucket
+pub struct RewardPreviewer {
+    previews: ShadowPreviews,
+    previews_sink_server: FileSink,
+    file_upload: FileUpload,
+    price_tracker: price::price_tracker::Settings,
+}
+
+struct ShadowPreviews {
+    pool: PgPool,
+    emission_schedule: IotEmissionSchedule,
+    interval: time::Duration,
+    previews_sink: FileSinkClient,
+}
+
+impl RewardPreviewer {
+    pub async fn from_settings(
+        settings: &Settings,
+        pool: PgPool,
+        shutdown: triggered::Listener,
+    ) -> Result<Option<Self>> {
+        let Some(preview_settings) = &settings.reward_preview else {
+            return Ok(None);
+        };
+
+        let (file_upload_tx, file_upload_rx) = file_upload::message_channel();
+        let file_upload =
+            FileUpload::from_settings(&preview_settings.output, file_upload_rx).await?;
+
+        let store_base_path = Path::new(&settings.cache).join("reward_preview");
+        let (previews_sink, previews_sink_server) = file_sink::FileSinkBuilder::new(
+            FileType::IotRewardSharePreview,
+            &store_base_path,
+            concat!(env!("CARGO_PKG_NAME"), "_reward_preview"),
+            shutdown,
+        )
+        .deposits(Some(file_upload_tx))
+        .auto_commit(false)
+        .create()
+        .await?;
+
+        Ok(Some(Self {
+            previews: ShadowPreviews {
+                pool,
+                emission_schedule: settings.emission_schedule.clone(),
+                interval: preview_settings.interval(),
+                previews_sink,
+            },
+            previews_sink_server,
+            file_upload,
+            price_tracker: settings.price_tracker.clone(),
+        }))
+    }
+
+    pub async fn run(self, shutdown: &triggered::Listener) -> Result<()> {
+        tracing::info!("starting reward previewer");
+        let Self {
+            mut previews,
+            mut previews_sink_server,
+            file_upload,
+            price_tracker,
+        } = self;
+        // a price tracker of its own, as the tracker kills itself on asking
+        // for an unavailable price and that must not take down the rewarder
+        let (price_tracker, price_receiver) =
+            PriceTracker::start(&price_tracker, shutdown.clone()).await?;
+
+        tokio::try_join!(
+            previews_sink_server.run().map_err(anyhow::Error::from),
+            file_upload.run(shutdown).map_err(anyhow::Error::from),
+            async {
+                tokio::select! {
+                    result = previews.run(price_tracker, shutdown) => result,
+                    result = price_receiver => {
+                        if let Err(err) = result {
+                            tracing::warn!("stopping reward previews, price tracker failed: {err}");
+                        }
+                        Ok(())
+                    }
+                }
+            },
+        )
+        .map(|_| ())
+    }
+}
+
+impl ShadowPreviews {
+    async fn run(
+        &mut self,
+        price_tracker: PriceTracker,
+        shutdown: &triggered::Listener,
+    ) -> Result<()> {
+        let mut preview_timer = time::interval(self.interval);
+        preview_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            tokio::select! {
+                _ = shutdown.clone() => return Ok(()),
+                _ = preview_timer.tick() => (),
+            }
+
+            let iot_price = match price_tracker.price(&BlockchainTokenTypeV1::Iot).await {
+                Ok(iot_price) => iot_price,
+                Err(err) => {
+                    tracing::warn!("stopping reward previews, iot price unavailable: {err}");
+                    return Ok(());
+                }
+            };
+            let start = fetch_rewarded_timestamp(keys::LAST_REWARDED_END_TIME, &self.pool).await?;
+            let period = start..Utc::now();
+            let preview =
+                RewardPreview::compute(&self.pool, &self.emission_schedule, &period, iot_price)
+                    .await?;
+            tracing::info!(
+                "reward preview for {:?} with iot_price: {iot_price}, hotspots: {}, total: {}",
+                period,
+                preview.hotspots.len(),
+                preview.totals.total
+            );
+
+            for reward_share in preview.reward_shares {
+                self.previews_sink.write(reward_share, []).await?;
+            }
+            self.previews_sink.commit().await?;
+        }
+    }
+}
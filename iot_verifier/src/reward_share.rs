@@ -1,72 +1,99 @@
 use crate::poc_report::ReportType as PocReportType;
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, TimeZone, Utc};
 use file_store::{iot_packet::IotValidPacket, iot_valid_poc::IotPoc, traits::TimestampEncode};
 use futures::stream::TryStreamExt;
 use helium_crypto::PublicKeyBinary;
 use helium_proto::services::poc_lora as proto;
 use helium_proto::services::poc_lora::iot_reward_share::Reward as ProtoReward;
 use lazy_static::lazy_static;
+use reward_scheduler::{emissions::ScheduleEntry, EmissionSchedule};
 use rust_decimal::prelude::*;
 use rust_decimal_macros::dec;
+use serde::Deserialize;
 use sqlx::{Postgres, Transaction};
 use std::{collections::HashMap, ops::Range};
 
 const DEFAULT_PREC: u32 = 15;
 
-// rewards in IoT Bones ( iot @ 10^6 ) per 24 hours based on emission curve year 1
-// TODO: expand to cover the full multi-year emission curve
 lazy_static! {
-    // TODO: year 1 emissions allocate 30% of total to PoC with 6% to beacons and 24% to witnesses but subsequent years back
-    // total PoC percentage off 1.5% each year; determine how beacons and witnesses will split the subsequent years' allocations
-    static ref REWARDS_PER_DAY: Decimal = (Decimal::from(65_000_000_000_u64) / Decimal::from(365)) * Decimal::from(1_000_000); // 178_082_191_780_822
-    static ref BEACON_REWARDS_PER_DAY_PERCENT: Decimal = dec!(0.06);
-    static ref WITNESS_REWARDS_PER_DAY_PERCENT: Decimal = dec!(0.24);
-    // Data transfer is allocated 50% of daily rewards
-    static ref DATA_TRANSFER_REWARDS_PER_DAY_PERCENT: Decimal = dec!(0.50);
-    // Operations fund is allocated 7% of daily rewards
-    static ref OPERATIONS_REWARDS_PER_DAY_PERCENT: Decimal = dec!(0.07);
     // dc remainer distributed at ration of 4:1 in favour of witnesses
-    // ie WITNESS_REWARDS_PER_DAY_PERCENT:BEACON_REWARDS_PER_DAY_PERCENT
+    // ie the beacon and witness allocations of the emission schedule
     static ref WITNESS_DC_REMAINER_PERCENT: Decimal = dec!(0.80);
     static ref BEACON_DC_REMAINER_PERCENT: Decimal = dec!(0.20);
     static ref DC_USD_PRICE: Decimal =  dec!(0.00001);
 }
 
-fn get_tokens_by_duration(tokens: Decimal, duration: Duration) -> Decimal {
-    ((tokens / Decimal::from(Duration::hours(24).num_seconds()))
-        * Decimal::from(duration.num_seconds()))
-    .round_dp_with_strategy(DEFAULT_PREC, RoundingStrategy::MidpointNearestEven)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IotAllocation {
+    Beacon,
+    Witness,
+    DataTransfer,
+    Operations,
+}
+
+pub type IotEmissionSchedule = EmissionSchedule<IotAllocation>;
+
+/// Emissions in iot bones ( iot @ 10^6 ) from the launch of iot on solana.
+/// Subsequent halvings and changes to the allocations are added as new entries
+/// under a new version
+pub fn default_emission_schedule() -> IotEmissionSchedule {
+    EmissionSchedule {
+        version: "iot-v1".to_string(),
+        entries: vec![ScheduleEntry {
+            start: Utc.with_ymd_and_hms(2023, 4, 18, 0, 0, 0).unwrap(),
+            annual_emissions: dec!(65_000_000_000_000_000),
+            // year 1 emissions allocate 30% of total to PoC with 6% to beacons
+            // and 24% to witnesses, 50% to data transfer and 7% to the
+            // operations fund
+            allocations: HashMap::from([
+                (IotAllocation::Beacon, dec!(0.06)),
+                (IotAllocation::Witness, dec!(0.24)),
+                (IotAllocation::DataTransfer, dec!(0.50)),
+                (IotAllocation::Operations, dec!(0.07)),
+            ]),
+        }],
+    }
+}
+
+fn get_scheduled_tokens(
+    schedule: &IotEmissionSchedule,
+    reward_period: &Range<DateTime<Utc>>,
+    allocation: IotAllocation,
+) -> Decimal {
+    schedule
+        .allocated(reward_period, allocation)
+        .round_dp_with_strategy(DEFAULT_PREC, RoundingStrategy::MidpointNearestEven)
 }
 
 fn get_scheduled_poc_tokens(
-    duration: Duration,
+    schedule: &IotEmissionSchedule,
+    reward_period: &Range<DateTime<Utc>>,
     dc_transfer_remainder: Decimal,
 ) -> (Decimal, Decimal) {
     (
-        get_tokens_by_duration(*REWARDS_PER_DAY * *BEACON_REWARDS_PER_DAY_PERCENT, duration)
+        get_scheduled_tokens(schedule, reward_period, IotAllocation::Beacon)
             + (dc_transfer_remainder * *BEACON_DC_REMAINER_PERCENT),
-        get_tokens_by_duration(
-            *REWARDS_PER_DAY * *WITNESS_REWARDS_PER_DAY_PERCENT,
-            duration,
-        ) + (dc_transfer_remainder * *WITNESS_DC_REMAINER_PERCENT),
+        get_scheduled_tokens(schedule, reward_period, IotAllocation::Witness)
+            + (dc_transfer_remainder * *WITNESS_DC_REMAINER_PERCENT),
     )
 }
 
-fn get_scheduled_dc_tokens(duration: Duration) -> Decimal {
-    get_tokens_by_duration(
-        *REWARDS_PER_DAY * *DATA_TRANSFER_REWARDS_PER_DAY_PERCENT,
-        duration,
-    )
+fn get_scheduled_dc_tokens(
+    schedule: &IotEmissionSchedule,
+    reward_period: &Range<DateTime<Utc>>,
+) -> Decimal {
+    get_scheduled_tokens(schedule, reward_period, IotAllocation::DataTransfer)
 }
 
-fn get_scheduled_ops_fund_tokens(duration: Duration) -> u64 {
-    get_tokens_by_duration(
-        *REWARDS_PER_DAY * *OPERATIONS_REWARDS_PER_DAY_PERCENT,
-        duration,
-    )
-    .round_dp_with_strategy(0, RoundingStrategy::ToZero)
-    .to_u64()
-    .unwrap_or(0)
+fn get_scheduled_ops_fund_tokens(
+    schedule: &IotEmissionSchedule,
+    reward_period: &Range<DateTime<Utc>>,
+) -> u64 {
+    get_scheduled_tokens(schedule, reward_period, IotAllocation::Operations)
+        .round_dp_with_strategy(0, RoundingStrategy::ToZero)
+        .to_u64()
+        .unwrap_or(0)
 }
 
 #[derive(sqlx::FromRow)]
@@ -300,17 +327,17 @@ impl GatewayShares {
         Ok(())
     }
 
-    pub fn into_iot_reward_shares(
+    pub fn into_iot_reward_shares<'a>(
         self,
-        reward_period: &'_ Range<DateTime<Utc>>,
+        schedule: &IotEmissionSchedule,
+        reward_period: &'a Range<DateTime<Utc>>,
         iot_price: Decimal,
-    ) -> impl Iterator<Item = proto::IotRewardShare> + '_ {
+    ) -> impl Iterator<Item = proto::IotRewardShare> + 'a {
         // the total number of shares for beacons, witnesses and data transfer
         // dc shares here is the sum of all spent data transfer DC this epoch
         let (total_beacon_shares, total_witness_shares, total_dc_shares) = self.total_shares();
         // the total number of iot rewards for dc transfer this epoch
-        let total_dc_transfer_rewards =
-            get_scheduled_dc_tokens(reward_period.end - reward_period.start);
+        let total_dc_transfer_rewards = get_scheduled_dc_tokens(schedule, reward_period);
 
         // convert the total spent data transfer DC to it equiv iot bone value
         // the rewards distributed to gateways will be equal to this
@@ -325,10 +352,8 @@ impl GatewayShares {
             );
         // the total amounts of iot rewards this epoch for beacons, witnesses
         // taking into account any remaining dc transfer rewards
-        let (total_beacon_rewards, total_witness_rewards) = get_scheduled_poc_tokens(
-            reward_period.end - reward_period.start,
-            dc_transfer_rewards_unused,
-        );
+        let (total_beacon_rewards, total_witness_rewards) =
+            get_scheduled_poc_tokens(schedule, reward_period, dc_transfer_rewards_unused);
 
         // work out the rewards per share for beacons, witnesses and dc transfer
         let beacon_rewards_per_share = rewards_per_share(total_beacon_rewards, total_beacon_shares);
@@ -377,9 +402,12 @@ impl GatewayShares {
 pub mod operational_rewards {
     use super::*;
 
-    pub fn compute(reward_period: &Range<DateTime<Utc>>) -> proto::IotRewardShare {
+    pub fn compute(
+        schedule: &IotEmissionSchedule,
+        reward_period: &Range<DateTime<Utc>>,
+    ) -> proto::IotRewardShare {
         let op_fund_reward = proto::OperationalReward {
-            amount: get_scheduled_ops_fund_tokens(reward_period.end - reward_period.start),
+            amount: get_scheduled_ops_fund_tokens(schedule, reward_period),
         };
         proto::IotRewardShare {
             start_period: reward_period.start.encode_timestamp(),
@@ -449,6 +477,7 @@ fn compute_rewards(rewards_per_share: Decimal, shares: Decimal) -> u64 {
 #[cfg(test)]
 mod test {
     use super::*;
+    use chrono::Duration;
 
     fn reward_shares_in_dec(
         beacon_shares: Decimal,
@@ -468,14 +497,43 @@ mod test {
 
     #[test]
     fn test_non_gateway_reward_shares() {
-        let epoch_duration = Duration::hours(1);
-        let total_tokens_for_period = *REWARDS_PER_DAY / dec!(24);
+        let schedule = default_emission_schedule();
+        let now = Utc::now();
+        let reward_period = (now - Duration::hours(1))..now;
+        let total_tokens_for_period = schedule.emissions(&reward_period);
         println!("total_tokens_for_period: {total_tokens_for_period}");
 
-        let operation_tokens_for_period = get_scheduled_ops_fund_tokens(epoch_duration);
+        let operation_tokens_for_period = get_scheduled_ops_fund_tokens(&schedule, &reward_period);
         assert_eq!(519406392694, operation_tokens_for_period);
     }
 
+    #[test]
+    fn test_reward_period_straddling_schedule_change() {
+        let mut schedule = default_emission_schedule();
+        let halving = Utc.with_ymd_and_hms(2025, 8, 1, 0, 0, 0).unwrap();
+        let mut halved = schedule.entries[0].clone();
+        halved.start = halving;
+        halved.annual_emissions /= dec!(2);
+        schedule.entries.push(halved);
+        schedule.version = "iot-test".to_string();
+
+        let before = (halving - Duration::hours(1))..halving;
+        let after = halving..(halving + Duration::hours(1));
+        let straddling = (halving - Duration::hours(1))..(halving + Duration::hours(1));
+        assert_eq!(
+            519406392694,
+            get_scheduled_ops_fund_tokens(&schedule, &before)
+        );
+        assert_eq!(
+            259703196347,
+            get_scheduled_ops_fund_tokens(&schedule, &after)
+        );
+        assert_eq!(
+            519406392694 + 259703196347,
+            get_scheduled_ops_fund_tokens(&schedule, &straddling)
+        );
+    }
+
     #[test]
     // test reward distribution where there is a fixed dc spend per gateway
     // with the total dc spend across all gateways being significantly lower than the
@@ -502,9 +560,11 @@ mod test {
             .parse()
             .expect("failed gw6 parse");
 
+        let schedule = default_emission_schedule();
         let now = Utc::now();
         let reward_period = (now - Duration::minutes(10))..now;
-        let total_data_transfer_tokens_for_period = get_scheduled_dc_tokens(Duration::minutes(10));
+        let total_data_transfer_tokens_for_period =
+            get_scheduled_dc_tokens(&schedule, &reward_period);
         println!("total data transfer scheduled tokens: {total_data_transfer_tokens_for_period}");
 
         let gw1_dc_spend = dec!(502);
@@ -555,7 +615,7 @@ mod test {
         let gw_shares = GatewayShares { shares };
         let mut rewards: HashMap<PublicKeyBinary, proto::GatewayReward> = HashMap::new();
         let gw_reward_shares: Vec<proto::IotRewardShare> = gw_shares
-            .into_iot_reward_shares(&reward_period, iot_price)
+            .into_iot_reward_shares(&schedule, &reward_period, iot_price)
             .collect();
         for reward in gw_reward_shares {
             if let Some(ProtoReward::GatewayReward(gateway_reward)) = reward.reward {
@@ -649,7 +709,7 @@ mod test {
             + gw6_rewards.witness_amount;
 
         let (exp_total_beacon_tokens, exp_total_witness_tokens) =
-            get_scheduled_poc_tokens(Duration::minutes(10), total_unused_data_transfer_tokens);
+            get_scheduled_poc_tokens(&schedule, &reward_period, total_unused_data_transfer_tokens);
         let exp_sum_poc_tokens = exp_total_beacon_tokens + exp_total_witness_tokens;
         println!("max poc rewards: {exp_sum_poc_tokens}");
         println!("total actual poc rewards distributed: {sum_poc_amounts}");
@@ -684,9 +744,11 @@ mod test {
             .parse()
             .expect("failed gw6 parse");
 
+        let schedule = default_emission_schedule();
         let now = Utc::now();
         let reward_period = (now - Duration::minutes(10))..now;
-        let total_data_transfer_tokens_for_period = get_scheduled_dc_tokens(Duration::minutes(10));
+        let total_data_transfer_tokens_for_period =
+            get_scheduled_dc_tokens(&schedule, &reward_period);
         println!("total data transfer scheduled tokens: {total_data_transfer_tokens_for_period}");
 
         // get the expected total amount of dc we need to spend
@@ -741,7 +803,7 @@ mod test {
         let gw_shares = GatewayShares { shares };
         let mut rewards: HashMap<PublicKeyBinary, proto::GatewayReward> = HashMap::new();
         let gw_reward_shares: Vec<proto::IotRewardShare> = gw_shares
-            .into_iot_reward_shares(&reward_period, iot_price)
+            .into_iot_reward_shares(&schedule, &reward_period, iot_price)
             .collect();
         for reward in gw_reward_shares {
             if let Some(ProtoReward::GatewayReward(gateway_reward)) = reward.reward {
@@ -827,7 +889,7 @@ mod test {
             + gw6_rewards.beacon_amount
             + gw6_rewards.witness_amount;
         let (exp_total_beacon_tokens, exp_total_witness_tokens) =
-            get_scheduled_poc_tokens(Duration::minutes(10), Decimal::ZERO);
+            get_scheduled_poc_tokens(&schedule, &reward_period, Decimal::ZERO);
         let exp_sum_poc_tokens = exp_total_beacon_tokens + exp_total_witness_tokens;
         println!("max poc rewards: {exp_sum_poc_tokens}");
         println!("total actual poc rewards distributed: {sum_poc_amounts}");
@@ -862,9 +924,11 @@ mod test {
             .parse()
             .expect("failed gw6 parse");
 
+        let schedule = default_emission_schedule();
         let now = Utc::now();
         let reward_period = (now - Duration::minutes(10))..now;
-        let total_data_transfer_tokens_for_period = get_scheduled_dc_tokens(Duration::minutes(10));
+        let total_data_transfer_tokens_for_period =
+            get_scheduled_dc_tokens(&schedule, &reward_period);
         println!("total_data_transfer_tokens_for_period: {total_data_transfer_tokens_for_period}");
 
         // get the expected total amount of dc we need to spend
@@ -911,7 +975,7 @@ mod test {
         let gw_shares = GatewayShares { shares };
         let mut rewards: HashMap<PublicKeyBinary, proto::GatewayReward> = HashMap::new();
         let gw_reward_shares: Vec<proto::IotRewardShare> = gw_shares
-            .into_iot_reward_shares(&reward_period, iot_price)
+            .into_iot_reward_shares(&schedule, &reward_period, iot_price)
             .collect();
         for reward in gw_reward_shares {
             if let Some(ProtoReward::GatewayReward(gateway_reward)) = reward.reward {
@@ -993,8 +1057,11 @@ mod test {
         let expected_data_transfer_tokens_for_poc = total_data_transfer_tokens_for_period
             - Decimal::from_u64(sum_data_transfer_amounts).unwrap();
         println!("expected_data_transfer_tokens_for_poc: {expected_data_transfer_tokens_for_poc}");
-        let (exp_total_beacon_tokens, exp_total_witness_tokens) =
-            get_scheduled_poc_tokens(Duration::minutes(10), expected_data_transfer_tokens_for_poc);
+        let (exp_total_beacon_tokens, exp_total_witness_tokens) = get_scheduled_poc_tokens(
+            &schedule,
+            &reward_period,
+            expected_data_transfer_tokens_for_poc,
+        );
         let exp_sum_poc_tokens = exp_total_beacon_tokens + exp_total_witness_tokens;
         println!("max poc rewards: {exp_sum_poc_tokens}");
         println!("total actual poc rewards distributed: {sum_poc_amounts}");
@@ -1,6 +1,6 @@
-use crate::reward_share::{operational_rewards, GatewayShares};
+use crate::reward_share::{operational_rewards, GatewayShares, IotEmissionSchedule};
 use chrono::{DateTime, Duration, TimeZone, Utc};
-use db_store::meta;
+use db_store::meta::{keys, MetaKey};
 use file_store::{file_sink, traits::TimestampEncode};
 use helium_proto::RewardManifest;
 use price::PriceTracker;
@@ -18,6 +18,7 @@ pub struct Rewarder {
     pub reward_manifests_sink: file_sink::FileSinkClient,
     pub reward_period_hours: i64,
     pub reward_offset: Duration,
+    pub emission_schedule: IotEmissionSchedule,
 }
 
 impl Rewarder {
@@ -35,8 +36,8 @@ impl Rewarder {
 
             let scheduler = Scheduler::new(
                 reward_period_length,
-                fetch_rewarded_timestamp("last_rewarded_end_time", &self.pool).await?,
-                fetch_rewarded_timestamp("next_rewarded_end_time", &self.pool).await?,
+                fetch_rewarded_timestamp(keys::LAST_REWARDED_END_TIME, &self.pool).await?,
+                fetch_rewarded_timestamp(keys::NEXT_REWARDED_END_TIME, &self.pool).await?,
                 self.reward_offset,
             );
 
@@ -82,9 +83,11 @@ impl Rewarder {
         let gateway_reward_shares =
             GatewayShares::aggregate(&self.pool, &scheduler.reward_period).await?;
 
-        for reward_share in
-            gateway_reward_shares.into_iot_reward_shares(&scheduler.reward_period, iot_price)
-        {
+        for reward_share in gateway_reward_shares.into_iot_reward_shares(
+            &self.emission_schedule,
+            &scheduler.reward_period,
+            iot_price,
+        ) {
             self.rewards_sink
                 .write(reward_share, [])
                 .await?
@@ -93,7 +96,10 @@ impl Rewarder {
         }
 
         self.rewards_sink
-            .write(operational_rewards::compute(&scheduler.reward_period), [])
+            .write(
+                operational_rewards::compute(&self.emission_schedule, &scheduler.reward_period),
+                [],
+            )
             .await?
             // Await the returned oneshot to ensure we wrote the file
             .await??;
@@ -103,17 +109,20 @@ impl Rewarder {
         // Clear gateway shares table period to end of reward period
         GatewayShares::clear_rewarded_shares(&mut transaction, scheduler.reward_period.end).await?;
         save_rewarded_timestamp(
-            "last_rewarded_end_time",
+            keys::LAST_REWARDED_END_TIME,
             &scheduler.reward_period.end,
             &mut transaction,
         )
         .await?;
         save_rewarded_timestamp(
-            "next_rewarded_end_time",
+            keys::NEXT_REWARDED_END_TIME,
             &scheduler.next_reward_period().end,
             &mut transaction,
         )
         .await?;
+        keys::LAST_REWARDED_EMISSION_SCHEDULE_VERSION
+            .store(&mut transaction, self.emission_schedule.version.clone())
+            .await?;
         transaction.commit().await?;
 
         // now that the db has been purged, safe to write out the manifest
@@ -129,6 +138,11 @@ impl Rewarder {
             .await?
             .await??;
         self.reward_manifests_sink.commit().await?;
+        tracing::info!(
+            "rewarded period ending {} with emission schedule version {}",
+            scheduler.reward_period.end,
+            self.emission_schedule.version
+        );
         metrics::gauge!(
             "last_rewarded_end_time",
             scheduler.reward_period.end.timestamp() as f64
@@ -172,28 +186,23 @@ impl Rewarder {
     }
 
     async fn disable_complete_data_checks_until(&self) -> db_store::Result<DateTime<Utc>> {
-        Utc.timestamp_opt(
-            meta::fetch(&self.pool, "disable_complete_data_checks_until").await?,
-            0,
-        )
-        .single()
-        .ok_or(db_store::Error::DecodeError)
+        fetch_rewarded_timestamp(keys::DISABLE_COMPLETE_DATA_CHECKS_UNTIL, &self.pool).await
     }
 }
 
-async fn fetch_rewarded_timestamp(
-    timestamp_key: &str,
+pub(crate) async fn fetch_rewarded_timestamp(
+    timestamp_key: MetaKey<i64>,
     db: impl PgExecutor<'_>,
 ) -> db_store::Result<DateTime<Utc>> {
-    Utc.timestamp_opt(meta::fetch(db, timestamp_key).await?, 0)
+    Utc.timestamp_opt(timestamp_key.fetch(db).await?, 0)
         .single()
         .ok_or(db_store::Error::DecodeError)
 }
 
 async fn save_rewarded_timestamp(
-    timestamp_key: &str,
+    timestamp_key: MetaKey<i64>,
     value: &DateTime<Utc>,
     db: impl PgExecutor<'_>,
 ) -> db_store::Result<()> {
-    meta::store(db, timestamp_key, value.timestamp()).await
+    timestamp_key.store(db, value.timestamp()).await
 }
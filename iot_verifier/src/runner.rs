@@ -1,7 +1,17 @@
 use crate::{
-    gateway_cache::GatewayCache, hex_density::HexDensityMap, last_beacon::LastBeacon,
-    metrics::Metrics, poc::Poc, poc_report::Report, region_cache::RegionCache,
-    reward_share::GatewayPocShare, Settings,
+    collusion,
+    gateway_cache::GatewayCache,
+    hex_density::HexDensityMap,
+    history::HistoryEntry,
+    last_beacon::LastBeacon,
+    metrics::Metrics,
+    poc::Poc,
+    poc_report::Report,
+    poc_rules::PocRuleSchedule,
+    region_cache::RegionCache,
+    reward_share::GatewayPocShare,
+    terrain::{Terrain, TerrainError},
+    Settings,
 };
 use chrono::{Duration as ChronoDuration, Utc};
 use file_store::{
@@ -20,12 +30,13 @@ use helium_proto::services::poc_lora::{
     InvalidParticipantSide, InvalidReason, LoraInvalidBeaconReportV1, LoraInvalidWitnessReportV1,
     LoraPocV1, VerificationStatus,
 };
+use iot_config::gateway_info::GatewayInfo;
 use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 use rust_decimal::{Decimal, MathematicalOps};
 use rust_decimal_macros::dec;
 use sha2::{Digest, Sha256};
 use sqlx::PgPool;
-use std::path::Path;
+use std::{path::Path, sync::Arc};
 use tokio::time::{self, MissedTickBehavior};
 
 /// the cadence in seconds at which the DB is polled for ready POCs
@@ -39,16 +50,20 @@ const HIP15_TX_REWARD_UNIT_CAP: Decimal = Decimal::TWO;
 pub struct Runner {
     pool: PgPool,
     cache: String,
-    beacon_interval: ChronoDuration,
-    beacon_interval_tolerance: ChronoDuration,
+    poc_rules: PocRuleSchedule,
+    terrain: Arc<Terrain>,
     max_witnesses_per_poc: u64,
     beacon_max_retries: u64,
     witness_max_retries: u64,
 }
 
 #[derive(thiserror::Error, Debug)]
-#[error("error creating runner: {0}")]
-pub struct NewRunnerError(#[from] db_store::Error);
+pub enum NewRunnerError {
+    #[error("error creating runner: {0}")]
+    DbStore(#[from] db_store::Error),
+    #[error("error loading terrain: {0}")]
+    Terrain(#[from] TerrainError),
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum RunnerError {
@@ -63,16 +78,16 @@ pub enum FilterStatus {
 impl Runner {
     pub async fn from_settings(settings: &Settings, pool: PgPool) -> Result<Self, NewRunnerError> {
         let cache = settings.cache.clone();
-        let beacon_interval = settings.beacon_interval();
-        let beacon_interval_tolerance = settings.beacon_interval_tolerance();
+        let poc_rules = settings.poc_rule_schedule();
+        let terrain = Arc::new(settings.terrain().await?);
         let max_witnesses_per_poc = settings.max_witnesses_per_poc;
         let beacon_max_retries = settings.beacon_max_retries;
         let witness_max_retries = settings.witness_max_retries;
         Ok(Self {
             pool,
             cache,
-            beacon_interval,
-            beacon_interval_tolerance,
+            poc_rules,
+            terrain,
             max_witnesses_per_poc,
             beacon_max_retries,
             witness_max_retries,
@@ -238,7 +253,6 @@ impl Runner {
 
         let beacon_buf: &[u8] = &db_beacon.report_data;
         let beacon_report = IotBeaconIngestReport::decode(beacon_buf)?;
-        let beacon = &beacon_report.report;
         let beacon_received_ts = beacon_report.received_timestamp;
 
         let db_witnesses =
@@ -260,6 +274,8 @@ impl Runner {
             witnesses.clone(),
             entropy_start_time,
             entropy_version,
+            self.poc_rules.rules_at(beacon_received_ts),
+            self.terrain.clone(),
         )
         .await;
 
@@ -270,8 +286,6 @@ impl Runner {
                 gateway_cache,
                 region_cache,
                 &self.pool,
-                self.beacon_interval,
-                self.beacon_interval_tolerance,
             )
             .await?;
         match beacon_verify_result.result {
@@ -298,75 +312,14 @@ impl Runner {
                         return Ok(());
                     };
 
-                    let max_witnesses_per_poc = self.max_witnesses_per_poc as usize;
-                    let beacon_id = beacon.report_id(beacon_received_ts);
-
-                    // filter witnesses into selected and unselected lists
-                    // the selected list will contain only valid witnesses
-                    // up to a max count equal to `max_witnesses_per_poc`
-                    // these witnesses will be rewarded
-                    // the unselected list will contain potentially a mix of
-                    // valid and invalid witnesses
-                    // none of which will be rewarded
-                    // we exclude self witnesses from the unselected lists
-                    // these are dropped to the floor, never make it to s3
-                    let (mut selected_witnesses, invalid_witnesses) =
-                        filter_witnesses(verified_witnesses_result.verified_witnesses);
-
-                    // keep a subset of our selected and valid witnesses
-                    let mut unselected_witnesses = shuffle_and_split_witnesses(
-                        &beacon_id,
-                        &mut selected_witnesses,
-                        max_witnesses_per_poc,
+                    let iot_poc = valid_poc(
+                        &beacon_report,
+                        beacon_info,
+                        beacon_verify_result.hex_scale,
+                        verified_witnesses_result.verified_witnesses,
+                        self.max_witnesses_per_poc as usize,
                     )?;
-
-                    // concat the unselected valid witnesses and the invalid witnesses
-                    // these will then form the unseleted list on the poc
-                    unselected_witnesses =
-                        [&unselected_witnesses[..], &invalid_witnesses[..]].concat();
-
-                    // get the number of valid witnesses in our selected list
-                    let num_valid_selected_witnesses = selected_witnesses.len();
-
-                    // get reward units based on the count of valid selected witnesses
-                    let beaconer_reward_units =
-                        poc_beaconer_reward_unit(num_valid_selected_witnesses as u32)?;
-                    let witness_reward_units =
-                        poc_per_witness_reward_unit(num_valid_selected_witnesses as u32)?;
-                    // update the reward units for those valid witnesses within our selected list
-                    selected_witnesses
-                        .iter_mut()
-                        .for_each(|witness| match witness.status {
-                            VerificationStatus::Valid => witness.reward_unit = witness_reward_units,
-                            VerificationStatus::Invalid => witness.reward_unit = Decimal::ZERO,
-                        });
-
-                    // metadata at this point will always be Some...
-                    let (location, gain, elevation) = match beacon_info.metadata {
-                        Some(metadata) => {
-                            (Some(metadata.location), metadata.gain, metadata.elevation)
-                        }
-                        None => (None, 0, 0),
-                    };
-
-                    let valid_beacon_report = IotValidBeaconReport {
-                        received_timestamp: beacon_received_ts,
-                        location,
-                        gain,
-                        elevation,
-                        hex_scale: beacon_verify_result
-                            .hex_scale
-                            .ok_or(RunnerError::NotFound("invalid hex scaling factor"))?,
-                        report: beacon.clone(),
-                        reward_unit: beaconer_reward_units,
-                    };
-                    self.handle_valid_poc(
-                        valid_beacon_report,
-                        selected_witnesses,
-                        unselected_witnesses,
-                        iot_poc_sink,
-                    )
-                    .await?;
+                    self.handle_valid_poc(iot_poc, iot_poc_sink).await?;
                 }
             }
             VerificationStatus::Invalid => {
@@ -418,6 +371,15 @@ impl Runner {
                 return Ok(());
             }
         }
+        let history = std::iter::once(HistoryEntry::invalid_beacon(beacon_report, invalid_reason))
+            .chain(witness_reports.iter().map(|witness_report| {
+                HistoryEntry::invalid_witness(
+                    witness_report,
+                    invalid_reason,
+                    InvalidParticipantSide::Beaconer,
+                )
+            }))
+            .collect();
         // save invalid witnesses to s3, ignore any failed witness writes
         // taking the lossly approach here as if we re attempt the POC later
         // we will have to clean out any sucessful writes of other witnesses
@@ -445,35 +407,35 @@ impl Runner {
                 }
             }
         }
-        // done with these poc reports, purge em from the db
-        Report::delete_poc(&self.pool, &beacon_id).await?;
+        // record the history and purge the poc reports from the db in one
+        // transaction, so a failed history write leaves the poc to be retried
+        let mut transaction = self.pool.begin().await?;
+        HistoryEntry::bulk_insert(&mut transaction, history).await?;
+        Report::delete_poc(&mut transaction, &beacon_id).await?;
+        transaction.commit().await?;
         Metrics::decrement_num_beacons();
         Ok(())
     }
 
     async fn handle_valid_poc(
         &self,
-        valid_beacon_report: IotValidBeaconReport,
-        selected_witnesses: Vec<IotVerifiedWitnessReport>,
-        unselected_witnesses: Vec<IotVerifiedWitnessReport>,
+        iot_poc: IotPoc,
         iot_poc_sink: &FileSinkClient,
     ) -> anyhow::Result<()> {
+        let valid_beacon_report = &iot_poc.beacon_report;
         let received_timestamp = valid_beacon_report.received_timestamp;
         let pub_key = valid_beacon_report.report.pub_key.clone();
-        let beacon_id = valid_beacon_report.report.report_id(received_timestamp);
         let packet_data = valid_beacon_report.report.data.clone();
         let beacon_report_id = valid_beacon_report.report.report_id(received_timestamp);
-        let iot_poc: IotPoc = IotPoc {
-            poc_id: beacon_id,
-            beacon_report: valid_beacon_report,
-            selected_witnesses: selected_witnesses.clone(),
-            unselected_witnesses: unselected_witnesses.clone(),
-        };
+        let selected_witnesses = iot_poc.selected_witnesses.clone();
+        let unselected_witnesses = iot_poc.unselected_witnesses.clone();
 
         let mut transaction = self.pool.begin().await?;
         for reward_share in GatewayPocShare::shares_from_poc(&iot_poc) {
             reward_share.save(&mut transaction).await?;
         }
+        collusion::record_witness_edges(&mut transaction, &iot_poc).await?;
+        HistoryEntry::bulk_insert(&mut transaction, HistoryEntry::from_poc(&iot_poc)).await?;
         // TODO: expand this transaction to cover all of the database access below?
         transaction.commit().await?;
 
@@ -503,6 +465,76 @@ impl Runner {
     }
 }
 
+/// Assemble the poc for a valid beacon from its verified witnesses, selecting
+/// up to `max_witnesses_per_poc` valid witnesses for rewards
+pub fn valid_poc(
+    beacon_report: &IotBeaconIngestReport,
+    beacon_info: GatewayInfo,
+    hex_scale: Option<Decimal>,
+    verified_witnesses: Vec<IotVerifiedWitnessReport>,
+    max_witnesses_per_poc: usize,
+) -> anyhow::Result<IotPoc> {
+    let beacon = &beacon_report.report;
+    let beacon_received_ts = beacon_report.received_timestamp;
+    let beacon_id = beacon.report_id(beacon_received_ts);
+
+    // filter witnesses into selected and unselected lists
+    // the selected list will contain only valid witnesses
+    // up to a max count equal to `max_witnesses_per_poc`
+    // these witnesses will be rewarded
+    // the unselected list will contain potentially a mix of
+    // valid and invalid witnesses
+    // none of which will be rewarded
+    // we exclude self witnesses from the unselected lists
+    // these are dropped to the floor, never make it to s3
+    let (mut selected_witnesses, invalid_witnesses) = filter_witnesses(verified_witnesses);
+
+    // keep a subset of our selected and valid witnesses
+    let mut unselected_witnesses =
+        shuffle_and_split_witnesses(&beacon_id, &mut selected_witnesses, max_witnesses_per_poc)?;
+
+    // concat the unselected valid witnesses and the invalid witnesses
+    // these will then form the unseleted list on the poc
+    unselected_witnesses = [&unselected_witnesses[..], &invalid_witnesses[..]].concat();
+
+    // get the number of valid witnesses in our selected list
+    let num_valid_selected_witnesses = selected_witnesses.len();
+
+    // get reward units based on the count of valid selected witnesses
+    let beaconer_reward_units = poc_beaconer_reward_unit(num_valid_selected_witnesses as u32)?;
+    let witness_reward_units = poc_per_witness_reward_unit(num_valid_selected_witnesses as u32)?;
+    // update the reward units for those valid witnesses within our selected list
+    selected_witnesses
+        .iter_mut()
+        .for_each(|witness| match witness.status {
+            VerificationStatus::Valid => witness.reward_unit = witness_reward_units,
+            VerificationStatus::Invalid => witness.reward_unit = Decimal::ZERO,
+        });
+
+    // metadata at this point will always be Some...
+    let (location, gain, elevation) = match beacon_info.metadata {
+        Some(metadata) => (Some(metadata.location), metadata.gain, metadata.elevation),
+        None => (None, 0, 0),
+    };
+
+    let valid_beacon_report = IotValidBeaconReport {
+        received_timestamp: beacon_received_ts,
+        location,
+        gain,
+        elevation,
+        hex_scale: hex_scale.ok_or(RunnerError::NotFound("invalid hex scaling factor"))?,
+        report: beacon.clone(),
+        reward_unit: beaconer_reward_units,
+    };
+
+    Ok(IotPoc {
+        poc_id: beacon_id,
+        beacon_report: valid_beacon_report,
+        selected_witnesses,
+        unselected_witnesses,
+    })
+}
+
 fn poc_beaconer_reward_unit(num_witnesses: u32) -> anyhow::Result<Decimal> {
     let reward_units = if num_witnesses == 0 {
         Decimal::ZERO
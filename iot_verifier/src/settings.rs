@@ -1,7 +1,15 @@
+use crate::{
+    collusion::CollusionSettings,
+    history::HistorySettings,
+    poc_rules::{PocRuleChange, PocRuleSchedule, PocRules},
+    reward_preview::RewardPreviewSettings,
+    reward_share::{default_emission_schedule, IotEmissionSchedule},
+    terrain::{Terrain, TerrainError},
+};
 use chrono::Duration;
 use config::{Config, Environment, File};
 use serde::Deserialize;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tokio::time;
 
 #[derive(Debug, Deserialize, Clone)]
@@ -35,17 +43,46 @@ pub struct Settings {
     /// of the reward period + reward_offset_minutes
     #[serde(default = "default_reward_offset_minutes")]
     pub reward_offset_minutes: i64,
+    /// Versioned table of the emissions and their allocations over time.
+    /// Defaults to the schedule built into this release
+    #[serde(default = "default_emission_schedule")]
+    pub emission_schedule: IotEmissionSchedule,
+    /// Shadow mode periodically writing previews of the rewards since the
+    /// last rewarded period to a separate bucket. Default none, in which no
+    /// previews are written
+    pub reward_preview: Option<RewardPreviewSettings>,
     #[serde(default = "default_max_witnesses_per_poc")]
     pub max_witnesses_per_poc: u64,
-    /// The cadence at which hotspots are permitted to beacon (in seconds)
-    #[serde(default = "default_beacon_interval")]
-    pub beacon_interval: i64,
-    /// Tolerance applied to beacon intervals within which beacons will be accepted (in seconds)
-    #[serde(default = "default_beacon_interval_tolerance")]
-    pub beacon_interval_tolerance: i64,
-    /// Trigger interval for generating a transmit scaling map
+    /// Thresholds applied by the beacon and witness verifications
+    #[serde(default)]
+    pub poc_rules: PocRules,
+    /// Scheduled changes to the poc rules, each applying to reports received
+    /// at or after its activation time. Default none
+    #[serde(default)]
+    pub poc_rule_changes: Vec<PocRuleChange>,
+    /// Deprecated alias of `poc_rules.beacon_interval`, used for the poc
+    /// rules unless those set it
+    beacon_interval: Option<i64>,
+    /// Deprecated alias of `poc_rules.beacon_interval_tolerance`, used for
+    /// the poc rules unless those set it
+    beacon_interval_tolerance: Option<i64>,
+    /// Directory of SRTM `.hgt` elevation tiles used by the terrain rssi
+    /// model. Default none, in which the terrain check is skipped
+    pub terrain_tiles: Option<PathBuf>,
+    /// Detection of witness rings over the history of verified pocs
+    #[serde(default)]
+    pub collusion: CollusionSettings,
+    /// Per hotspot verification history and the api serving it
+    #[serde(default)]
+    pub history: HistorySettings,
+    /// Interval at which the recent beacon activity of gateways is checked
+    /// for changes to the transmit scaling map (in seconds)
     #[serde(default = "default_transmit_scale_interval")]
     pub transmit_scale_interval: i64,
+    /// Interval at which a snapshot of the per hex density breakdown is
+    /// written out (in hours). Default 6
+    #[serde(default = "default_density_snapshot_interval")]
+    pub density_snapshot_interval: i64,
     // roll up time defined in the ingestors ( in seconds )
     // ie the time after which they will write out files to s3
     // this will be used when padding out the witness
@@ -136,21 +173,15 @@ pub fn default_poc_loader_poll_time() -> u64 {
     5 * 60
 }
 
-// Default: 10 minutes
-pub fn default_beacon_interval_tolerance() -> i64 {
-    10 * 60
-}
-
-// Default: 6 hours
-pub fn default_beacon_interval() -> i64 {
-    6 * 60 * 60
-}
-
 // Default: 30 min
 pub fn default_transmit_scale_interval() -> i64 {
     1800
 }
 
+fn default_density_snapshot_interval() -> i64 {
+    6
+}
+
 pub fn default_log() -> String {
     "iot_verifier=debug,poc_store=info".to_string()
 }
@@ -211,18 +242,72 @@ impl Settings {
             .add_source(Environment::with_prefix("VERIFY").separator("_"))
             .build()
             .and_then(|config| config.try_deserialize())
+            .map(Self::apply_deprecated_keys)
+            .and_then(Self::check_reward_preview_output)
+    }
+
+    /// Feed the deprecated top-level aliases into the poc rules. A rule set
+    /// away from its default in `poc_rules` takes precedence over its alias
+    fn apply_deprecated_keys(mut self) -> Self {
+        let defaults = PocRules::default();
+        if let Some(beacon_interval) = self.beacon_interval {
+            if self.poc_rules.beacon_interval == defaults.beacon_interval {
+                self.poc_rules.beacon_interval = beacon_interval;
+            }
+        }
+        if let Some(tolerance) = self.beacon_interval_tolerance {
+            if self.poc_rules.beacon_interval_tolerance == defaults.beacon_interval_tolerance {
+                self.poc_rules.beacon_interval_tolerance = tolerance;
+            }
+        }
+        self
+    }
+
+    /// Warn about each deprecated key still set. Settings are loaded before
+    /// logging is set up, so this is called once it is
+    pub fn warn_deprecated_keys(&self) {
+        let deprecated = [
+            ("beacon_interval", self.beacon_interval.is_some()),
+            (
+                "beacon_interval_tolerance",
+                self.beacon_interval_tolerance.is_some(),
+            ),
+        ];
+        for (key, _) in deprecated.iter().filter(|(_, present)| *present) {
+            tracing::warn!("{key} is deprecated, set poc_rules.{key} instead");
+        }
+    }
+
+    /// Refuse to write reward previews to the bucket the rewards are written
+    /// to, where they would be taken for the real rewards
+    fn check_reward_preview_output(self) -> Result<Self, config::ConfigError> {
+        match &self.reward_preview {
+            Some(preview)
+                if preview.output.bucket == self.output.bucket
+                    && preview.output.endpoint == self.output.endpoint
+                    && preview.output.local_path == self.output.local_path =>
+            {
+                Err(config::ConfigError::Message(
+                    "reward_preview.output must not be the rewards output".to_string(),
+                ))
+            }
+            _ => Ok(self),
+        }
     }
 
     pub fn reward_offset_duration(&self) -> Duration {
         Duration::minutes(self.reward_offset_minutes)
     }
 
-    pub fn beacon_interval(&self) -> Duration {
-        Duration::seconds(self.beacon_interval)
+    pub async fn terrain(&self) -> Result<Terrain, TerrainError> {
+        match &self.terrain_tiles {
+            Some(path) => Terrain::from_dir(path).await,
+            None => Ok(Terrain::default()),
+        }
     }
 
-    pub fn beacon_interval_tolerance(&self) -> Duration {
-        Duration::seconds(self.beacon_interval_tolerance)
+    pub fn poc_rule_schedule(&self) -> PocRuleSchedule {
+        PocRuleSchedule::new(self.poc_rules.clone(), self.poc_rule_changes.clone())
     }
 
     pub fn poc_loader_window_width(&self) -> Duration {
@@ -258,6 +343,12 @@ impl Settings {
     pub fn gateway_refresh_interval(&self) -> Duration {
         Duration::seconds(self.gateway_refresh_interval)
     }
+    pub fn transmit_scale_interval(&self) -> time::Duration {
+        time::Duration::from_secs(self.transmit_scale_interval as u64)
+    }
+    pub fn density_snapshot_interval(&self) -> Duration {
+        Duration::hours(self.density_snapshot_interval)
+    }
     pub fn region_params_refresh_interval(&self) -> time::Duration {
         time::Duration::from_secs(self.region_params_refresh_interval)
     }
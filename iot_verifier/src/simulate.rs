@@ -0,0 +1,610 @@
+use crate::{
+    gateway_cache::GatewayCache,
+    gateway_updater::{refresh_gateways, GatewayMap},
+    hex_density::{compute_hex_density_map, GlobalHexMap, HexDensityMap, SharedHexDensityMap},
+    last_beacon::{LastBeacon, LastBeaconError, LastBeaconLookup},
+    poc::{Poc, VerifyWitnessesResult},
+    region_cache::RegionCache,
+    runner, Settings,
+};
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use file_store::{
+    entropy_report::EntropyReport,
+    file_sink::{self, FileSinkClient},
+    file_source,
+    iot_beacon_report::IotBeaconIngestReport,
+    iot_invalid_poc::{IotInvalidBeaconReport, IotInvalidWitnessReport},
+    iot_valid_poc::IotPoc,
+    iot_witness_report::IotWitnessIngestReport,
+    traits::MsgDecode,
+    FileStore, FileType,
+};
+use futures::{StreamExt, TryStreamExt};
+use helium_proto::services::{
+    iot_config::GatewayInfo as GatewayInfoProto,
+    poc_lora::{
+        InvalidParticipantSide, InvalidReason, LoraInvalidBeaconReportV1,
+        LoraInvalidWitnessReportV1, LoraPocV1, VerificationStatus,
+    },
+};
+use iot_config::{client::Client as IotConfigClient, gateway_info::GatewayInfo};
+use prost::Message;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+};
+use tokio::sync::watch;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+/// Padding applied either side of the simulated time range when loading
+/// entropy, so that beacons early in the range find the entropy they were
+/// generated from
+const ENTROPY_PADDING_MINS: i64 = 30;
+
+/// Re-run poc verification over a past time range of beacon and witness
+/// ingest reports. Verification runs entirely in memory against a gateway
+/// snapshot, without the database, and writes the resulting pocs and invalid
+/// reports to a local directory along with a summary of invalid reasons.
+///
+/// Last beacon timestamps start out empty and are tracked from the simulated
+/// pocs, and transmit scaling is derived from the gateways beaconing in the
+/// time range.
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// Beacons received at or after this time are verified
+    #[clap(long)]
+    after: NaiveDateTime,
+    /// Beacons received before this time are verified
+    #[clap(long)]
+    before: NaiveDateTime,
+    /// File of gateway info messages to verify against. A snapshot is taken
+    /// from the iot config service when not given
+    #[clap(long)]
+    gateways: Option<PathBuf>,
+    /// Directory the simulated pocs and invalid reports are written to
+    out_dir: PathBuf,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    beacons: u64,
+    witnesses: u64,
+    missing_entropy: u64,
+    valid_pocs: u64,
+    invalid_beacons: u64,
+    valid_witnesses: u64,
+    invalid_witnesses: u64,
+    failed_witnesses: u64,
+    invalid_beacon_reasons: BTreeMap<&'static str, u64>,
+    invalid_witness_reasons: BTreeMap<&'static str, u64>,
+}
+
+impl Summary {
+    fn invalid_beacon(&mut self, reason: InvalidReason, witnesses: usize) {
+        self.invalid_beacons += 1;
+        *self
+            .invalid_beacon_reasons
+            .entry(reason.as_str_name())
+            .or_default() += 1;
+        self.invalid_witnesses += witnesses as u64;
+        *self
+            .invalid_witness_reasons
+            .entry(reason.as_str_name())
+            .or_default() += witnesses as u64;
+    }
+
+    fn witness(&mut self, status: VerificationStatus, reason: InvalidReason) {
+        match status {
+            VerificationStatus::Valid => self.valid_witnesses += 1,
+            VerificationStatus::Invalid => {
+                self.invalid_witnesses += 1;
+                *self
+                    .invalid_witness_reasons
+                    .entry(reason.as_str_name())
+                    .or_default() += 1;
+            }
+        }
+    }
+}
+
+/// Last beacon timestamps of the simulated pocs
+#[derive(Default)]
+struct LastBeacons(HashMap<Vec<u8>, DateTime<Utc>>);
+
+#[async_trait::async_trait]
+impl LastBeaconLookup for LastBeacons {
+    async fn last_beacon(&self, id: &[u8]) -> Result<Option<LastBeacon>, LastBeaconError> {
+        Ok(self.0.get(id).map(|timestamp| LastBeacon {
+            id: id.to_vec(),
+            timestamp: *timestamp,
+        }))
+    }
+}
+
+struct Sinks {
+    poc: FileSinkClient,
+    invalid_beacon: FileSinkClient,
+    invalid_witness: FileSinkClient,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::EnvFilter::new(&settings.log))
+            .with(tracing_subscriber::fmt::layer())
+            .init();
+
+        let after = DateTime::from_utc(self.after, Utc);
+        let before = DateTime::from_utc(self.before, Utc);
+        let poc_rules = settings.poc_rule_schedule();
+        let terrain = Arc::new(settings.terrain().await?);
+        let max_witnesses_per_poc = settings.max_witnesses_per_poc as usize;
+
+        let iot_config_client = IotConfigClient::from_settings(&settings.iot_config_client)?;
+        let gateways = self.gateways(iot_config_client.clone()).await?;
+        let region_cache = RegionCache::from_settings(settings, iot_config_client)?;
+
+        let ingest_store = FileStore::from_settings(&settings.ingest).await?;
+        let entropy_store = FileStore::from_settings(&settings.entropy).await?;
+        let entropy = load_entropy(&entropy_store, after, before).await?;
+        let mut beacons: Vec<IotBeaconIngestReport> = load_reports(
+            &ingest_store,
+            FileType::IotBeaconIngestReport,
+            after,
+            before,
+        )
+        .await?;
+        beacons.retain(|beacon| {
+            beacon.received_timestamp >= after && beacon.received_timestamp < before
+        });
+        beacons.sort_by_key(|beacon| beacon.received_timestamp);
+        // witnesses may land in files rolled up after the beacon they witness
+        let mut witnesses = HashMap::<Vec<u8>, Vec<IotWitnessIngestReport>>::new();
+        for witness in load_reports::<IotWitnessIngestReport>(
+            &ingest_store,
+            FileType::IotWitnessIngestReport,
+            after,
+            before + settings.ingestor_rollup_time(),
+        )
+        .await?
+        {
+            witnesses
+                .entry(witness.report.data.clone())
+                .or_default()
+                .push(witness);
+        }
+        tracing::info!(
+            beacons = beacons.len(),
+            gateways = gateways.len(),
+            entropy = entropy.len(),
+            "loaded reports for simulation"
+        );
+
+        let hex_density_map = SharedHexDensityMap::new();
+        hex_density_map.swap(density_map(&gateways, &beacons)).await;
+        let (_gateway_sender, gateway_receiver) = watch::channel(gateways);
+        let gateway_cache = GatewayCache::new(gateway_receiver);
+
+        tokio::fs::create_dir_all(&self.out_dir).await?;
+        let (shutdown_trigger, shutdown) = triggered::trigger();
+        let (sinks, sink_servers) = self.sinks(&shutdown).await?;
+
+        let mut last_beacons = LastBeacons::default();
+        let mut summary = Summary::default();
+        for beacon_report in beacons {
+            summary.beacons += 1;
+            let beacon_witnesses = witnesses
+                .get(&beacon_report.report.data)
+                .cloned()
+                .unwrap_or_default();
+            summary.witnesses += beacon_witnesses.len() as u64;
+            let Some((entropy_start, entropy_version)) =
+                entropy.get(&beacon_report.report.remote_entropy).copied()
+            else {
+                summary.missing_entropy += 1;
+                continue;
+            };
+
+            let mut poc = Poc::new(
+                beacon_report.clone(),
+                beacon_witnesses.clone(),
+                entropy_start,
+                entropy_version,
+                poc_rules.rules_at(beacon_report.received_timestamp),
+                terrain.clone(),
+            )
+            .await;
+            let beacon_verify_result = poc
+                .verify_beacon(
+                    hex_density_map.clone(),
+                    &gateway_cache,
+                    &region_cache,
+                    &last_beacons,
+                )
+                .await?;
+            match (
+                beacon_verify_result.result,
+                beacon_verify_result.gateway_info,
+            ) {
+                (VerificationStatus::Valid, Some(beacon_info)) => {
+                    let verified_witnesses_result = poc
+                        .verify_witnesses(&beacon_info, hex_density_map.clone(), &gateway_cache)
+                        .await?;
+                    let iot_poc = simulate_valid_poc(
+                        &mut summary,
+                        &mut last_beacons,
+                        &beacon_report,
+                        beacon_info,
+                        beacon_verify_result.hex_scale,
+                        verified_witnesses_result,
+                        max_witnesses_per_poc,
+                    )?;
+                    sinks.poc.write(LoraPocV1::from(iot_poc), []).await?;
+                }
+                _ => {
+                    let reason = beacon_verify_result.invalid_reason;
+                    summary.invalid_beacon(reason, beacon_witnesses.len());
+                    write_invalid_poc(&sinks, beacon_report, beacon_witnesses, reason).await?;
+                }
+            }
+        }
+
+        for sink in [sinks.poc, sinks.invalid_beacon, sinks.invalid_witness] {
+            sink.commit().await?.await??;
+        }
+        shutdown_trigger.trigger();
+        for server in sink_servers {
+            server.await??;
+        }
+
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        Ok(())
+    }
+
+    async fn gateways(&self, mut iot_config_client: IotConfigClient) -> Result<GatewayMap> {
+        match &self.gateways {
+            Some(path) => Ok(file_source::source([path])
+                .map_err(anyhow::Error::from)
+                .and_then(|buf| async move {
+                    let info = GatewayInfo::from(GatewayInfoProto::decode(buf)?);
+                    Ok((info.address.clone(), info))
+                })
+                .try_collect()
+                .await?),
+            None => Ok(refresh_gateways(&mut iot_config_client).await?),
+        }
+    }
+
+    async fn sinks(
+        &self,
+        shutdown: &triggered::Listener,
+    ) -> Result<(Sinks, Vec<tokio::task::JoinHandle<file_store::Result>>)> {
+        let mut servers = Vec::new();
+        let sink = |file_type: FileType, metric: &'static str| {
+            file_sink::FileSinkBuilder::new(file_type, &self.out_dir, metric, shutdown.clone())
+                .auto_commit(false)
+                .roll_time(Duration::days(1))
+                .create()
+        };
+        let (poc, mut poc_server) = sink(
+            FileType::IotPoc,
+            concat!(env!("CARGO_PKG_NAME"), "_valid_poc"),
+        )
+        .await?;
+        let (invalid_beacon, mut invalid_beacon_server) = sink(
+            FileType::IotInvalidBeaconReport,
+            concat!(env!("CARGO_PKG_NAME"), "_invalid_beacon_report"),
+        )
+        .await?;
+        let (invalid_witness, mut invalid_witness_server) = sink(
+            FileType::IotInvalidWitnessReport,
+            concat!(env!("CARGO_PKG_NAME"), "_invalid_witness_report"),
+        )
+        .await?;
+        servers.push(tokio::spawn(async move { poc_server.run().await }));
+        servers.push(tokio::spawn(
+            async move { invalid_beacon_server.run().await },
+        ));
+        servers.push(tokio::spawn(
+            async move { invalid_witness_server.run().await },
+        ));
+        Ok((
+            Sinks {
+                poc,
+                invalid_beacon,
+                invalid_witness,
+            },
+            servers,
+        ))
+    }
+}
+
+/// The poc of a valid beacon, recorded in the summary and as the last beacon
+/// of its beaconer for the beacons that follow it
+fn simulate_valid_poc(
+    summary: &mut Summary,
+    last_beacons: &mut LastBeacons,
+    beacon_report: &IotBeaconIngestReport,
+    beacon_info: GatewayInfo,
+    hex_scale: Option<Decimal>,
+    verified_witnesses_result: VerifyWitnessesResult,
+    max_witnesses_per_poc: usize,
+) -> Result<IotPoc> {
+    summary.failed_witnesses += verified_witnesses_result.failed_witnesses.len() as u64;
+    for witness in &verified_witnesses_result.verified_witnesses {
+        summary.witness(witness.status, witness.invalid_reason);
+    }
+    let iot_poc = runner::valid_poc(
+        beacon_report,
+        beacon_info,
+        hex_scale,
+        verified_witnesses_result.verified_witnesses,
+        max_witnesses_per_poc,
+    )?;
+    summary.valid_pocs += 1;
+    last_beacons.0.insert(
+        beacon_report.report.pub_key.as_ref().to_vec(),
+        beacon_report.received_timestamp,
+    );
+    Ok(iot_poc)
+}
+
+async fn load_reports<T>(
+    store: &FileStore,
+    file_type: FileType,
+    after: DateTime<Utc>,
+    before: DateTime<Utc>,
+) -> Result<Vec<T>>
+where
+    T: MsgDecode + TryFrom<T::Msg, Error = file_store::Error>,
+{
+    Ok(store
+        .source(store.list(file_type, after, before))
+        .map_err(anyhow::Error::from)
+        .and_then(|buf| async move { Ok(T::decode(buf)?) })
+        .try_collect()
+        .await?)
+}
+
+/// Entropy start time and version keyed by entropy data, as matched against
+/// the remote entropy of beacons
+async fn load_entropy(
+    store: &FileStore,
+    after: DateTime<Utc>,
+    before: DateTime<Utc>,
+) -> Result<HashMap<Vec<u8>, (DateTime<Utc>, i32)>> {
+    let padding = Duration::minutes(ENTROPY_PADDING_MINS);
+    let reports: Vec<EntropyReport> = load_reports(
+        store,
+        FileType::EntropyReport,
+        after - padding,
+        before + padding,
+    )
+    .await?;
+    Ok(reports
+        .into_iter()
+        .map(|report| (report.data, (report.timestamp, report.version as i32)))
+        .collect())
+}
+
+/// Transmit scaling derived from the asserted locations of the gateways
+/// beaconing in the simulated time range
+fn density_map(gateways: &GatewayMap, beacons: &[IotBeaconIngestReport]) -> HashMap<u64, Decimal> {
+    let mut global_map = GlobalHexMap::new();
+    let beaconers: HashSet<_> = beacons
+        .iter()
+        .map(|beacon| &beacon.report.pub_key)
+        .collect();
+    for beaconer in beaconers {
+        if let Some(metadata) = gateways
+            .get(beaconer)
+            .and_then(|info| info.metadata.as_ref())
+        {
+            global_map.increment_unclipped(metadata.location);
+        }
+    }
+    global_map.reduce_global();
+    compute_hex_density_map(&global_map)
+}
+
+async fn write_invalid_poc(
+    sinks: &Sinks,
+    beacon_report: IotBeaconIngestReport,
+    witness_reports: Vec<IotWitnessIngestReport>,
+    reason: InvalidReason,
+) -> Result<()> {
+    let invalid_beacon = IotInvalidBeaconReport {
+        received_timestamp: beacon_report.received_timestamp,
+        reason,
+        report: beacon_report.report,
+    };
+    sinks
+        .invalid_beacon
+        .write(
+            LoraInvalidBeaconReportV1::from(invalid_beacon),
+            &[("reason", reason.as_str_name())],
+        )
+        .await?;
+    for witness_report in witness_reports {
+        let invalid_witness = IotInvalidWitnessReport {
+            received_timestamp: witness_report.received_timestamp,
+            report: witness_report.report,
+            reason,
+            participant_side: InvalidParticipantSide::Beaconer,
+        };
+        sinks
+            .invalid_witness
+            .write(
+                LoraInvalidWitnessReportV1::from(invalid_witness),
+                &[("reason", reason.as_str_name())],
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use file_store::{
+        iot_beacon_report::IotBeaconReport, iot_valid_poc::IotVerifiedWitnessReport,
+        iot_witness_report::IotWitnessReport,
+    };
+    use helium_crypto::PublicKeyBinary;
+    use helium_proto::{services::poc_lora::InvalidParticipantSide, DataRate, Region};
+    use iot_config::gateway_info::GatewayMetadata;
+    use std::str::FromStr;
+
+    const BEACONER: &str = "112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf";
+    const WITNESS: &str = "11z69eJ3czc92k6snrfR9ek7g2uRWXosFbnG9v4bXgwhfUCivUo";
+    const LOCATION: u64 = 631615575095659519;
+
+    fn beacon_report(received_timestamp: DateTime<Utc>) -> IotBeaconIngestReport {
+        IotBeaconIngestReport {
+            received_timestamp,
+            report: IotBeaconReport {
+                pub_key: PublicKeyBinary::from_str(BEACONER).unwrap(),
+                local_entropy: vec![1, 2, 3, 4],
+                remote_entropy: vec![5, 6, 7, 8],
+                data: vec![9, 10, 11, 12],
+                frequency: 867900000,
+                channel: 0,
+                datarate: DataRate::Sf12bw125,
+                tx_power: 8,
+                timestamp: received_timestamp,
+                signature: vec![],
+                tmst: 0,
+            },
+        }
+    }
+
+    fn beacon_info() -> GatewayInfo {
+        GatewayInfo {
+            address: PublicKeyBinary::from_str(BEACONER).unwrap(),
+            is_full_hotspot: true,
+            metadata: Some(GatewayMetadata {
+                location: LOCATION,
+                gain: 12,
+                elevation: 100,
+                region: Region::Eu868,
+            }),
+        }
+    }
+
+    fn verified_witness(
+        received_timestamp: DateTime<Utc>,
+        status: VerificationStatus,
+        invalid_reason: InvalidReason,
+    ) -> IotVerifiedWitnessReport {
+        IotVerifiedWitnessReport {
+            received_timestamp,
+            report: IotWitnessReport {
+                pub_key: PublicKeyBinary::from_str(WITNESS).unwrap(),
+                data: vec![9, 10, 11, 12],
+                timestamp: received_timestamp,
+                tmst: 0,
+                signal: -1080,
+                snr: 35,
+                frequency: 867900000,
+                datarate: DataRate::Sf12bw125,
+                signature: vec![],
+            },
+            location: Some(LOCATION),
+            gain: 20,
+            elevation: 100,
+            hex_scale: Decimal::ONE,
+            reward_unit: Decimal::ZERO,
+            status,
+            invalid_reason,
+            participant_side: match status {
+                VerificationStatus::Valid => InvalidParticipantSide::SideNone,
+                VerificationStatus::Invalid => InvalidParticipantSide::Witness,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn valid_poc_sets_last_beacon() {
+        let received = Utc.with_ymd_and_hms(2023, 3, 1, 12, 0, 0).unwrap();
+        let beacon = beacon_report(received);
+        let beaconer = beacon.report.pub_key.as_ref().to_vec();
+        let mut summary = Summary::default();
+        let mut last_beacons = LastBeacons::default();
+        assert!(last_beacons.last_beacon(&beaconer).await.unwrap().is_none());
+
+        let iot_poc = simulate_valid_poc(
+            &mut summary,
+            &mut last_beacons,
+            &beacon,
+            beacon_info(),
+            Some(Decimal::ONE),
+            VerifyWitnessesResult {
+                verified_witnesses: vec![
+                    verified_witness(
+                        received,
+                        VerificationStatus::Valid,
+                        InvalidReason::ReasonNone,
+                    ),
+                    verified_witness(
+                        received,
+                        VerificationStatus::Invalid,
+                        InvalidReason::MaxDistanceExceeded,
+                    ),
+                ],
+                failed_witnesses: vec![],
+            },
+            14,
+        )
+        .unwrap();
+
+        assert_eq!(1, iot_poc.selected_witnesses.len());
+        assert_eq!(1, iot_poc.unselected_witnesses.len());
+        assert_eq!(1, summary.valid_pocs);
+        assert_eq!(1, summary.valid_witnesses);
+        assert_eq!(1, summary.invalid_witnesses);
+        assert_eq!(
+            Some(&1),
+            summary
+                .invalid_witness_reasons
+                .get(InvalidReason::MaxDistanceExceeded.as_str_name())
+        );
+
+        let last_beacon = last_beacons
+            .last_beacon(&beaconer)
+            .await
+            .unwrap()
+            .expect("last beacon recorded");
+        assert_eq!(received, last_beacon.timestamp);
+
+        // a later beacon of the same beaconer moves its last beacon on
+        let later = received + Duration::hours(7);
+        simulate_valid_poc(
+            &mut summary,
+            &mut last_beacons,
+            &beacon_report(later),
+            beacon_info(),
+            Some(Decimal::ONE),
+            VerifyWitnessesResult {
+                verified_witnesses: vec![],
+                failed_witnesses: vec![],
+            },
+            14,
+        )
+        .unwrap();
+        assert_eq!(2, summary.valid_pocs);
+        assert_eq!(
+            later,
+            last_beacons
+                .last_beacon(&beaconer)
+                .await
+                .unwrap()
+                .unwrap()
+                .timestamp
+        );
+    }
+}
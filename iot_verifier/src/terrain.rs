@@ -0,0 +1,289 @@
+use crate::poc::{C, R};
+use h3o::{CellIndex, LatLng};
+use iot_config::gateway_info::GatewayMetadata;
+use std::{collections::HashMap, path::Path};
+use tokio::fs;
+
+/// Elevation of samples missing from a tile
+const VOID_ELEVATION: i16 = -32768;
+/// Number of points sampled along the path between a beaconer and witness
+const PROFILE_SAMPLES: usize = 64;
+/// Effective earth radius factor accounting for standard atmospheric refraction
+const EFFECTIVE_EARTH_RADIUS_FACTOR: f64 = 4.0 / 3.0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TerrainError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid elevation tile: {0}")]
+    InvalidTile(String),
+    #[error("h3 invalid cell: {0}")]
+    InvalidCell(#[from] h3o::error::InvalidCellIndex),
+}
+
+/// A one degree square SRTM elevation tile, as found in `.hgt` files named by
+/// the latitude and longitude of their south west corner, ie `N37W122.hgt`
+#[derive(Debug)]
+struct ElevationTile {
+    size: usize,
+    samples: Vec<i16>,
+}
+
+impl ElevationTile {
+    fn from_bytes(name: &str, data: &[u8]) -> Result<Self, TerrainError> {
+        let size = ((data.len() / 2) as f64).sqrt() as usize;
+        if size < 2 || size * size * 2 != data.len() {
+            return Err(TerrainError::InvalidTile(name.to_string()));
+        }
+        let samples = data
+            .chunks_exact(2)
+            .map(|sample| i16::from_be_bytes([sample[0], sample[1]]))
+            .collect();
+        Ok(Self { size, samples })
+    }
+
+    /// Elevation in meters of the sample nearest to the given offset in
+    /// degrees from the south west corner of the tile
+    fn elevation(&self, lat_offset: f64, lng_offset: f64) -> Option<f64> {
+        let last = (self.size - 1) as f64;
+        let row = ((1.0 - lat_offset) * last).round() as usize;
+        let col = (lng_offset * last).round() as usize;
+        match self.samples.get(row * self.size + col) {
+            Some(&VOID_ELEVATION) | None => None,
+            Some(elevation) => Some(*elevation as f64),
+        }
+    }
+}
+
+fn parse_tile_name(name: &str) -> Option<(i32, i32)> {
+    let name = name.strip_suffix(".hgt")?.to_ascii_uppercase();
+    let lng_pos = name.find(|c| c == 'E' || c == 'W')?;
+    let (lat, lng) = name.split_at(lng_pos);
+    let lat = match lat.split_at(1) {
+        ("N", degrees) => degrees.parse::<i32>().ok()?,
+        ("S", degrees) => -degrees.parse::<i32>().ok()?,
+        _ => return None,
+    };
+    let lng = match lng.split_at(1) {
+        ("E", degrees) => degrees.parse::<i32>().ok()?,
+        ("W", degrees) => -degrees.parse::<i32>().ok()?,
+        _ => return None,
+    };
+    Some((lat, lng))
+}
+
+/// Ground elevation used to work out whether the path between a beaconer and
+/// a witness is obstructed. Nothing is known of locations not covered by a
+/// loaded tile, or of voids in a tile.
+#[derive(Debug, Default)]
+pub struct Terrain {
+    tiles: HashMap<(i32, i32), ElevationTile>,
+}
+
+impl Terrain {
+    /// Load all `.hgt` elevation tiles in the given directory
+    pub async fn from_dir(path: &Path) -> Result<Self, TerrainError> {
+        let mut tiles = Vec::new();
+        let mut entries = fs::read_dir(path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if parse_tile_name(&name).is_none() {
+                continue;
+            }
+            tiles.push((name, fs::read(entry.path()).await?));
+        }
+        let terrain = Self::from_tiles(tiles)?;
+        tracing::info!("loaded {} elevation tiles", terrain.tiles.len());
+        Ok(terrain)
+    }
+
+    /// Terrain from the contents of `.hgt` elevation tiles keyed by their file
+    /// name. Tiles not named by their south west corner are ignored.
+    pub fn from_tiles<N, D>(tiles: impl IntoIterator<Item = (N, D)>) -> Result<Self, TerrainError>
+    where
+        N: AsRef<str>,
+        D: AsRef<[u8]>,
+    {
+        let mut loaded = HashMap::new();
+        for (name, data) in tiles {
+            let name = name.as_ref();
+            let Some(corner) = parse_tile_name(name) else {
+                continue;
+            };
+            loaded.insert(corner, ElevationTile::from_bytes(name, data.as_ref())?);
+        }
+        Ok(Self { tiles: loaded })
+    }
+
+    /// Ground elevation in meters at the given location, if a loaded tile
+    /// has a sample for it
+    pub fn ground_elevation(&self, latlng: LatLng) -> Option<f64> {
+        let (lat, lng) = (latlng.lat(), latlng.lng());
+        let corner = (lat.floor() as i32, lng.floor() as i32);
+        self.tiles
+            .get(&corner)
+            .and_then(|tile| tile.elevation(lat - lat.floor(), lng - lng.floor()))
+    }
+
+    /// Diffraction loss in dB from terrain obstructing the first fresnel zone
+    /// of the path between the beaconer and witness. The asserted elevation
+    /// of each gateway is taken as its antenna height above ground, so as
+    /// never to understate the clearance of the path. None when the ground
+    /// elevation of any point sampled along the path is unknown.
+    pub fn obstruction_loss(
+        &self,
+        beaconer: &GatewayMetadata,
+        witness: &GatewayMetadata,
+        freq: u64,
+    ) -> Result<Option<f64>, TerrainError> {
+        let beaconer_latlng = LatLng::from(CellIndex::try_from(beaconer.location)?);
+        let witness_latlng = LatLng::from(CellIndex::try_from(witness.location)?);
+        let (Some(beaconer_ground), Some(witness_ground)) = (
+            self.ground_elevation(beaconer_latlng),
+            self.ground_elevation(witness_latlng),
+        ) else {
+            return Ok(None);
+        };
+        let distance = beaconer_latlng.distance_m(witness_latlng);
+        if distance <= 0.0 || freq == 0 {
+            return Ok(Some(0.0));
+        }
+        let beaconer_height = beaconer_ground + beaconer.elevation.max(0) as f64;
+        let witness_height = witness_ground + witness.elevation.max(0) as f64;
+        let wavelength = C / freq as f64;
+        let effective_radius = EFFECTIVE_EARTH_RADIUS_FACTOR * R;
+
+        // the dominant obstruction along the path, expressed as the
+        // fresnel-kirchhoff diffraction parameter
+        let mut max_v = f64::MIN;
+        for sample in 1..PROFILE_SAMPLES {
+            let fraction = sample as f64 / PROFILE_SAMPLES as f64;
+            let latlng = interpolate(beaconer_latlng, witness_latlng, fraction);
+            let Some(ground) = self.ground_elevation(latlng) else {
+                return Ok(None);
+            };
+            let d1 = distance * fraction;
+            let d2 = distance - d1;
+            let earth_bulge = d1 * d2 / (2.0 * effective_radius);
+            let obstacle = ground + earth_bulge;
+            let line_of_sight = beaconer_height + (witness_height - beaconer_height) * fraction;
+            let v = (obstacle - line_of_sight) * (2.0 * distance / (wavelength * d1 * d2)).sqrt();
+            max_v = max_v.max(v);
+        }
+        Ok(Some(knife_edge_loss(max_v)))
+    }
+}
+
+/// Single knife edge diffraction loss in dB, per ITU-R P.526
+fn knife_edge_loss(v: f64) -> f64 {
+    if v <= -0.78 {
+        0.0
+    } else {
+        6.9 + 20.0 * (((v - 0.1).powi(2) + 1.0).sqrt() + v - 0.1).log10()
+    }
+}
+
+fn interpolate(from: LatLng, to: LatLng, fraction: f64) -> LatLng {
+    let lat = from.lat() + (to.lat() - from.lat()) * fraction;
+    let lng = from.lng() + (to.lng() - from.lng()) * fraction;
+    LatLng::new(lat, lng).unwrap_or(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use h3o::Resolution;
+    use helium_proto::Region as ProtoRegion;
+
+    fn metadata(lat: f64, lng: f64, elevation: i32) -> GatewayMetadata {
+        let cell = LatLng::new(lat, lng).unwrap().to_cell(Resolution::Twelve);
+        GatewayMetadata {
+            location: u64::from(cell),
+            elevation,
+            gain: 12,
+            region: ProtoRegion::Us915,
+        }
+    }
+
+    fn terrain_with_ridge(ridge_elevation: i16) -> Terrain {
+        // a flat tile at 100m with a north south ridge half way across
+        let size = 121;
+        let mut samples = vec![100_i16; size * size];
+        for row in 0..size {
+            samples[row * size + size / 2] = ridge_elevation;
+        }
+        let data: Vec<u8> = samples.iter().flat_map(|s| s.to_be_bytes()).collect();
+        Terrain::from_tiles([("N37W122.hgt", data)]).unwrap()
+    }
+
+    #[test]
+    fn parses_tile_names() {
+        assert_eq!(Some((37, -122)), parse_tile_name("N37W122.hgt"));
+        assert_eq!(Some((-34, 18)), parse_tile_name("s34e018.hgt"));
+        assert_eq!(None, parse_tile_name("N37W122.tif"));
+    }
+
+    #[test]
+    fn skips_paths_not_covered_by_the_tiles() {
+        let terrain = terrain_with_ridge(100);
+        let freq = 904_700_000;
+        let inside = metadata(37.5, -121.9, 10);
+        // the witness is on an unloaded tile, the path runs off the tile
+        let outside = metadata(38.5, -121.9, 10);
+        assert_eq!(
+            None,
+            terrain.obstruction_loss(&inside, &outside, freq).unwrap()
+        );
+        assert_eq!(
+            None,
+            Terrain::default()
+                .obstruction_loss(&inside, &inside, freq)
+                .unwrap()
+        );
+
+        // a void half way along the path
+        let voided = terrain_with_ridge(VOID_ELEVATION);
+        let witness = metadata(37.5, -121.1, 10);
+        assert_eq!(
+            None,
+            voided.obstruction_loss(&inside, &witness, freq).unwrap()
+        );
+        assert!(terrain
+            .obstruction_loss(&inside, &witness, freq)
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn obstruction_loss_from_terrain() {
+        let beaconer = metadata(37.5, -121.9, 10);
+        let witness = metadata(37.5, -121.1, 10);
+        let freq = 904_700_000;
+
+        let flat = terrain_with_ridge(100);
+        let ridge = terrain_with_ridge(1500);
+        assert_eq!(
+            Some(100.0),
+            ridge.ground_elevation(LatLng::new(37.5, -121.9).unwrap())
+        );
+
+        let flat_loss = flat
+            .obstruction_loss(&beaconer, &witness, freq)
+            .unwrap()
+            .unwrap();
+        let ridge_loss = ridge
+            .obstruction_loss(&beaconer, &witness, freq)
+            .unwrap()
+            .unwrap();
+        assert!(ridge_loss > 30.0);
+        assert!(ridge_loss > flat_loss);
+
+        // raising both antennas well above the ridge clears the path
+        let beaconer = metadata(37.5, -121.9, 3000);
+        let witness = metadata(37.5, -121.1, 3000);
+        assert_eq!(
+            Some(0.0),
+            ridge.obstruction_loss(&beaconer, &witness, freq).unwrap()
+        );
+    }
+}
@@ -1,13 +1,18 @@
 use crate::{
     gateway_updater::MessageReceiver,
-    hex_density::{compute_hex_density_map, GlobalHexMap, HexDensityMap, SharedHexDensityMap},
+    hex_density::{HexDensityMap, IncrementalHexDensity, SharedHexDensityMap},
     last_beacon::LastBeacon,
     Settings,
 };
 use chrono::{DateTime, Duration, Utc};
+use file_store::{
+    file_sink,
+    iot_hex_density::{IotHexDensity, IotHexDensityV1},
+};
 use helium_crypto::PublicKeyBinary;
 use sqlx::PgPool;
 use std::collections::HashMap;
+use tokio::time::{self, MissedTickBehavior};
 
 // The number in minutes within which the gateway has registered a beacon
 // to the oracle for inclusion in transmit scaling density calculations
@@ -17,7 +22,15 @@ pub struct Server {
     hex_density_map: SharedHexDensityMap,
     pool: PgPool,
     refresh_offset: Duration,
+    refresh_interval: time::Duration,
+    snapshot_interval: Duration,
     gateway_cache_receiver: MessageReceiver,
+    density_sink: file_sink::FileSinkClient,
+    /// Gateways which have beaconed within the interactivity limit, with
+    /// their asserted location if any
+    active_gateways: HashMap<PublicKeyBinary, Option<u64>>,
+    density: IncrementalHexDensity,
+    last_snapshot: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -26,6 +39,8 @@ pub enum TxScalerError {
     DbConnect(#[from] db_store::Error),
     #[error("txn scaler error retrieving recent activity")]
     RecentActivity(#[from] sqlx::Error),
+    #[error("tx scaler error writing density snapshot")]
+    Snapshot(#[from] file_store::Error),
 }
 
 impl Server {
@@ -33,15 +48,23 @@ impl Server {
         settings: &Settings,
         pool: PgPool,
         gateway_cache_receiver: MessageReceiver,
+        density_sink: file_sink::FileSinkClient,
     ) -> Result<Self, TxScalerError> {
         let mut server = Self {
             hex_density_map: SharedHexDensityMap::new(),
             pool,
             refresh_offset: settings.loader_window_max_lookback_age(),
+            refresh_interval: settings.transmit_scale_interval(),
+            snapshot_interval: settings.density_snapshot_interval(),
             gateway_cache_receiver,
+            density_sink,
+            active_gateways: HashMap::new(),
+            density: IncrementalHexDensity::default(),
+            last_snapshot: None,
         };
 
-        server.refresh_scaling_map().await?;
+        server.refresh_activity().await?;
+        server.update_scaling_map().await;
 
         Ok(server)
     }
@@ -53,6 +76,9 @@ impl Server {
     pub async fn run(&mut self, shutdown: &triggered::Listener) -> Result<(), TxScalerError> {
         tracing::info!("density_scaler: starting transmit scaler process");
 
+        let mut refresh_timer = time::interval(self.refresh_interval);
+        refresh_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
         loop {
             if shutdown.is_triggered() {
                 tracing::info!("density_scaler: stopping transmit scaler");
@@ -60,53 +86,110 @@ impl Server {
             }
 
             tokio::select! {
-                _ = self.gateway_cache_receiver.changed() => self.refresh_scaling_map().await?,
+                _ = self.gateway_cache_receiver.changed() => self.refresh_locations(),
+                _ = refresh_timer.tick() => self.refresh_activity().await?,
                 _ = shutdown.clone() => return Ok(()),
             }
+            self.update_scaling_map().await;
+            self.maybe_write_snapshot().await?;
         }
     }
 
-    pub async fn refresh_scaling_map(&mut self) -> Result<(), TxScalerError> {
+    /// Bring the set of active gateways in line with the gateways which have
+    /// beaconed within the interactivity limit
+    async fn refresh_activity(&mut self) -> Result<(), TxScalerError> {
         let refresh_start = Utc::now() - self.refresh_offset;
-        tracing::info!("density_scaler: generating hex scaling map, starting at {refresh_start:?}");
-        let mut global_map = GlobalHexMap::new();
-        let active_gateways = self
-            .gateways_recent_activity(refresh_start)
-            .await
-            .map_err(sqlx::Error::from)?;
-        for k in active_gateways.keys() {
-            let pubkey = PublicKeyBinary::from(k.clone());
-            if let Some(gateway_info) = self.gateway_cache_receiver.borrow().get(&pubkey) {
-                if let Some(metadata) = &gateway_info.metadata {
-                    global_map.increment_unclipped(metadata.location)
+        let interactivity_deadline = refresh_start - Duration::minutes(HIP_17_INTERACTIVITY_LIMIT);
+        let recent_activity: Vec<PublicKeyBinary> =
+            LastBeacon::get_all_since(interactivity_deadline, &self.pool)
+                .await?
+                .into_iter()
+                .map(|beacon| PublicKeyBinary::from(beacon.id))
+                .collect();
+
+        let mut inactive = std::mem::take(&mut self.active_gateways);
+        let gateways = self.gateway_cache_receiver.borrow();
+        for pubkey in recent_activity {
+            let location = match inactive.remove(&pubkey) {
+                Some(location) => location,
+                None => {
+                    let location = gateways
+                        .get(&pubkey)
+                        .and_then(|gateway_info| gateway_info.metadata.as_ref())
+                        .map(|metadata| metadata.location);
+                    if let Some(location) = location {
+                        self.density.insert(location);
+                    }
+                    location
+                }
+            };
+            self.active_gateways.insert(pubkey, location);
+        }
+        for location in inactive.into_values().flatten() {
+            self.density.remove(location);
+        }
+        Ok(())
+    }
+
+    /// Pick up the asserted locations of active gateways which have changed
+    /// in the gateway cache
+    fn refresh_locations(&mut self) {
+        let gateways = self.gateway_cache_receiver.borrow();
+        for (pubkey, active_location) in self.active_gateways.iter_mut() {
+            let location = gateways
+                .get(pubkey)
+                .and_then(|gateway_info| gateway_info.metadata.as_ref())
+                .map(|metadata| metadata.location);
+            if location != *active_location {
+                if let Some(previous) = *active_location {
+                    self.density.remove(previous);
+                }
+                if let Some(location) = location {
+                    self.density.insert(location);
                 }
+                *active_location = location;
             }
         }
-        global_map.reduce_global();
-        let new_map = compute_hex_density_map(&global_map);
-        tracing::info!(
-            "density_scaler: scaling factor map entries: {}",
-            new_map.len()
-        );
-        self.hex_density_map.swap(new_map).await;
+    }
+
+    async fn update_scaling_map(&mut self) {
+        if !self.density.has_changes() {
+            return;
+        }
+        tracing::info!("density_scaler: updating hex scaling map");
+        let update = self.density.update();
         tracing::info!(
-            "density_scaler: generating hex scaling map, completed at {:?}",
-            Utc::now()
+            "density_scaler: scaling factor map entries updated: {}, removed: {}",
+            update.updated.len(),
+            update.removed.len()
         );
-        Ok(())
+        let updated = update
+            .updated
+            .into_iter()
+            .map(|(hex, density)| (hex, density.scale))
+            .collect();
+        self.hex_density_map.update(&update.removed, updated).await;
     }
 
-    async fn gateways_recent_activity(
-        &self,
-        now: DateTime<Utc>,
-    ) -> Result<HashMap<Vec<u8>, DateTime<Utc>>, sqlx::Error> {
-        let interactivity_deadline = now - Duration::minutes(HIP_17_INTERACTIVITY_LIMIT);
-        Ok(
-            LastBeacon::get_all_since(interactivity_deadline, &self.pool)
-                .await?
-                .into_iter()
-                .map(|beacon| (beacon.id, beacon.timestamp))
-                .collect::<HashMap<Vec<u8>, DateTime<Utc>>>(),
-        )
+    async fn maybe_write_snapshot(&mut self) -> Result<(), TxScalerError> {
+        let now = Utc::now();
+        if matches!(self.last_snapshot, Some(last) if now - last < self.snapshot_interval) {
+            return Ok(());
+        }
+        tracing::info!("density_scaler: writing hex density snapshot");
+        for (hex, density) in self.density.densities() {
+            let snapshot = IotHexDensity {
+                timestamp: now,
+                hex: *hex,
+                hex_scale: density.scale,
+                resolutions: density.resolutions.clone(),
+            };
+            self.density_sink
+                .write(IotHexDensityV1::from(snapshot), [])
+                .await?;
+        }
+        self.density_sink.commit().await?;
+        self.last_snapshot = Some(now);
+        Ok(())
     }
 }
@@ -53,20 +53,13 @@ impl Client {
             cache,
         })
     }
-}
 
-#[async_trait::async_trait]
-impl gateway_info::GatewayInfoResolver for Client {
-    type Error = ClientError;
-
-    async fn resolve_gateway_info(
+    /// Look up the info of a gateway from the config service, bypassing the
+    /// cache of `resolve_gateway_info`. The result is not cached either.
+    pub async fn fetch_gateway_info(
         &mut self,
         address: &PublicKeyBinary,
-    ) -> Result<Option<gateway_info::GatewayInfo>, Self::Error> {
-        if let Some(cached_response) = self.cache.get(address).await {
-            return Ok(cached_response.value().clone());
-        }
-
+    ) -> Result<Option<gateway_info::GatewayInfo>, ClientError> {
         let mut request = mobile_config::GatewayInfoReqV1 {
             address: address.clone().into(),
             signer: self.signing_key.public_key().into(),
@@ -84,6 +77,24 @@ impl gateway_info::GatewayInfoResolver for Client {
             Err(status) => Err(status)?,
         };
 
+        Ok(response)
+    }
+}
+
+#[async_trait::async_trait]
+impl gateway_info::GatewayInfoResolver for Client {
+    type Error = ClientError;
+
+    async fn resolve_gateway_info(
+        &mut self,
+        address: &PublicKeyBinary,
+    ) -> Result<Option<gateway_info::GatewayInfo>, Self::Error> {
+        if let Some(cached_response) = self.cache.get(address).await {
+            return Ok(cached_response.value().clone());
+        }
+
+        let response = self.fetch_gateway_info(address).await?;
+
         self.cache
             .insert(address.clone(), response.clone(), self.cache_ttl)
             .await;
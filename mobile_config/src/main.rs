@@ -35,12 +35,20 @@ impl Cli {
 #[derive(Debug, clap::Subcommand)]
 pub enum Cmd {
     Server(Daemon),
+    Migrate(db_store::schema::Cmd),
 }
 
 impl Cmd {
     pub async fn run(&self, settings: Settings) -> Result<()> {
         match self {
             Self::Server(cmd) => cmd.run(&settings).await,
+            Self::Migrate(cmd) => Ok(cmd
+                .run(
+                    &settings.database,
+                    env!("CARGO_PKG_NAME"),
+                    &sqlx::migrate!(),
+                )
+                .await?),
         }
     }
 }
@@ -73,7 +81,7 @@ impl Daemon {
             .database
             .connect("mobile-config-store", shutdown_listener.clone())
             .await?;
-        sqlx::migrate!().run(&pool).await?;
+        db_store::schema::migrate(&pool, &sqlx::migrate!()).await?;
 
         // Create on-chain metadata pool
         let (metadata_pool, md_pool_handle) = settings
@@ -1,5 +1,12 @@
+use crate::burner::bytes_to_dc;
 use chrono::{DateTime, Utc};
-use file_store::mobile_session::DataTransferSessionIngestReport;
+use file_store::{
+    file_sink::FileSinkClient,
+    mobile_session::DataTransferSessionIngestReport,
+    mobile_transfer::{
+        InvalidDataTransferReason, InvalidDataTransferSession, InvalidDataTransferSessionV1,
+    },
+};
 use futures::{Stream, StreamExt};
 use mobile_config::{
     client::{Client, ClientError},
@@ -22,21 +29,47 @@ pub enum AccumulationError {
 pub async fn accumulate_sessions(
     config_client: &mut Client,
     conn: &mut Transaction<'_, Postgres>,
+    invalid_sessions: &FileSinkClient,
     curr_file_ts: DateTime<Utc>,
     reports: impl Stream<Item = DataTransferSessionIngestReport>,
 ) -> Result<(), AccumulationError> {
     tokio::pin!(reports);
 
-    while let Some(DataTransferSessionIngestReport { report, .. }) = reports.next().await {
+    while let Some(DataTransferSessionIngestReport {
+        received_timestamp,
+        report,
+    }) = reports.next().await
+    {
         let event = report.data_transfer_usage;
-        // If the reward has been cancelled or we cannot resolve this gateway, skip the
-        // report
-        if report.reward_cancelled
-            || config_client
-                .resolve_gateway_info(&event.pub_key)
-                .await?
-                .is_none()
+        // If the reward has been cancelled or we cannot resolve this gateway, write the
+        // report out as an invalid session
+        let invalid_reason = if report.reward_cancelled {
+            Some(InvalidDataTransferReason::RewardCancelled)
+        } else if config_client
+            .resolve_gateway_info(&event.pub_key)
+            .await?
+            .is_none()
         {
+            Some(InvalidDataTransferReason::UnknownGateway)
+        } else {
+            None
+        };
+        if let Some(reason) = invalid_reason {
+            invalid_sessions
+                .write(
+                    InvalidDataTransferSessionV1::from(InvalidDataTransferSession {
+                        num_dcs: bytes_to_dc(event.upload_bytes + event.download_bytes),
+                        pub_key: event.pub_key,
+                        payer: event.payer,
+                        upload_bytes: event.upload_bytes,
+                        download_bytes: event.download_bytes,
+                        first_timestamp: received_timestamp,
+                        last_timestamp: received_timestamp,
+                        reason,
+                    }),
+                    &[],
+                )
+                .await?;
             continue;
         }
         sqlx::query(
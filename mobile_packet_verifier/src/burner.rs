@@ -1,10 +1,17 @@
 use chrono::{DateTime, Utc};
-use file_store::{file_sink::FileSinkClient, traits::TimestampEncode};
+use file_store::{
+    file_sink::FileSinkClient,
+    mobile_transfer::{
+        InvalidDataTransferReason, InvalidDataTransferSession, InvalidDataTransferSessionV1,
+    },
+    traits::TimestampEncode,
+};
 use helium_crypto::PublicKeyBinary;
 use helium_proto::services::packet_verifier::ValidDataTransferSession;
+use payer_alerts::PayerAlerts;
 use solana::SolanaNetwork;
 use sqlx::{FromRow, Pool, Postgres};
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 #[derive(FromRow)]
 pub struct DataTransferSession {
@@ -16,29 +23,54 @@ pub struct DataTransferSession {
     last_timestamp: DateTime<Utc>,
 }
 
-#[derive(Default)]
-pub struct PayerTotals {
-    total_dcs: u64,
-    sessions: Vec<DataTransferSession>,
+impl DataTransferSession {
+    fn num_dcs(&self) -> u64 {
+        bytes_to_dc(self.uploaded_bytes as u64 + self.downloaded_bytes as u64)
+    }
 }
 
-impl PayerTotals {
-    fn push_sess(&mut self, sess: DataTransferSession) {
-        self.total_dcs += bytes_to_dc(sess.downloaded_bytes as u64 + sess.uploaded_bytes as u64);
-        self.sessions.push(sess);
-    }
+/// Split the sessions of a payer into the oldest sessions its balance
+/// covers and the rest
+fn accept_up_to(
+    mut sessions: Vec<DataTransferSession>,
+    balance: u64,
+) -> (Vec<DataTransferSession>, Vec<DataTransferSession>) {
+    sessions.sort_by_key(|session| session.first_timestamp);
+    let mut remaining = balance;
+    let covered = sessions
+        .iter()
+        .take_while(|session| {
+            let num_dcs = session.num_dcs();
+            let fits = num_dcs <= remaining;
+            if fits {
+                remaining -= num_dcs;
+            }
+            fits
+        })
+        .count();
+    let rejected = sessions.split_off(covered);
+    (sessions, rejected)
 }
 
 pub struct Burner<S> {
     valid_sessions: FileSinkClient,
+    invalid_sessions: FileSinkClient,
     solana: S,
+    payer_alerts: Option<Arc<PayerAlerts>>,
 }
 
 impl<S> Burner<S> {
-    pub fn new(valid_sessions: FileSinkClient, solana: S) -> Self {
+    pub fn new(
+        valid_sessions: FileSinkClient,
+        invalid_sessions: FileSinkClient,
+        solana: S,
+        payer_alerts: Option<PayerAlerts>,
+    ) -> Self {
         Self {
             valid_sessions,
+            invalid_sessions,
             solana,
+            payer_alerts: payer_alerts.map(Arc::new),
         }
     }
 }
@@ -65,38 +97,51 @@ where
                 .await?;
 
         // Fetch all of the sessions and group by the payer
-        let mut payer_totals = HashMap::<PublicKeyBinary, PayerTotals>::new();
+        let mut payer_sessions = HashMap::<PublicKeyBinary, Vec<DataTransferSession>>::new();
         for session in sessions.into_iter() {
-            payer_totals
+            payer_sessions
                 .entry(session.payer.clone())
                 .or_default()
-                .push_sess(session);
+                .push(session);
         }
 
-        for (
-            payer,
-            PayerTotals {
-                total_dcs,
-                sessions,
-            },
-        ) in payer_totals.into_iter()
-        {
-            tracing::info!(%total_dcs, %payer, "Burning DC");
-
-            if self
-                .solana
-                .burn_data_credits(&payer, total_dcs)
-                .await
-                .is_err()
-            {
-                // We have failed to burn data credits:
-                metrics::counter!("burned", total_dcs, "payer" => payer.to_string(), "success" => "false");
-                continue;
-            }
+        for (payer, sessions) in payer_sessions.into_iter() {
+            // Only burn for the sessions the payer's balance covers
+            let balance = match self.solana.payer_balance(&payer).await {
+                Ok(balance) => balance,
+                Err(err) => {
+                    // The sessions are retried at the next burn:
+                    tracing::error!(%payer, "Failed to fetch payer balance: {err}");
+                    continue;
+                }
+            };
+            let (accepted, rejected) = accept_up_to(sessions, balance);
+            let total_dcs: u64 = accepted.iter().map(DataTransferSession::num_dcs).sum();
+
+            if total_dcs > 0 {
+                tracing::info!(%total_dcs, %payer, "Burning DC");
+
+                if let Err(err) = self.solana.burn_data_credits(&payer, total_dcs).await {
+                    // We have failed to burn data credits, the sessions are
+                    // retried at the next burn:
+                    tracing::error!(%total_dcs, %payer, "Failed to burn DC: {err}");
+                    metrics::counter!("burned", total_dcs, "payer" => payer.to_string(), "success" => "false");
+                    continue;
+                }
 
-            // We succesfully managed to burn data credits:
+                // We succesfully managed to burn data credits:
+
+                metrics::counter!("burned", total_dcs, "payer" => payer.to_string(), "success" => "true");
+            }
 
-            metrics::counter!("burned", total_dcs, "payer" => payer.to_string(), "success" => "true");
+            if !rejected.is_empty() {
+                tracing::warn!(
+                    %balance,
+                    %payer,
+                    "Insufficient balance for {} sessions",
+                    rejected.len()
+                );
+            }
 
             // Delete from the data transfer session and write out to S3
 
@@ -105,9 +150,8 @@ where
                 .execute(pool)
                 .await?;
 
-            for session in sessions {
-                let num_dcs =
-                    bytes_to_dc(session.uploaded_bytes as u64 + session.downloaded_bytes as u64);
+            for session in accepted {
+                let num_dcs = session.num_dcs();
                 self.valid_sessions
                     .write(
                         ValidDataTransferSession {
@@ -125,17 +169,43 @@ where
                     .await?;
             }
 
+            for session in rejected {
+                let num_dcs = session.num_dcs();
+                self.invalid_sessions
+                    .write(
+                        InvalidDataTransferSessionV1::from(InvalidDataTransferSession {
+                            pub_key: session.pub_key,
+                            payer: session.payer,
+                            upload_bytes: session.uploaded_bytes as u64,
+                            download_bytes: session.downloaded_bytes as u64,
+                            num_dcs,
+                            first_timestamp: session.first_timestamp,
+                            last_timestamp: session.last_timestamp,
+                            reason: InvalidDataTransferReason::InsufficientBalance,
+                        }),
+                        &[],
+                    )
+                    .await?;
+            }
+            self.invalid_sessions.commit().await?;
+
             // Fetch the balance after
 
-            metrics::gauge!(
-                "balance",
-                self
-                    .solana
-                    .payer_balance(&payer)
-                    .await
-                    .map_err(BurnError::SolanaError)? as f64,
-                "payer" => payer.to_string()
-            );
+            let balance = self
+                .solana
+                .payer_balance(&payer)
+                .await
+                .map_err(BurnError::SolanaError)?;
+            metrics::gauge!("balance", balance as f64, "payer" => payer.to_string());
+
+            if let Some(payer_alerts) = &self.payer_alerts {
+                // Delivering a warning may wait on the webhook, which must
+                // not hold up burning for the remaining payers
+                let payer_alerts = payer_alerts.clone();
+                tokio::spawn(async move {
+                    payer_alerts.observe(None, &payer, balance).await;
+                });
+            }
         }
 
         Ok(())
@@ -144,8 +214,78 @@ where
 
 const BYTES_PER_DC: u64 = 20_000;
 
-fn bytes_to_dc(bytes: u64) -> u64 {
+pub fn bytes_to_dc(bytes: u64) -> u64 {
     let bytes = bytes.max(BYTES_PER_DC);
     // Integer div/ceil from: https://stackoverflow.com/a/2745086
     (bytes + BYTES_PER_DC - 1) / BYTES_PER_DC
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn session(uploaded_bytes: i64, first_timestamp: DateTime<Utc>) -> DataTransferSession {
+        DataTransferSession {
+            pub_key: PublicKeyBinary::from(vec![1]),
+            payer: PublicKeyBinary::from(vec![0]),
+            uploaded_bytes,
+            downloaded_bytes: 0,
+            first_timestamp,
+            last_timestamp: first_timestamp,
+        }
+    }
+
+    fn first_timestamps(sessions: &[DataTransferSession]) -> Vec<DateTime<Utc>> {
+        sessions
+            .iter()
+            .map(|session| session.first_timestamp)
+            .collect()
+    }
+
+    #[test]
+    fn balance_covers_all_sessions() {
+        let now = Utc::now();
+        let sessions = vec![session(20_000, now), session(40_000, now)];
+        let (accepted, rejected) = accept_up_to(sessions, 3);
+        assert_eq!(accepted.len(), 2);
+        assert!(rejected.is_empty());
+    }
+
+    #[test]
+    fn oldest_sessions_are_accepted_first() {
+        let now = Utc::now();
+        let older = now - Duration::hours(1);
+        let sessions = vec![session(20_000, now), session(20_000, older)];
+        let (accepted, rejected) = accept_up_to(sessions, 1);
+        assert_eq!(first_timestamps(&accepted), vec![older]);
+        assert_eq!(first_timestamps(&rejected), vec![now]);
+    }
+
+    #[test]
+    fn zero_balance_rejects_all_sessions() {
+        let now = Utc::now();
+        let sessions = vec![session(20_000, now), session(0, now)];
+        let (accepted, rejected) = accept_up_to(sessions, 0);
+        assert!(accepted.is_empty());
+        assert_eq!(rejected.len(), 2);
+    }
+
+    #[test]
+    fn session_larger_than_remaining_balance_is_rejected() {
+        let now = Utc::now();
+        let sessions = vec![
+            session(20_000, now - Duration::hours(2)),
+            session(100_000, now - Duration::hours(1)),
+            session(20_000, now),
+        ];
+        // The second session needs 5 DC with only 2 left, and the newer
+        // session behind it isn't accepted in its place
+        let (accepted, rejected) = accept_up_to(sessions, 3);
+        assert_eq!(first_timestamps(&accepted), vec![now - Duration::hours(2)]);
+        assert_eq!(
+            first_timestamps(&rejected),
+            vec![now - Duration::hours(1), now]
+        );
+    }
+}
@@ -3,12 +3,14 @@ use anyhow::{bail, Error, Result};
 use chrono::{TimeZone, Utc};
 use file_store::{
     file_info_poller::{FileInfoStream, LookbackBehavior},
+    file_sink::FileSinkClient,
     file_source, file_upload,
     mobile_session::DataTransferSessionIngestReport,
     FileSinkBuilder, FileStore, FileType,
 };
 use futures_util::TryFutureExt;
 use mobile_config::Client;
+use payer_alerts::PayerAlerts;
 use solana::{SolanaNetwork, SolanaRpc};
 use sqlx::{Pool, Postgres};
 use tokio::{
@@ -23,6 +25,7 @@ pub struct Daemon<S> {
     reports: Receiver<FileInfoStream<DataTransferSessionIngestReport>>,
     burn_period: Duration,
     config_client: Client,
+    invalid_sessions: FileSinkClient,
 }
 
 impl<S> Daemon<S> {
@@ -32,6 +35,7 @@ impl<S> Daemon<S> {
         reports: Receiver<FileInfoStream<DataTransferSessionIngestReport>>,
         burner: Burner<S>,
         config_client: Client,
+        invalid_sessions: FileSinkClient,
     ) -> Self {
         Self {
             pool,
@@ -39,6 +43,7 @@ impl<S> Daemon<S> {
             reports,
             burn_period: Duration::from_secs(60 * 60 * settings.burn_period as u64),
             config_client,
+            invalid_sessions,
         }
     }
 }
@@ -60,8 +65,9 @@ where
                     let ts = file.file_info.timestamp;
                     let mut transaction = self.pool.begin().await?;
                     let reports = file.into_stream(&mut transaction).await?;
-                    crate::accumulate::accumulate_sessions(&mut self.config_client, &mut transaction, ts, reports).await?;
+                    crate::accumulate::accumulate_sessions(&mut self.config_client, &mut transaction, &self.invalid_sessions, ts, reports).await?;
                     transaction.commit().await?;
+                    self.invalid_sessions.commit().await?;
                 },
                 _ = sleep_until(burn_time) => {
                     // It's time to burn
@@ -95,7 +101,7 @@ impl Cmd {
             .database
             .connect("mobile-packet-verifier", shutdown_listener.clone())
             .await?;
-        sqlx::migrate!().run(&pool).await?;
+        db_store::schema::migrate(&pool, &sqlx::migrate!()).await?;
 
         // Set up the solana network:
         let solana = if settings.enable_solana_integration {
@@ -132,7 +138,43 @@ impl Cmd {
         .create()
         .await?;
 
-        let burner = Burner::new(valid_sessions, solana);
+        let (invalid_sessions, mut invalid_sessions_server) = FileSinkBuilder::new(
+            FileType::InvalidDataTransferSession,
+            store_base_path,
+            concat!(env!("CARGO_PKG_NAME"), "_invalid_data_transfer_sessions"),
+            shutdown_listener.clone(),
+        )
+        .deposits(Some(file_upload_tx.clone()))
+        .auto_commit(false)
+        .create()
+        .await?;
+
+        let (payer_alerts, alerts_sink_server) = match &settings.payer_alerts {
+            Some(alert_settings) => {
+                let (alerts_sink, alerts_sink_server) = FileSinkBuilder::new(
+                    FileType::PayerLowBalance,
+                    store_base_path,
+                    concat!(env!("CARGO_PKG_NAME"), "_payer_low_balance"),
+                    shutdown_listener.clone(),
+                )
+                .deposits(Some(file_upload_tx.clone()))
+                .auto_commit(false)
+                .create()
+                .await?;
+                (
+                    Some(PayerAlerts::from_settings(alert_settings, alerts_sink)?),
+                    Some(alerts_sink_server),
+                )
+            }
+            None => (None, None),
+        };
+
+        let burner = Burner::new(
+            valid_sessions,
+            invalid_sessions.clone(),
+            solana,
+            payer_alerts,
+        );
 
         let file_store = FileStore::from_settings(&settings.ingest).await?;
 
@@ -151,15 +193,31 @@ impl Cmd {
 
         let config_client = Client::from_settings(&settings.config_client)?;
 
-        let daemon = Daemon::new(settings, pool, reports, burner, config_client);
+        let daemon = Daemon::new(
+            settings,
+            pool,
+            reports,
+            burner,
+            config_client,
+            invalid_sessions,
+        );
 
         tokio::try_join!(
             source_join_handle.map_err(Error::from),
             valid_sessions_server.run().map_err(Error::from),
+            invalid_sessions_server.run().map_err(Error::from),
             file_upload.run(&shutdown_listener).map_err(Error::from),
             daemon.run(&shutdown_listener).map_err(Error::from),
             conn_handler.map_err(Error::from),
             sol_balance_monitor.map_err(Error::from),
+            async {
+                match alerts_sink_server {
+                    Some(mut alerts_sink_server) => {
+                        alerts_sink_server.run().await.map_err(Error::from)
+                    }
+                    None => Ok(()),
+                }
+            },
         )?;
 
         Ok(())
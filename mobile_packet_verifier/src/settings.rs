@@ -24,6 +24,9 @@ pub struct Settings {
     pub config_client: mobile_config::ClientSettings,
     #[serde(default = "default_start_after")]
     pub start_after: u64,
+    /// Low balance warnings to payers, checked after each burn. Disabled if
+    /// not set
+    pub payer_alerts: Option<payer_alerts::Settings>,
 }
 
 pub fn default_start_after() -> u64 {
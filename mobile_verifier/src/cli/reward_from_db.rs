@@ -30,7 +30,8 @@ impl Cmd {
 
         tracing::info!("Rewarding shares from the following time range: {start} to {end}");
         let epoch = start..end;
-        let expected_rewards = get_scheduled_tokens_for_poc_and_dc(epoch.end - epoch.start);
+        let expected_rewards =
+            get_scheduled_tokens_for_poc_and_dc(&settings.emission_schedule, &epoch);
 
         let (shutdown_trigger, shutdown_listener) = triggered::trigger();
         let (pool, _join_handle) = settings
@@ -44,7 +45,8 @@ impl Cmd {
 
         let mut total_rewards = 0_u64;
         let mut owner_rewards = HashMap::<_, u64>::new();
-        for reward in reward_shares.into_rewards(Decimal::ZERO, &epoch) {
+        for reward in reward_shares.into_rewards(&settings.emission_schedule, Decimal::ZERO, &epoch)
+        {
             if let Some(proto::mobile_reward_share::Reward::RadioReward(proto::RadioReward {
                 hotspot_key,
                 poc_reward,
@@ -33,7 +33,7 @@ impl Cmd {
             .database
             .connect(env!("CARGO_PKG_NAME"), shutdown_listener.clone())
             .await?;
-        sqlx::migrate!().run(&pool).await?;
+        db_store::schema::migrate(&pool, &sqlx::migrate!()).await?;
 
         let (file_upload_tx, file_upload_rx) = file_upload::message_channel();
         let file_upload =
@@ -136,6 +136,7 @@ impl Cmd {
             valid_speedtests,
         );
 
+        settings.emission_schedule.validate()?;
         let rewarder = Rewarder::new(
             pool.clone(),
             Duration::hours(reward_period_hours),
@@ -144,6 +145,7 @@ impl Cmd {
             reward_manifests,
             price_tracker,
             data_transfer_ingest,
+            settings.emission_schedule.clone(),
         );
 
         tokio::try_join!(
@@ -36,6 +36,7 @@ impl Cli {
 pub enum Cmd {
     Server(server::Cmd),
     RewardFromDb(reward_from_db::Cmd),
+    Migrate(db_store::schema::Cmd),
 }
 
 impl Cmd {
@@ -43,6 +44,13 @@ impl Cmd {
         match self {
             Self::Server(cmd) => cmd.run(&settings).await,
             Self::RewardFromDb(cmd) => cmd.run(&settings).await,
+            Self::Migrate(cmd) => Ok(cmd
+                .run(
+                    &settings.database,
+                    env!("CARGO_PKG_NAME"),
+                    &sqlx::migrate!(),
+                )
+                .await?),
         }
     }
 }
@@ -2,19 +2,18 @@ use crate::{
     heartbeats::HeartbeatReward,
     speedtests::{Average, SpeedtestAverages},
 };
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, TimeZone, Utc};
 use file_store::{mobile_transfer::ValidDataTransferSession, traits::TimestampEncode};
 use futures::{Stream, StreamExt};
 use helium_crypto::PublicKeyBinary;
 use helium_proto::services::poc_mobile as proto;
+use reward_scheduler::{emissions::ScheduleEntry, EmissionSchedule};
 use rust_decimal::prelude::*;
 use rust_decimal_macros::dec;
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::ops::Range;
 
-/// Total tokens emissions pool per 365 days
-const TOTAL_EMISSIONS_POOL: Decimal = dec!(60_000_000_000_000_000);
-
 /// Maximum amount of the total emissions pool allocated for data transfer
 /// rewards
 const MAX_DATA_TRANSFER_REWARDS_PERCENT: Decimal = dec!(0.4);
@@ -25,6 +24,29 @@ const DC_USD_PRICE: Decimal = dec!(0.00001);
 /// Default precision used for rounding
 const DEFAULT_PREC: u32 = 15;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MobileAllocation {
+    /// Rewards to hotspots for proof of coverage and data transfer
+    PocAndDataTransfer,
+}
+
+pub type MobileEmissionSchedule = EmissionSchedule<MobileAllocation>;
+
+/// Emissions in mobile bones from the launch of mobile on solana. Subsequent
+/// halvings and changes to the allocations are added as new entries under a
+/// new version
+pub fn default_emission_schedule() -> MobileEmissionSchedule {
+    EmissionSchedule {
+        version: "mobile-v1".to_string(),
+        entries: vec![ScheduleEntry {
+            start: Utc.with_ymd_and_hms(2023, 4, 18, 0, 0, 0).unwrap(),
+            annual_emissions: dec!(60_000_000_000_000_000),
+            allocations: HashMap::from([(MobileAllocation::PocAndDataTransfer, dec!(0.6))]),
+        }],
+    }
+}
+
 pub struct TransferRewards {
     reward_scale: Decimal,
     rewards: HashMap<PublicKeyBinary, Decimal>,
@@ -46,6 +68,7 @@ impl TransferRewards {
     }
 
     pub async fn from_transfer_sessions(
+        schedule: &MobileEmissionSchedule,
         mobile_bone_price: Decimal,
         transfer_sessions: impl Stream<Item = ValidDataTransferSession>,
         hotspots: &PocShares,
@@ -74,8 +97,7 @@ impl TransferRewards {
             })
             .collect();
 
-        let duration = epoch.end - epoch.start;
-        let total_emissions_pool = get_total_scheduled_tokens(duration);
+        let total_emissions_pool = get_total_scheduled_tokens(schedule, epoch);
 
         // Determine if we need to scale the rewards given for data transfer rewards.
         // Ideally this should never happen, but if the total number of data transfer rewards
@@ -198,14 +220,15 @@ impl PocShares {
             })
     }
 
-    pub fn into_rewards(
+    pub fn into_rewards<'a>(
         self,
+        schedule: &MobileEmissionSchedule,
         transfer_rewards_sum: Decimal,
-        epoch: &'_ Range<DateTime<Utc>>,
-    ) -> impl Iterator<Item = proto::MobileRewardShare> + '_ {
+        epoch: &'a Range<DateTime<Utc>>,
+    ) -> impl Iterator<Item = proto::MobileRewardShare> + 'a {
         let total_shares = self.total_shares();
         let available_poc_rewards =
-            get_scheduled_tokens_for_poc_and_dc(epoch.end - epoch.start) - transfer_rewards_sum;
+            get_scheduled_tokens_for_poc_and_dc(schedule, epoch) - transfer_rewards_sum;
         let poc_rewards_per_share = available_poc_rewards / total_shares;
         let start_period = epoch.start.encode_timestamp();
         let end_period = epoch.end.encode_timestamp();
@@ -241,13 +264,18 @@ impl PocShares {
     }
 }
 
-pub fn get_total_scheduled_tokens(duration: Duration) -> Decimal {
-    (TOTAL_EMISSIONS_POOL / dec!(365) / Decimal::from(Duration::hours(24).num_seconds()))
-        * Decimal::from(duration.num_seconds())
+pub fn get_total_scheduled_tokens(
+    schedule: &MobileEmissionSchedule,
+    epoch: &Range<DateTime<Utc>>,
+) -> Decimal {
+    schedule.emissions(epoch)
 }
 
-pub fn get_scheduled_tokens_for_poc_and_dc(duration: Duration) -> Decimal {
-    get_total_scheduled_tokens(duration) * dec!(0.6)
+pub fn get_scheduled_tokens_for_poc_and_dc(
+    schedule: &MobileEmissionSchedule,
+    epoch: &Range<DateTime<Utc>>,
+) -> Decimal {
+    schedule.allocated(epoch, MobileAllocation::PocAndDataTransfer)
 }
 
 #[cfg(test)]
@@ -306,7 +334,8 @@ mod test {
 
         let now = Utc::now();
         let epoch = (now - Duration::hours(1))..now;
-        let total_rewards = get_scheduled_tokens_for_poc_and_dc(epoch.end - epoch.start);
+        let total_rewards =
+            get_scheduled_tokens_for_poc_and_dc(&default_emission_schedule(), &epoch);
 
         // confirm our hourly rewards add up to expected 24hr amount
         // total_rewards will be in bones
@@ -316,6 +345,7 @@ mod test {
         );
 
         let data_transfer_rewards = TransferRewards::from_transfer_sessions(
+            &default_emission_schedule(),
             dec!(1.0),
             data_transfer_sessions,
             &poc_shares,
@@ -325,8 +355,9 @@ mod test {
 
         assert_eq!(data_transfer_rewards.reward(&owner), dec!(0.00002));
         assert_eq!(data_transfer_rewards.reward_scale(), dec!(1.0));
-        let available_poc_rewards = get_scheduled_tokens_for_poc_and_dc(epoch.end - epoch.start)
-            - data_transfer_rewards.reward_sum;
+        let available_poc_rewards =
+            get_scheduled_tokens_for_poc_and_dc(&default_emission_schedule(), &epoch)
+                - data_transfer_rewards.reward_sum;
         assert_eq!(
             available_poc_rewards,
             total_rewards
@@ -368,6 +399,7 @@ mod test {
         let poc_shares = PocShares { hotspot_shares };
 
         let data_transfer_rewards = TransferRewards::from_transfer_sessions(
+            &default_emission_schedule(),
             dec!(1.0),
             data_transfer_sessions,
             &poc_shares,
@@ -379,8 +411,9 @@ mod test {
         // allotted reward amount for data transfer, which is 40% of the daily tokens. We check to
         // ensure that amount of tokens remaining for POC is no less than 20% of the rewards allocated
         // for POC and data transfer (which is 60% of the daily total emissions).
-        let available_poc_rewards = get_scheduled_tokens_for_poc_and_dc(epoch.end - epoch.start)
-            - data_transfer_rewards.reward_sum;
+        let available_poc_rewards =
+            get_scheduled_tokens_for_poc_and_dc(&default_emission_schedule(), &epoch)
+                - data_transfer_rewards.reward_sum;
         assert_eq!(available_poc_rewards.trunc(), dec!(32_876_712_328_767));
         assert_eq!(
             // Rewards are automatically scaled
@@ -683,7 +716,7 @@ mod test {
         for mobile_reward in PocShares::aggregate(stream::iter(heartbeats).map(Ok), speedtest_avgs)
             .await
             .unwrap()
-            .into_rewards(Decimal::ZERO, &epoch)
+            .into_rewards(&default_emission_schedule(), Decimal::ZERO, &epoch)
         {
             let radio_reward = match mobile_reward.reward {
                 Some(proto::mobile_reward_share::Reward::RadioReward(radio_reward)) => radio_reward,
@@ -764,7 +797,9 @@ mod test {
         let owner_shares = PocShares { hotspot_shares };
         let epoch = now - Duration::hours(1)..now;
         let expected_hotspot = gw1;
-        for mobile_reward in owner_shares.into_rewards(Decimal::ZERO, &epoch) {
+        for mobile_reward in
+            owner_shares.into_rewards(&default_emission_schedule(), Decimal::ZERO, &epoch)
+        {
             let radio_reward = match mobile_reward.reward {
                 Some(proto::mobile_reward_share::Reward::RadioReward(radio_reward)) => radio_reward,
                 _ => unreachable!(),
@@ -1,12 +1,12 @@
 use crate::{
     heartbeats::HeartbeatReward,
     ingest,
-    reward_shares::{PocShares, TransferRewards},
+    reward_shares::{MobileEmissionSchedule, PocShares, TransferRewards},
     speedtests::SpeedtestAverages,
 };
 use anyhow::bail;
 use chrono::{DateTime, Duration, TimeZone, Utc};
-use db_store::meta;
+use db_store::meta::keys;
 use file_store::{file_sink::FileSinkClient, traits::TimestampEncode, FileStore};
 use helium_proto::RewardManifest;
 use price::PriceTracker;
@@ -27,6 +27,7 @@ pub struct Rewarder {
     reward_manifests: FileSinkClient,
     price_tracker: PriceTracker,
     data_transfer_ingest: FileStore,
+    emission_schedule: MobileEmissionSchedule,
 }
 
 impl Rewarder {
@@ -38,6 +39,7 @@ impl Rewarder {
         reward_manifests: FileSinkClient,
         price_tracker: PriceTracker,
         data_transfer_ingest: FileStore,
+        emission_schedule: MobileEmissionSchedule,
     ) -> Self {
         Self {
             pool,
@@ -47,6 +49,7 @@ impl Rewarder {
             reward_manifests,
             price_tracker,
             data_transfer_ingest,
+            emission_schedule,
         }
     }
 
@@ -87,20 +90,22 @@ impl Rewarder {
     }
 
     async fn last_rewarded_end_time(&self) -> db_store::Result<DateTime<Utc>> {
-        Utc.timestamp_opt(meta::fetch(&self.pool, "last_rewarded_end_time").await?, 0)
+        Utc.timestamp_opt(keys::LAST_REWARDED_END_TIME.fetch(&self.pool).await?, 0)
             .single()
             .ok_or(db_store::Error::DecodeError)
     }
 
     async fn next_rewarded_end_time(&self) -> db_store::Result<DateTime<Utc>> {
-        Utc.timestamp_opt(meta::fetch(&self.pool, "next_rewarded_end_time").await?, 0)
+        Utc.timestamp_opt(keys::NEXT_REWARDED_END_TIME.fetch(&self.pool).await?, 0)
             .single()
             .ok_or(db_store::Error::DecodeError)
     }
 
     async fn disable_complete_data_checks_until(&self) -> db_store::Result<DateTime<Utc>> {
         Utc.timestamp_opt(
-            meta::fetch(&self.pool, "disable_complete_data_checks_until").await?,
+            keys::DISABLE_COMPLETE_DATA_CHECKS_UNTIL
+                .fetch(&self.pool)
+                .await?,
             0,
         )
         .single()
@@ -166,6 +171,7 @@ impl Rewarder {
                 / dec!(1_000_000)  // Per Mobile token
                 / dec!(1_000_000); // Per Bone
         let transfer_rewards = TransferRewards::from_transfer_sessions(
+            &self.emission_schedule,
             mobile_bone_price,
             ingest::ingest_valid_data_transfers(&self.data_transfer_ingest, reward_period).await,
             &poc_rewards,
@@ -180,9 +186,11 @@ impl Rewarder {
         };
         metrics::gauge!("data_transfer_rewards_scale", scale);
 
-        for mobile_reward_share in
-            poc_rewards.into_rewards(transfer_rewards.reward_sum(), reward_period)
-        {
+        for mobile_reward_share in poc_rewards.into_rewards(
+            &self.emission_schedule,
+            transfer_rewards.reward_sum(),
+            reward_period,
+        ) {
             self.mobile_rewards
                 .write(mobile_reward_share, [])
                 .await?
@@ -211,6 +219,9 @@ impl Rewarder {
         let next_reward_period = scheduler.next_reward_period();
         save_last_rewarded_end_time(&mut transaction, &next_reward_period.start).await?;
         save_next_rewarded_end_time(&mut transaction, &next_reward_period.end).await?;
+        keys::LAST_REWARDED_EMISSION_SCHEDULE_VERSION
+            .store(&mut transaction, self.emission_schedule.version.clone())
+            .await?;
         transaction.commit().await?;
 
         // now that the db has been purged, safe to write out the manifest
@@ -227,6 +238,11 @@ impl Rewarder {
             .await??;
 
         self.reward_manifests.commit().await?;
+        tracing::info!(
+            "rewarded period ending {} with emission schedule version {}",
+            reward_period.end,
+            self.emission_schedule.version
+        );
         metrics::gauge!(
             "last_rewarded_end_time",
             next_reward_period.start.timestamp() as f64
@@ -239,12 +255,16 @@ async fn save_last_rewarded_end_time(
     exec: impl PgExecutor<'_>,
     value: &DateTime<Utc>,
 ) -> db_store::Result<()> {
-    meta::store(exec, "last_rewarded_end_time", value.timestamp()).await
+    keys::LAST_REWARDED_END_TIME
+        .store(exec, value.timestamp())
+        .await
 }
 
 async fn save_next_rewarded_end_time(
     exec: impl PgExecutor<'_>,
     value: &DateTime<Utc>,
 ) -> db_store::Result<()> {
-    meta::store(exec, "next_rewarded_end_time", value.timestamp()).await
+    keys::NEXT_REWARDED_END_TIME
+        .store(exec, value.timestamp())
+        .await
 }
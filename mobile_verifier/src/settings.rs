@@ -1,3 +1,4 @@
+use crate::reward_shares::{default_emission_schedule, MobileEmissionSchedule};
 use chrono::{DateTime, TimeZone, Utc};
 use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
@@ -16,6 +17,10 @@ pub struct Settings {
     pub rewards: i64,
     #[serde(default = "default_reward_offset_minutes")]
     pub reward_offset_minutes: i64,
+    /// Versioned table of the emissions and their allocations over time.
+    /// Defaults to the schedule built into this release
+    #[serde(default = "default_emission_schedule")]
+    pub emission_schedule: MobileEmissionSchedule,
     pub database: db_store::Settings,
     pub ingest: file_store::Settings,
     pub data_transfer_ingest: file_store::Settings,
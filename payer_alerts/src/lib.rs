@@ -0,0 +1,258 @@
+//! Warnings to the payers of the packet verifiers that their data credit
+//! balance is running low, either below a configured threshold or projected
+//! to run out soon at the rate it has been burned recently.
+use chrono::{DateTime, Duration, Utc};
+use file_store::{file_sink::FileSinkClient, payer_low_balance::PayerLowBalance};
+use helium_crypto::PublicKeyBinary;
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::Mutex;
+
+pub mod notifier;
+
+pub use notifier::{Notifier, NotifyError, WebhookNotifier};
+
+/// Minimum spacing of the balance samples the burn rate is taken over
+const SAMPLE_SPACING_SECS: i64 = 60;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Settings {
+    /// Balance in data credits below which a payer is warned. Default
+    /// 1_000_000
+    #[serde(default = "default_warning_threshold")]
+    pub warning_threshold: u64,
+    /// Thresholds overriding the warning threshold, keyed by the b58 payer
+    /// key
+    #[serde(default)]
+    pub payer_thresholds: HashMap<String, u64>,
+    /// A payer is also warned when its balance is projected to run out
+    /// within this many hours at its recent burn rate. Default 24
+    #[serde(default = "default_empty_warning_hours")]
+    pub empty_warning_hours: i64,
+    /// Window of balance history the burn rate is taken over (in minutes).
+    /// Default 360
+    #[serde(default = "default_burn_rate_window")]
+    pub burn_rate_window: i64,
+    /// Interval at which a warning is repeated while the balance stays low
+    /// (in minutes). Default 360
+    #[serde(default = "default_repeat_interval")]
+    pub repeat_interval: i64,
+    /// Url warnings are posted to as json, if any
+    pub webhook_url: Option<String>,
+}
+
+fn default_warning_threshold() -> u64 {
+    1_000_000
+}
+
+fn default_empty_warning_hours() -> i64 {
+    24
+}
+
+fn default_burn_rate_window() -> i64 {
+    360
+}
+
+fn default_repeat_interval() -> i64 {
+    360
+}
+
+impl Settings {
+    pub fn threshold(&self, payer: &PublicKeyBinary) -> u64 {
+        self.payer_thresholds
+            .get(&payer.to_string())
+            .copied()
+            .unwrap_or(self.warning_threshold)
+    }
+
+    pub fn empty_warning(&self) -> Duration {
+        Duration::hours(self.empty_warning_hours)
+    }
+
+    pub fn burn_rate_window(&self) -> Duration {
+        Duration::minutes(self.burn_rate_window)
+    }
+
+    pub fn repeat_interval(&self) -> Duration {
+        Duration::minutes(self.repeat_interval)
+    }
+}
+
+#[derive(Default)]
+struct PayerHistory {
+    /// Balance samples, oldest first, since the last top up
+    samples: VecDeque<(DateTime<Utc>, u64)>,
+    last_warned: Option<DateTime<Utc>>,
+}
+
+impl PayerHistory {
+    fn record(&mut self, now: DateTime<Utc>, balance: u64, window: Duration) {
+        if matches!(self.samples.back(), Some((_, last)) if balance > *last) {
+            self.samples.clear();
+        }
+        match self.samples.back() {
+            Some((time, _)) if now - *time < Duration::seconds(SAMPLE_SPACING_SECS) => (),
+            _ => self.samples.push_back((now, balance)),
+        }
+        while matches!(self.samples.front(), Some((time, _)) if *time < now - window) {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Data credits burned per hour over the samples
+    fn burn_rate(&self) -> u64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some((first_time, first)), Some((last_time, last))) => {
+                let seconds = (*last_time - *first_time).num_seconds();
+                if seconds > 0 {
+                    (first - last) * 3600 / seconds as u64
+                } else {
+                    0
+                }
+            }
+            _ => 0,
+        }
+    }
+}
+
+pub struct PayerAlerts {
+    settings: Settings,
+    notifiers: Vec<Box<dyn Notifier>>,
+    payers: Mutex<HashMap<PublicKeyBinary, PayerHistory>>,
+}
+
+impl PayerAlerts {
+    pub fn new(settings: Settings, notifiers: Vec<Box<dyn Notifier>>) -> Self {
+        Self {
+            settings,
+            notifiers,
+            payers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Warnings to the configured webhook, if any, and as reports to the
+    /// given sink
+    pub fn from_settings(settings: &Settings, sink: FileSinkClient) -> Result<Self, NotifyError> {
+        let mut notifiers: Vec<Box<dyn Notifier>> = vec![Box::new(sink)];
+        if let Some(url) = &settings.webhook_url {
+            notifiers.push(Box::new(WebhookNotifier::new(url.clone())?));
+        }
+        Ok(Self::new(settings.clone(), notifiers))
+    }
+
+    /// Record the current balance of a payer, warning it if the balance is
+    /// low. Failures to deliver a warning are logged and otherwise ignored
+    pub async fn observe(&self, oui: Option<u64>, payer: &PublicKeyBinary, balance: u64) {
+        let Some(alert) = self.check(Utc::now(), oui, payer, balance).await else {
+            return;
+        };
+        tracing::warn!(
+            "low balance for payer {payer}, oui: {oui:?}, balance: {balance}, burn rate: {}",
+            alert.burn_rate
+        );
+        for notifier in &self.notifiers {
+            if let Err(err) = notifier.notify(&alert).await {
+                tracing::error!("failed to deliver low balance warning for {payer}: {err}");
+            }
+        }
+    }
+
+    async fn check(
+        &self,
+        now: DateTime<Utc>,
+        oui: Option<u64>,
+        payer: &PublicKeyBinary,
+        balance: u64,
+    ) -> Option<PayerLowBalance> {
+        let mut payers = self.payers.lock().await;
+        let history = payers.entry(payer.clone()).or_default();
+        history.record(now, balance, self.settings.burn_rate_window());
+
+        let threshold = self.settings.threshold(payer);
+        let burn_rate = history.burn_rate();
+        let seconds_until_empty = (burn_rate > 0).then(|| balance * 3600 / burn_rate);
+        let running_out = matches!(
+            seconds_until_empty,
+            Some(seconds) if (seconds as i64) < self.settings.empty_warning().num_seconds()
+        );
+        if balance >= threshold && !running_out {
+            history.last_warned = None;
+            return None;
+        }
+        if matches!(history.last_warned, Some(last) if now - last < self.settings.repeat_interval())
+        {
+            return None;
+        }
+        history.last_warned = Some(now);
+
+        Some(PayerLowBalance {
+            timestamp: now,
+            oui,
+            payer: payer.clone(),
+            balance,
+            threshold,
+            burn_rate,
+            seconds_until_empty,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::str::FromStr;
+
+    const PAYER: &str = "112NqN2WWMwtK29PMzRby62fDydBJfsCLkCAf392stdok48ovNT6";
+
+    fn settings() -> Settings {
+        Settings {
+            warning_threshold: 1_000,
+            payer_thresholds: HashMap::new(),
+            empty_warning_hours: 24,
+            burn_rate_window: 360,
+            repeat_interval: 60,
+            webhook_url: None,
+        }
+    }
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap() + Duration::minutes(minutes)
+    }
+
+    #[tokio::test]
+    async fn warns_below_threshold() {
+        let payer = PublicKeyBinary::from_str(PAYER).unwrap();
+        let alerts = PayerAlerts::new(settings(), vec![]);
+
+        assert_eq!(None, alerts.check(at(0), Some(1), &payer, 2_000).await);
+        let alert = alerts.check(at(1), Some(1), &payer, 999).await.unwrap();
+        assert_eq!(1_000, alert.threshold);
+        assert_eq!(Some(1), alert.oui);
+        // not repeated within the repeat interval
+        assert_eq!(None, alerts.check(at(2), Some(1), &payer, 998).await);
+        assert!(alerts.check(at(62), Some(1), &payer, 997).await.is_some());
+
+        // a payer specific threshold
+        let mut settings = settings();
+        settings.payer_thresholds.insert(PAYER.to_string(), 10);
+        let alerts = PayerAlerts::new(settings, vec![]);
+        assert_eq!(None, alerts.check(at(0), None, &payer, 999).await);
+    }
+
+    #[tokio::test]
+    async fn warns_when_running_out() {
+        let payer = PublicKeyBinary::from_str(PAYER).unwrap();
+        let alerts = PayerAlerts::new(settings(), vec![]);
+
+        // burning 12_000 an hour, with 240_000 left runs out in 20 hours
+        assert_eq!(None, alerts.check(at(0), None, &payer, 300_000).await);
+        let alert = alerts.check(at(300), None, &payer, 240_000).await.unwrap();
+        assert_eq!(12_000, alert.burn_rate);
+        assert_eq!(Some(20 * 3600), alert.seconds_until_empty);
+
+        // a top up clears the history and the warning
+        assert_eq!(None, alerts.check(at(301), None, &payer, 1_000_000).await);
+        assert_eq!(None, alerts.check(at(400), None, &payer, 999_000).await);
+    }
+}
@@ -0,0 +1,64 @@
+use async_trait::async_trait;
+use file_store::{
+    file_sink::FileSinkClient,
+    payer_low_balance::{PayerLowBalance, PayerLowBalanceV1},
+};
+use std::time::Duration;
+
+/// Time allowed for the webhook to answer a warning
+const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+
+#[derive(thiserror::Error, Debug)]
+pub enum NotifyError {
+    #[error("webhook error: {0}")]
+    Webhook(#[from] reqwest::Error),
+    #[error("file store error: {0}")]
+    FileStore(#[from] file_store::Error),
+}
+
+/// Destination of low balance warnings
+#[async_trait]
+pub trait Notifier: Send + Sync + 'static {
+    async fn notify(&self, alert: &PayerLowBalance) -> Result<(), NotifyError>;
+}
+
+/// Posts each warning as json to a webhook
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Result<Self, NotifyError> {
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(WEBHOOK_TIMEOUT_SECS))
+                .build()?,
+            url,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, alert: &PayerLowBalance) -> Result<(), NotifyError> {
+        self.client
+            .post(&self.url)
+            .json(alert)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Writes each warning as a `payer_low_balance` report
+#[async_trait]
+impl Notifier for FileSinkClient {
+    async fn notify(&self, alert: &PayerLowBalance) -> Result<(), NotifyError> {
+        self.write(PayerLowBalanceV1::from(alert.clone()), [])
+            .await?;
+        self.commit().await?;
+        Ok(())
+    }
+}
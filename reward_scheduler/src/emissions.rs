@@ -0,0 +1,221 @@
+//! Token emissions over time, as a versioned table of dated entries each
+//! setting the annual emissions and how they are split between allocations
+//! from its start until the start of the next entry. A halving is an entry
+//! halving the annual emissions of the one before it.
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::{collections::HashMap, hash::Hash, ops::Range};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum EmissionScheduleError {
+    #[error("emission schedule {0} has no entries")]
+    Empty(String),
+    #[error("emission schedule {version} entries not in order of start at {start}")]
+    Unordered {
+        version: String,
+        start: DateTime<Utc>,
+    },
+    #[error("emission schedule {version} allocates more than all emissions at {start}")]
+    OverAllocated {
+        version: String,
+        start: DateTime<Utc>,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(bound(deserialize = "A: Deserialize<'de> + Eq + Hash"))]
+pub struct EmissionSchedule<A> {
+    /// Identifies the schedule in the reward manifests of the periods
+    /// rewarded against it. Must change with any change to the entries
+    pub version: String,
+    pub entries: Vec<ScheduleEntry<A>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(bound(deserialize = "A: Deserialize<'de> + Eq + Hash"))]
+pub struct ScheduleEntry<A> {
+    pub start: DateTime<Utc>,
+    /// Tokens emitted per 365 days, in bones
+    pub annual_emissions: Decimal,
+    /// Share of the emissions of each allocation
+    pub allocations: HashMap<A, Decimal>,
+}
+
+impl<A> ScheduleEntry<A>
+where
+    A: Copy + Eq + Hash,
+{
+    fn emissions(&self, duration: Duration) -> Decimal {
+        self.annual_emissions * Decimal::from(duration.num_seconds())
+            / Decimal::from(Duration::days(365).num_seconds())
+    }
+
+    fn share(&self, allocation: A) -> Decimal {
+        self.allocations
+            .get(&allocation)
+            .copied()
+            .unwrap_or(Decimal::ZERO)
+    }
+}
+
+impl<A> EmissionSchedule<A>
+where
+    A: Copy + Eq + Hash,
+{
+    pub fn validate(&self) -> Result<(), EmissionScheduleError> {
+        if self.entries.is_empty() {
+            return Err(EmissionScheduleError::Empty(self.version.clone()));
+        }
+        for (entry, next) in self.entries.iter().zip(self.entries.iter().skip(1)) {
+            if next.start <= entry.start {
+                return Err(EmissionScheduleError::Unordered {
+                    version: self.version.clone(),
+                    start: next.start,
+                });
+            }
+        }
+        for entry in &self.entries {
+            if entry.allocations.values().sum::<Decimal>() > Decimal::ONE {
+                return Err(EmissionScheduleError::OverAllocated {
+                    version: self.version.clone(),
+                    start: entry.start,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Total emissions over the period, across every entry it overlaps
+    pub fn emissions(&self, period: &Range<DateTime<Utc>>) -> Decimal {
+        self.spans(period)
+            .map(|(entry, duration)| entry.emissions(duration))
+            .sum()
+    }
+
+    /// Emissions of an allocation over the period, across every entry it
+    /// overlaps
+    pub fn allocated(&self, period: &Range<DateTime<Utc>>, allocation: A) -> Decimal {
+        self.spans(period)
+            .map(|(entry, duration)| entry.emissions(duration) * entry.share(allocation))
+            .sum()
+    }
+
+    /// Each entry overlapping the period with the duration of the overlap.
+    /// The first entry also covers any time before its start
+    fn spans<'a>(
+        &'a self,
+        period: &'a Range<DateTime<Utc>>,
+    ) -> impl Iterator<Item = (&'a ScheduleEntry<A>, Duration)> + 'a {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, entry)| {
+                let start = if index == 0 {
+                    period.start
+                } else {
+                    entry.start.max(period.start)
+                };
+                let end = self
+                    .entries
+                    .get(index + 1)
+                    .map_or(period.end, |next| next.start.min(period.end));
+                (end > start).then(|| (entry, end - start))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Allocation {
+        Poc,
+        DataTransfer,
+    }
+
+    fn dt(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap()
+    }
+
+    fn schedule() -> EmissionSchedule<Allocation> {
+        EmissionSchedule {
+            version: "test-v1".to_string(),
+            entries: vec![
+                ScheduleEntry {
+                    start: dt(2023, 1, 1, 0),
+                    annual_emissions: dec!(365_000),
+                    allocations: HashMap::from([
+                        (Allocation::Poc, dec!(0.3)),
+                        (Allocation::DataTransfer, dec!(0.5)),
+                    ]),
+                },
+                // halving with a shift from data transfer to poc
+                ScheduleEntry {
+                    start: dt(2024, 1, 1, 0),
+                    annual_emissions: dec!(182_500),
+                    allocations: HashMap::from([
+                        (Allocation::Poc, dec!(0.4)),
+                        (Allocation::DataTransfer, dec!(0.4)),
+                    ]),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn emissions_within_an_entry() {
+        let schedule = schedule();
+        assert!(schedule.validate().is_ok());
+
+        let day = dt(2023, 6, 1, 0)..dt(2023, 6, 2, 0);
+        assert_eq!(dec!(1_000), schedule.emissions(&day));
+        assert_eq!(dec!(300), schedule.allocated(&day, Allocation::Poc));
+        assert_eq!(
+            dec!(500),
+            schedule.allocated(&day, Allocation::DataTransfer)
+        );
+
+        // the first entry applies before its start
+        let day = dt(2022, 6, 1, 0)..dt(2022, 6, 2, 0);
+        assert_eq!(dec!(1_000), schedule.emissions(&day));
+    }
+
+    #[test]
+    fn emissions_straddling_entries() {
+        let schedule = schedule();
+        // 18 hours before the halving and 6 hours after
+        let day = dt(2023, 12, 31, 6)..dt(2024, 1, 1, 6);
+        assert_eq!(dec!(750) + dec!(125), schedule.emissions(&day));
+        assert_eq!(
+            dec!(225) + dec!(50),
+            schedule.allocated(&day, Allocation::Poc)
+        );
+        assert_eq!(
+            dec!(375) + dec!(50),
+            schedule.allocated(&day, Allocation::DataTransfer)
+        );
+    }
+
+    #[test]
+    fn invalid_schedules() {
+        let mut unordered = schedule();
+        unordered.entries.reverse();
+        assert!(matches!(
+            unordered.validate(),
+            Err(EmissionScheduleError::Unordered { .. })
+        ));
+
+        let mut over_allocated = schedule();
+        over_allocated.entries[1]
+            .allocations
+            .insert(Allocation::DataTransfer, dec!(0.7));
+        assert!(matches!(
+            over_allocated.validate(),
+            Err(EmissionScheduleError::OverAllocated { .. })
+        ));
+    }
+}
@@ -1,6 +1,10 @@
 use chrono::{DateTime, Duration, Utc};
 use std::ops::Range;
 
+pub mod emissions;
+
+pub use emissions::EmissionSchedule;
+
 #[derive(Debug)]
 pub struct Scheduler {
     pub reward_period_length: Duration,